@@ -1 +1,200 @@
-// Placeholder for future bearoff/race database support.
+mod one_sided;
+mod two_sided;
+
+pub use one_sided::{
+    one_sided_count, one_sided_index, one_sided_unindex, BearoffDistribution, BearoffProbabilities,
+    LazyOneSidedBearoff, OneSidedBearoff,
+};
+pub use two_sided::{CubefulEquities, TwoSidedBearoff};
+
+use crate::dice::Dice;
+use crate::position::{generate_legal_positions, Position, State, O_BAR, X_BAR};
+use std::io::{Read, Write};
+
+/// Highest number of checkers a bearoff database can hold per side.
+pub const MAX_CHECKERS: u8 = 15;
+
+/// Number of points in the home board.
+pub const HOME_POINTS: usize = 6;
+
+/// Length of the roll distributions. No home board needs more than 30 rolls to bear off.
+pub const MAX_ROLLS: usize = 32;
+
+const MAGIC: [u8; 4] = *b"BKBO";
+const VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BearoffError {
+    #[error("bearoff i/o failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid bearoff header")]
+    InvalidHeader,
+    #[error("unsupported bearoff file version {0}")]
+    UnsupportedVersion(u32),
+    #[error("expected {expected} bearoff database, found {found}")]
+    KindMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("bearoff database supports at most {MAX_CHECKERS} checkers, got {0}")]
+    TooManyCheckers(u8),
+}
+
+/// Kind of database stored in a bearoff file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BearoffKind {
+    OneSided,
+    TwoSided,
+}
+
+impl BearoffKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            BearoffKind::OneSided => 1,
+            BearoffKind::TwoSided => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, BearoffError> {
+        match byte {
+            1 => Ok(BearoffKind::OneSided),
+            2 => Ok(BearoffKind::TwoSided),
+            _ => Err(BearoffError::InvalidHeader),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            BearoffKind::OneSided => "one-sided",
+            BearoffKind::TwoSided => "two-sided",
+        }
+    }
+}
+
+/// Header shared by all bearoff files. All numbers are little endian.
+///
+/// | bytes | content                             |
+/// |-------|-------------------------------------|
+/// | 4     | magic `BKBO`                        |
+/// | 4     | format version                      |
+/// | 1     | kind (1 = one-sided, 2 = two-sided) |
+/// | 1     | max checkers                        |
+/// | 1     | flags, bit 0 = cubeful              |
+/// | 1     | reserved                            |
+/// | 4     | number of entries                   |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BearoffHeader {
+    pub(crate) kind: BearoffKind,
+    pub(crate) max_checkers: u8,
+    pub(crate) flags: u8,
+    pub(crate) entries: u32,
+}
+
+impl BearoffHeader {
+    pub(crate) fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), BearoffError> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[self.kind.to_byte(), self.max_checkers, self.flags, 0])?;
+        writer.write_all(&self.entries.to_le_bytes())?;
+        Ok(())
+    }
+
+    pub(crate) fn read_from<R: Read>(
+        reader: &mut R,
+        expected: BearoffKind,
+    ) -> Result<Self, BearoffError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(BearoffError::InvalidHeader);
+        }
+        let version = read_u32(reader)?;
+        if version != VERSION {
+            return Err(BearoffError::UnsupportedVersion(version));
+        }
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes)?;
+        let kind = BearoffKind::from_byte(bytes[0])?;
+        if kind != expected {
+            return Err(BearoffError::KindMismatch {
+                expected: expected.name(),
+                found: kind.name(),
+            });
+        }
+        let max_checkers = bytes[1];
+        if max_checkers > MAX_CHECKERS {
+            return Err(BearoffError::TooManyCheckers(max_checkers));
+        }
+        let entries = read_u32(reader)?;
+        Ok(Self {
+            kind,
+            max_checkers,
+            flags: bytes[2],
+            entries,
+        })
+    }
+}
+
+pub(crate) fn read_u32<R: Read>(reader: &mut R) -> Result<u32, BearoffError> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_f32<R: Read>(reader: &mut R) -> Result<f32, BearoffError> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+/// Home board of the player on roll, pips 1 to 6. `None` if a checker is outside the home board.
+pub fn home_board<const N: u8>(position: &Position<N>) -> Option<[u8; HOME_POINTS]> {
+    if position.x_bar() > 0 || position.pips[7..X_BAR].iter().any(|&p| p > 0) {
+        return None;
+    }
+    let mut board = [0u8; HOME_POINTS];
+    for (i, point) in board.iter_mut().enumerate() {
+        *point = position.pip(i + 1).max(0) as u8;
+    }
+    Some(board)
+}
+
+/// Home board of the opponent, seen from the opponent's side. `None` if a checker is outside.
+pub fn opponent_home_board<const N: u8>(position: &Position<N>) -> Option<[u8; HOME_POINTS]> {
+    if position.o_bar() > 0 || position.pips[O_BAR + 1..19].iter().any(|&p| p < 0) {
+        return None;
+    }
+    let mut board = [0u8; HOME_POINTS];
+    for (i, point) in board.iter_mut().enumerate() {
+        *point = (-position.pip(24 - i)).max(0) as u8;
+    }
+    Some(board)
+}
+
+/// Whether both sides have all their remaining checkers in their home boards.
+pub fn is_bearoff<const N: u8>(position: &Position<N>) -> bool {
+    home_board(position).is_some() && opponent_home_board(position).is_some()
+}
+
+pub(crate) fn board_pips(board: &[u8; HOME_POINTS]) -> usize {
+    board
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| (i + 1) * c as usize)
+        .sum()
+}
+
+/// Home boards reachable with `dice`, using the regular move generation.
+pub(crate) fn child_boards(board: &[u8; HOME_POINTS], dice: &Dice) -> Vec<[u8; HOME_POINTS]> {
+    // A single checker of `o` on the bar doesn't interfere with bearing off.
+    let mut pips = [0i8; 26];
+    for (i, &c) in board.iter().enumerate() {
+        pips[i + 1] = c as i8;
+    }
+    pips[O_BAR] = -1;
+    let position = Position::<MAX_CHECKERS>::try_from(pips).expect("at most 15 checkers");
+    generate_legal_positions(position, dice)
+        .iter()
+        .map(|child| home_board(&child.flip()).expect("bearoff stays in the home board"))
+        .collect()
+}
@@ -0,0 +1,483 @@
+use crate::bearoff::{
+    board_pips, child_boards, home_board, opponent_home_board, read_f32, BearoffError,
+    BearoffHeader, BearoffKind, HOME_POINTS, MAX_CHECKERS, MAX_ROLLS,
+};
+use crate::dice::ALL_21;
+use crate::position::{Position, State};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// `COMPLETIONS[p][c]` is the number of ways to distribute `c` checkers on `p` points.
+const COMPLETIONS: [[usize; MAX_CHECKERS as usize + 1]; HOME_POINTS + 1] = completions();
+
+const fn completions() -> [[usize; MAX_CHECKERS as usize + 1]; HOME_POINTS + 1] {
+    let mut table = [[0usize; MAX_CHECKERS as usize + 1]; HOME_POINTS + 1];
+    table[0][0] = 1;
+    let mut points = 1;
+    while points <= HOME_POINTS {
+        let mut checkers = 0;
+        while checkers <= MAX_CHECKERS as usize {
+            // The first point takes `k` checkers, the other points the rest.
+            let mut k = 0;
+            while k <= checkers {
+                table[points][checkers] += table[points - 1][checkers - k];
+                k += 1;
+            }
+            checkers += 1;
+        }
+        points += 1;
+    }
+    table
+}
+
+/// Number of home boards with at most `max_checkers` checkers, including the empty board.
+pub const fn one_sided_count(max_checkers: u8) -> usize {
+    let mut count = 0;
+    let mut checkers = 0;
+    while checkers <= max_checkers as usize {
+        count += COMPLETIONS[HOME_POINTS][checkers];
+        checkers += 1;
+    }
+    count
+}
+
+/// Index of a home board, `board[0]` being the 1 point.
+///
+/// Boards are ordered by number of checkers first, so the indices of a database for fewer
+/// checkers are a prefix of the indices of a bigger database.
+pub fn one_sided_index(board: &[u8; HOME_POINTS]) -> usize {
+    let checkers: usize = board.iter().map(|&c| c as usize).sum();
+    debug_assert!(checkers <= MAX_CHECKERS as usize);
+    let mut index = if checkers == 0 {
+        0
+    } else {
+        one_sided_count(checkers as u8 - 1)
+    };
+    let mut remaining = checkers;
+    for (i, &on_point) in board.iter().enumerate() {
+        let points_after = HOME_POINTS - 1 - i;
+        for k in 0..on_point as usize {
+            index += COMPLETIONS[points_after][remaining - k];
+        }
+        remaining -= on_point as usize;
+    }
+    index
+}
+
+/// Inverse of [one_sided_index]. Returns `None` for indices beyond 15 checkers.
+pub fn one_sided_unindex(index: usize) -> Option<[u8; HOME_POINTS]> {
+    let mut checkers = 0;
+    while one_sided_count(checkers) <= index {
+        checkers += 1;
+        if checkers > MAX_CHECKERS {
+            return None;
+        }
+    }
+    let mut rank = if checkers == 0 {
+        index
+    } else {
+        index - one_sided_count(checkers - 1)
+    };
+    let mut board = [0u8; HOME_POINTS];
+    let mut remaining = checkers as usize;
+    for (i, on_point) in board.iter_mut().enumerate() {
+        let points_after = HOME_POINTS - 1 - i;
+        let mut k = 0;
+        loop {
+            let n = COMPLETIONS[points_after][remaining - k];
+            if rank < n {
+                break;
+            }
+            rank -= n;
+            k += 1;
+        }
+        *on_point = k as u8;
+        remaining -= k;
+    }
+    Some(board)
+}
+
+/// How many rolls one side needs to bear off, when playing to minimize the expected number of rolls.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BearoffDistribution {
+    /// `rolls[k]` is the probability to bear off all checkers with exactly `k` rolls.
+    pub rolls: [f32; MAX_ROLLS],
+    /// `gammon[k]` is the probability to bear off the first checker with exactly `k` rolls.
+    /// This is what counts for saving the gammon.
+    pub gammon: [f32; MAX_ROLLS],
+}
+
+impl BearoffDistribution {
+    const BORNE_OFF: Self = {
+        let mut rolls = [0.0; MAX_ROLLS];
+        rolls[0] = 1.0;
+        Self {
+            rolls,
+            gammon: rolls,
+        }
+    };
+
+    pub fn mean_rolls(&self) -> f32 {
+        mean(&self.rolls)
+    }
+
+    pub fn mean_gammon_rolls(&self) -> f32 {
+        mean(&self.gammon)
+    }
+}
+
+fn mean(distribution: &[f32; MAX_ROLLS]) -> f32 {
+    distribution
+        .iter()
+        .enumerate()
+        .map(|(k, p)| k as f32 * p)
+        .sum()
+}
+
+/// Probability that `distribution` needs at least `rolls` rolls.
+fn at_least(distribution: &[f32; MAX_ROLLS], rolls: usize) -> f32 {
+    (1.0 - distribution[..rolls].iter().sum::<f32>()).max(0.0)
+}
+
+/// Race result for the player on roll, assuming both sides bear off independently.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BearoffProbabilities {
+    pub win: f32,
+    pub win_gammon: f32,
+    pub lose_gammon: f32,
+}
+
+/// Roll distributions for all home boards up to a certain number of checkers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OneSidedBearoff {
+    max_checkers: u8,
+    entries: Vec<BearoffDistribution>,
+}
+
+impl OneSidedBearoff {
+    /// Generates the database. For 15 checkers these are 54264 home boards.
+    pub fn generate(max_checkers: u8) -> Result<Self, BearoffError> {
+        if max_checkers > MAX_CHECKERS {
+            return Err(BearoffError::TooManyCheckers(max_checkers));
+        }
+        let count = one_sided_count(max_checkers);
+        let boards: Vec<[u8; HOME_POINTS]> = (0..count)
+            .map(|index| one_sided_unindex(index).expect("index within count"))
+            .collect();
+        // Every move reduces the pip count, so we can fill the table by ascending pips.
+        let mut order: Vec<usize> = (0..count).collect();
+        order.sort_by_key(|&index| board_pips(&boards[index]));
+
+        let mut entries = vec![BearoffDistribution::BORNE_OFF; count];
+        let mut means = vec![0.0f32; count];
+        let mut gammon_means = vec![0.0f32; count];
+        for index in order {
+            let board = &boards[index];
+            if board_pips(board) == 0 {
+                continue;
+            }
+            let checkers = board.iter().sum::<u8>();
+            let mut entry = BearoffDistribution {
+                rolls: [0.0; MAX_ROLLS],
+                gammon: [0.0; MAX_ROLLS],
+            };
+            for (dice, weight) in ALL_21 {
+                let probability = weight as f32 / 36.0;
+                let mut best: Option<usize> = None;
+                let mut best_gammon: Option<usize> = None;
+                let mut bears_off = false;
+                for child_board in child_boards(board, &dice) {
+                    let child_index = one_sided_index(&child_board);
+                    if best.is_none_or(|b| means[child_index] < means[b]) {
+                        best = Some(child_index);
+                    }
+                    if child_board.iter().sum::<u8>() < checkers {
+                        bears_off = true;
+                    } else if best_gammon
+                        .is_none_or(|b| gammon_means[child_index] < gammon_means[b])
+                    {
+                        best_gammon = Some(child_index);
+                    }
+                }
+                let best = &entries[best.expect("there is always a legal position")];
+                add_shifted(&mut entry.rolls, &best.rolls, probability);
+                if bears_off {
+                    entry.gammon[1] += probability;
+                } else {
+                    let best_gammon = best_gammon.expect("no bear off means there is a move");
+                    add_shifted(&mut entry.gammon, &entries[best_gammon].gammon, probability);
+                }
+            }
+            means[index] = entry.mean_rolls();
+            gammon_means[index] = entry.mean_gammon_rolls();
+            entries[index] = entry;
+        }
+        Ok(Self {
+            max_checkers,
+            entries,
+        })
+    }
+
+    pub fn max_checkers(&self) -> u8 {
+        self.max_checkers
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn distribution(&self, index: usize) -> Option<&BearoffDistribution> {
+        self.entries.get(index)
+    }
+
+    /// Distribution for a home board, `board[0]` being the 1 point.
+    pub fn board(&self, board: &[u8; HOME_POINTS]) -> Option<&BearoffDistribution> {
+        if board.iter().sum::<u8>() > self.max_checkers {
+            return None;
+        }
+        self.distribution(one_sided_index(board))
+    }
+
+    /// Distribution for the player on roll. `None` if not all checkers are in the home board.
+    pub fn lookup<const N: u8>(&self, position: &Position<N>) -> Option<&BearoffDistribution> {
+        self.board(&home_board(position)?)
+    }
+
+    /// Distribution for the opponent. `None` if not all checkers are in the home board.
+    pub fn lookup_opponent<const N: u8>(
+        &self,
+        position: &Position<N>,
+    ) -> Option<&BearoffDistribution> {
+        self.board(&opponent_home_board(position)?)
+    }
+
+    /// Race probabilities for the player on roll, if both sides are in the database.
+    ///
+    /// Gammons are only possible for a side which hasn't borne off any checker yet.
+    pub fn probabilities<const N: u8>(
+        &self,
+        position: &Position<N>,
+    ) -> Option<BearoffProbabilities> {
+        let player = self.lookup(position)?;
+        let opponent = self.lookup_opponent(position)?;
+        // The player wins with the `k`th roll if the opponent needs at least `k` rolls.
+        let mut win = 0.0;
+        let mut win_gammon = 0.0;
+        let mut lose_gammon = 0.0;
+        for k in 1..MAX_ROLLS {
+            win += player.rolls[k] * at_least(&opponent.rolls, k);
+            if position.o_off() == 0 {
+                win_gammon += player.rolls[k] * at_least(&opponent.gammon, k);
+            }
+            if position.x_off() == 0 {
+                lose_gammon += opponent.rolls[k] * at_least(&player.gammon, k + 1);
+            }
+        }
+        Some(BearoffProbabilities {
+            win,
+            win_gammon,
+            lose_gammon,
+        })
+    }
+
+    /// Probability that the player on roll wins the race.
+    pub fn win_probability<const N: u8>(&self, position: &Position<N>) -> Option<f32> {
+        self.probabilities(position).map(|p| p.win)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), BearoffError> {
+        let header = BearoffHeader {
+            kind: BearoffKind::OneSided,
+            max_checkers: self.max_checkers,
+            flags: 0,
+            entries: self.entries.len() as u32,
+        };
+        header.write_to(writer)?;
+        for entry in &self.entries {
+            for value in entry.rolls.iter().chain(entry.gammon.iter()) {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, BearoffError> {
+        let header = BearoffHeader::read_from(reader, BearoffKind::OneSided)?;
+        Self::read_entries(reader, header)
+    }
+
+    fn read_entries<R: Read>(reader: &mut R, header: BearoffHeader) -> Result<Self, BearoffError> {
+        if header.entries as usize != one_sided_count(header.max_checkers) {
+            return Err(BearoffError::InvalidHeader);
+        }
+        let mut entries = Vec::with_capacity(header.entries as usize);
+        for _ in 0..header.entries {
+            let mut entry = BearoffDistribution::BORNE_OFF;
+            for value in entry.rolls.iter_mut().chain(entry.gammon.iter_mut()) {
+                *value = read_f32(reader)?;
+            }
+            entries.push(entry);
+        }
+        Ok(Self {
+            max_checkers: header.max_checkers,
+            entries,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BearoffError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BearoffError> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+/// A one-sided database file which is only read when it's used for the first time.
+#[derive(Debug)]
+pub struct LazyOneSidedBearoff {
+    path: PathBuf,
+    max_checkers: u8,
+    database: OnceLock<OneSidedBearoff>,
+}
+
+impl LazyOneSidedBearoff {
+    /// Checks the header, but doesn't read the distributions yet.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BearoffError> {
+        let path = path.as_ref().to_path_buf();
+        let mut reader = BufReader::new(File::open(&path)?);
+        let header = BearoffHeader::read_from(&mut reader, BearoffKind::OneSided)?;
+        Ok(Self {
+            path,
+            max_checkers: header.max_checkers,
+            database: OnceLock::new(),
+        })
+    }
+
+    pub fn max_checkers(&self) -> u8 {
+        self.max_checkers
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.database.get().is_some()
+    }
+
+    /// Returns the database, reading the file on the first call.
+    pub fn get(&self) -> Result<&OneSidedBearoff, BearoffError> {
+        if let Some(database) = self.database.get() {
+            return Ok(database);
+        }
+        let database = OneSidedBearoff::load(&self.path)?;
+        Ok(self.database.get_or_init(|| database))
+    }
+}
+
+fn add_shifted(target: &mut [f32; MAX_ROLLS], source: &[f32; MAX_ROLLS], probability: f32) {
+    for k in 1..MAX_ROLLS {
+        target[k] += probability * source[k - 1];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        one_sided_count, one_sided_index, one_sided_unindex, LazyOneSidedBearoff, OneSidedBearoff,
+    };
+    use crate::position::Position;
+
+    #[test]
+    fn count_matches_combinations() {
+        assert_eq!(one_sided_count(0), 1);
+        assert_eq!(one_sided_count(1), 7);
+        assert_eq!(one_sided_count(15), 54264);
+    }
+
+    #[test]
+    fn index_roundtrip() {
+        for index in 0..one_sided_count(15) {
+            let board = one_sided_unindex(index).unwrap();
+            assert_eq!(one_sided_index(&board), index);
+        }
+        assert_eq!(one_sided_unindex(one_sided_count(15)), None);
+    }
+
+    #[test]
+    fn two_checkers_on_six_point() {
+        let db = OneSidedBearoff::generate(2).unwrap();
+        let dist = db.board(&[0, 0, 0, 0, 0, 2]).unwrap();
+        // 3-3, 4-4, 5-5 and 6-6 bear off both checkers.
+        assert!((dist.rolls[1] - 4.0 / 36.0).abs() < 1e-6);
+        assert!((dist.rolls.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        // Doubles from 2-2, rolls with a 6, 5-1 and 4-2 bear off a checker.
+        assert!((dist.gammon[1] - 19.0 / 36.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn single_checker_is_always_off_in_one_roll() {
+        let db = OneSidedBearoff::generate(1).unwrap();
+        let dist = db.board(&[0, 0, 1, 0, 0, 0]).unwrap();
+        assert!((dist.rolls[1] - 1.0).abs() < 1e-6);
+        // Only 2-1 doesn't bear off a checker from the 4 point.
+        let dist = db.board(&[0, 0, 0, 1, 0, 0]).unwrap();
+        assert!((dist.rolls[2] - 2.0 / 36.0).abs() < 1e-6);
+        assert_eq!(db.board(&[0, 0, 0, 2, 0, 0]), None);
+    }
+
+    #[test]
+    fn win_probability_for_race() {
+        let db = OneSidedBearoff::generate(2).unwrap();
+        let position = Position::<2>::from_pairs(&[(1, 1)], &[(19, 2)]);
+        assert!((db.win_probability(&position).unwrap() - 1.0).abs() < 1e-6);
+
+        let position = Position::<2>::from_pairs(&[(6, 2)], &[(24, 1)]);
+        let probabilities = db.probabilities(&position).unwrap();
+        assert!((probabilities.win - 4.0 / 36.0).abs() < 1e-6);
+        assert!(probabilities.win_gammon.abs() < 1e-6);
+
+        let position = Position::<2>::from_pairs(&[(12, 1)], &[(24, 1)]);
+        assert_eq!(db.win_probability(&position), None);
+    }
+
+    #[test]
+    fn gammon_probabilities() {
+        let db = OneSidedBearoff::generate(2).unwrap();
+        // The opponent needs at least two rolls to bear off a checker from the bar point.
+        let position = Position::<2>::from_pairs(&[(1, 2)], &[(18, 2)]);
+        assert_eq!(db.probabilities(&position), None);
+        let position = Position::<2>::from_pairs(&[(1, 2)], &[(19, 2)]);
+        let probabilities = db.probabilities(&position).unwrap();
+        assert!((probabilities.win - 1.0).abs() < 1e-6);
+        assert!((probabilities.win_gammon - 1.0).abs() < 1e-6);
+        assert!(probabilities.lose_gammon.abs() < 1e-6);
+    }
+
+    #[test]
+    fn file_roundtrip() {
+        let db = OneSidedBearoff::generate(3).unwrap();
+        let mut bytes = Vec::new();
+        db.write_to(&mut bytes).unwrap();
+        let read = OneSidedBearoff::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, db);
+    }
+
+    #[test]
+    fn lazy_file_is_read_on_first_use() {
+        let path = std::env::temp_dir().join(format!("bkgm-one-sided-{}.bin", std::process::id()));
+        OneSidedBearoff::generate(2).unwrap().save(&path).unwrap();
+        let lazy = LazyOneSidedBearoff::open(&path).unwrap();
+        assert_eq!(lazy.max_checkers(), 2);
+        assert!(!lazy.is_loaded());
+        assert_eq!(lazy.get().unwrap().len(), 28);
+        assert!(lazy.is_loaded());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,314 @@
+use crate::bearoff::{
+    board_pips, child_boards, home_board, one_sided_count, one_sided_index, one_sided_unindex,
+    opponent_home_board, read_f32, BearoffError, BearoffHeader, BearoffKind, HOME_POINTS,
+    MAX_CHECKERS,
+};
+use crate::dice::ALL_21;
+use crate::position::Position;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const FLAG_CUBEFUL: u8 = 1;
+
+/// Money equities normalized to a cube value of 1, seen from the player on roll.
+///
+/// Gammons are not counted, as in the two-sided databases of GNU Backgammon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CubefulEquities {
+    /// The cube is in the middle.
+    pub centered: f32,
+    /// The player on roll owns the cube.
+    pub owned: f32,
+    /// The opponent owns the cube.
+    pub unavailable: f32,
+}
+
+/// Exact values for all positions where both sides have their checkers in the home board.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoSidedBearoff {
+    max_checkers: u8,
+    /// Cubeless probability that the player on roll wins.
+    win: Vec<f32>,
+    cubeful: Option<Vec<CubefulEquities>>,
+}
+
+impl TwoSidedBearoff {
+    /// Generates the database for up to `max_checkers` checkers per side.
+    ///
+    /// The number of entries grows with the square of the one-sided database, so this is only
+    /// practical for small numbers of checkers: 6 checkers mean 853776 positions.
+    pub fn generate(max_checkers: u8, cubeful: bool) -> Result<Self, BearoffError> {
+        if max_checkers > MAX_CHECKERS {
+            return Err(BearoffError::TooManyCheckers(max_checkers));
+        }
+        let count = one_sided_count(max_checkers);
+        let boards: Vec<[u8; HOME_POINTS]> = (0..count)
+            .map(|index| one_sided_unindex(index).expect("index within count"))
+            .collect();
+        // Moves in the bearoff don't depend on the opponent, so they are generated once per board.
+        let children: Vec<Vec<Vec<usize>>> = boards
+            .iter()
+            .map(|board| {
+                if board_pips(board) == 0 {
+                    return Vec::new();
+                }
+                ALL_21
+                    .iter()
+                    .map(|(dice, _)| {
+                        let mut indices: Vec<usize> = child_boards(board, dice)
+                            .iter()
+                            .map(one_sided_index)
+                            .collect();
+                        indices.sort_unstable();
+                        indices.dedup();
+                        indices
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // Every move reduces the pips of one side, so we can fill the table by ascending pips.
+        let mut order: Vec<usize> = (0..count * count).collect();
+        order.sort_by_key(|&index| {
+            board_pips(&boards[index / count]) + board_pips(&boards[index % count])
+        });
+
+        let mut win = vec![0.0f32; count * count];
+        let mut equities = vec![
+            CubefulEquities {
+                centered: 0.0,
+                owned: 0.0,
+                unavailable: 0.0,
+            };
+            if cubeful { count * count } else { 0 }
+        ];
+        for index in order {
+            let (player, opponent) = (index / count, index % count);
+            if player == 0 {
+                // The player on roll has already borne off, this can't happen in a real game.
+                continue;
+            }
+            if opponent == 0 {
+                // The opponent has already won.
+                win[index] = 0.0;
+                if cubeful {
+                    equities[index] = CubefulEquities {
+                        centered: -1.0,
+                        owned: -1.0,
+                        unavailable: -1.0,
+                    };
+                }
+                continue;
+            }
+            let mut win_sum = 0.0;
+            let mut no_double = [0.0f32; 3];
+            for (roll, (_, weight)) in ALL_21.iter().enumerate() {
+                let probability = *weight as f32 / 36.0;
+                let mut best_win = f32::MIN;
+                let mut best = [f32::MIN; 3];
+                for &child in &children[player][roll] {
+                    if child == 0 {
+                        best_win = 1.0;
+                        best = [1.0; 3];
+                        break;
+                    }
+                    // After the move the opponent is on roll.
+                    let child_index = opponent * count + child;
+                    best_win = best_win.max(1.0 - win[child_index]);
+                    if cubeful {
+                        let child = equities[child_index];
+                        best[0] = best[0].max(-child.centered);
+                        best[1] = best[1].max(-child.unavailable);
+                        best[2] = best[2].max(-child.owned);
+                    }
+                }
+                win_sum += probability * best_win;
+                for (sum, value) in no_double.iter_mut().zip(best) {
+                    *sum += probability * value;
+                }
+            }
+            win[index] = win_sum;
+            if cubeful {
+                let [centered, owned, unavailable] = no_double;
+                // After double and take, the opponent owns a cube of twice the value.
+                let double = (2.0 * unavailable).min(1.0);
+                equities[index] = CubefulEquities {
+                    centered: centered.max(double),
+                    owned: owned.max(double),
+                    unavailable,
+                };
+            }
+        }
+        Ok(Self {
+            max_checkers,
+            win,
+            cubeful: if cubeful { Some(equities) } else { None },
+        })
+    }
+
+    pub fn max_checkers(&self) -> u8 {
+        self.max_checkers
+    }
+
+    pub fn len(&self) -> usize {
+        self.win.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.win.is_empty()
+    }
+
+    pub fn is_cubeful(&self) -> bool {
+        self.cubeful.is_some()
+    }
+
+    fn index<const N: u8>(&self, position: &Position<N>) -> Option<usize> {
+        let player = home_board(position)?;
+        let opponent = opponent_home_board(position)?;
+        if player.iter().sum::<u8>() > self.max_checkers
+            || opponent.iter().sum::<u8>() > self.max_checkers
+        {
+            return None;
+        }
+        let count = one_sided_count(self.max_checkers);
+        Some(one_sided_index(&player) * count + one_sided_index(&opponent))
+    }
+
+    /// Cubeless probability that the player on roll wins.
+    pub fn win_probability<const N: u8>(&self, position: &Position<N>) -> Option<f32> {
+        self.index(position).map(|index| self.win[index])
+    }
+
+    /// Cubeful money equities, if the database was generated with them.
+    pub fn cubeful_equities<const N: u8>(&self, position: &Position<N>) -> Option<CubefulEquities> {
+        let equities = self.cubeful.as_ref()?;
+        self.index(position).map(|index| equities[index])
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), BearoffError> {
+        let header = BearoffHeader {
+            kind: BearoffKind::TwoSided,
+            max_checkers: self.max_checkers,
+            flags: if self.is_cubeful() { FLAG_CUBEFUL } else { 0 },
+            entries: self.win.len() as u32,
+        };
+        header.write_to(writer)?;
+        for (index, win) in self.win.iter().enumerate() {
+            writer.write_all(&win.to_le_bytes())?;
+            if let Some(equities) = &self.cubeful {
+                let e = equities[index];
+                for value in [e.centered, e.owned, e.unavailable] {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, BearoffError> {
+        let header = BearoffHeader::read_from(reader, BearoffKind::TwoSided)?;
+        let count = one_sided_count(header.max_checkers);
+        if header.entries as usize != count * count {
+            return Err(BearoffError::InvalidHeader);
+        }
+        let cubeful = header.flags & FLAG_CUBEFUL != 0;
+        let mut win = Vec::with_capacity(header.entries as usize);
+        let mut equities = Vec::new();
+        for _ in 0..header.entries {
+            win.push(read_f32(reader)?);
+            if cubeful {
+                equities.push(CubefulEquities {
+                    centered: read_f32(reader)?,
+                    owned: read_f32(reader)?,
+                    unavailable: read_f32(reader)?,
+                });
+            }
+        }
+        Ok(Self {
+            max_checkers: header.max_checkers,
+            win,
+            cubeful: if cubeful { Some(equities) } else { None },
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BearoffError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BearoffError> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TwoSidedBearoff;
+    use crate::bearoff::OneSidedBearoff;
+    use crate::position::Position;
+
+    #[test]
+    fn last_roll_positions() {
+        let db = TwoSidedBearoff::generate(2, true).unwrap();
+        let position = Position::<2>::from_pairs(&[(6, 2)], &[(24, 1)]);
+        assert!((db.win_probability(&position).unwrap() - 4.0 / 36.0).abs() < 1e-6);
+        let equities = db.cubeful_equities(&position).unwrap();
+        assert!((equities.centered - (-28.0 / 36.0)).abs() < 1e-6);
+
+        let position = Position::<2>::from_pairs(&[(1, 1)], &[(19, 2)]);
+        assert!((db.win_probability(&position).unwrap() - 1.0).abs() < 1e-6);
+        assert!((db.cubeful_equities(&position).unwrap().centered - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn double_when_favorite_on_last_roll() {
+        let db = TwoSidedBearoff::generate(2, true).unwrap();
+        // Only 2-1 misses, so the player doubles and the opponent passes.
+        let position = Position::<2>::from_pairs(&[(4, 1)], &[(24, 1)]);
+        let equities = db.cubeful_equities(&position).unwrap();
+        assert!((equities.centered - 1.0).abs() < 1e-6);
+        assert!((equities.owned - 1.0).abs() < 1e-6);
+        assert!((equities.unavailable - 32.0 / 36.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn matches_one_sided_when_play_is_forced() {
+        let one_sided = OneSidedBearoff::generate(3).unwrap();
+        let two_sided = TwoSidedBearoff::generate(3, false).unwrap();
+        let position = Position::<3>::from_pairs(&[(6, 1)], &[(19, 1)]);
+        let expected = one_sided.win_probability(&position).unwrap();
+        let actual = two_sided.win_probability(&position).unwrap();
+        assert!((expected - actual).abs() < 1e-6);
+    }
+
+    #[test]
+    fn exact_play_is_at_least_as_good_as_one_sided() {
+        let one_sided = OneSidedBearoff::generate(3).unwrap();
+        let two_sided = TwoSidedBearoff::generate(3, false).unwrap();
+        let position = Position::<3>::from_pairs(&[(6, 2), (5, 1)], &[(24, 1), (20, 1)]);
+        let approximation = one_sided.win_probability(&position).unwrap();
+        let exact = two_sided.win_probability(&position).unwrap();
+        assert!(exact >= approximation - 1e-6);
+        assert!(exact - approximation < 0.01);
+    }
+
+    #[test]
+    fn outside_home_board_is_not_in_database() {
+        let db = TwoSidedBearoff::generate(2, false).unwrap();
+        let position = Position::<2>::from_pairs(&[(7, 1)], &[(24, 1)]);
+        assert_eq!(db.win_probability(&position), None);
+        assert_eq!(db.cubeful_equities(&position), None);
+    }
+
+    #[test]
+    fn file_roundtrip() {
+        let db = TwoSidedBearoff::generate(2, true).unwrap();
+        let mut bytes = Vec::new();
+        db.write_to(&mut bytes).unwrap();
+        let read = TwoSidedBearoff::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, db);
+    }
+}
@@ -0,0 +1,73 @@
+use std::env;
+use std::time::Instant;
+
+use bkgm::bearoff::{OneSidedBearoff, TwoSidedBearoff, MAX_CHECKERS};
+use mimalloc::MiMalloc;
+
+#[global_allocator]
+static GLOBAL: MiMalloc = MiMalloc;
+
+fn parse_string_flag(args: &[String], name: &str) -> Option<String> {
+    args.windows(2).find(|w| w[0] == name).map(|w| w[1].clone())
+}
+
+fn parse_u8_flag(args: &[String], name: &str) -> Option<u8> {
+    args.windows(2)
+        .find(|w| w[0] == name)
+        .and_then(|w| w[1].parse::<u8>().ok())
+}
+
+fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|a| a == name)
+}
+
+fn usage() {
+    println!("Usage: bkgm-bearoff [OPTIONS]");
+    println!("  --checkers <N>   Max checkers per side (default: 15, two-sided: 6)");
+    println!("  --two-sided      Generate the exact two-sided database");
+    println!("  --cubeful        Include cubeful money equities (two-sided only)");
+    println!("  --output <PATH>  Output file (default: bearoff-os<N>.bin or bearoff-ts<N>.bin)");
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if has_flag(&args, "--help") || has_flag(&args, "-h") {
+        usage();
+        return;
+    }
+
+    let two_sided = has_flag(&args, "--two-sided");
+    let cubeful = has_flag(&args, "--cubeful");
+    let checkers = parse_u8_flag(&args, "--checkers")
+        .or_else(|| parse_u8_flag(&args, "-c"))
+        .unwrap_or(if two_sided { 6 } else { MAX_CHECKERS });
+    let default_output = if two_sided {
+        format!("bearoff-ts{checkers}.bin")
+    } else {
+        format!("bearoff-os{checkers}.bin")
+    };
+    let output = parse_string_flag(&args, "--output")
+        .or_else(|| parse_string_flag(&args, "-o"))
+        .unwrap_or(default_output);
+
+    let started = Instant::now();
+    let entries = if two_sided {
+        let database =
+            TwoSidedBearoff::generate(checkers, cubeful).expect("could not generate database");
+        database.save(&output).expect("could not write database");
+        database.len()
+    } else {
+        let database = OneSidedBearoff::generate(checkers).expect("could not generate database");
+        database.save(&output).expect("could not write database");
+        database.len()
+    };
+    println!(
+        "mode={} checkers={} cubeful={} entries={} output={}",
+        if two_sided { "two-sided" } else { "one-sided" },
+        checkers,
+        if cubeful && two_sided { 1 } else { 0 },
+        entries,
+        output
+    );
+    println!("time_s={:.6}", started.elapsed().as_secs_f64());
+}
@@ -0,0 +1,179 @@
+use std::env;
+use std::time::Instant;
+
+use bkgm::dataset::{generate_with, DatasetFormat, DatasetOptions, DatasetWriter};
+use bkgm::evaluator::{PUBEVAL_CONTACT_FILE, PUBEVAL_RACE_FILE};
+use bkgm::mlp::Mlp;
+use bkgm::solver::SolvedTable;
+use bkgm::{
+    Evaluator, Policy, Probabilities, Pubeval, RaceHeuristic, RandomPlayer, Search, SearchOptions,
+    Variant, VariantPosition,
+};
+use mimalloc::MiMalloc;
+
+#[global_allocator]
+static GLOBAL: MiMalloc = MiMalloc;
+
+fn parse_string_flag(args: &[String], name: &str) -> Option<String> {
+    args.windows(2).find(|w| w[0] == name).map(|w| w[1].clone())
+}
+
+fn parse_u64_flag(args: &[String], name: &str) -> Option<u64> {
+    args.windows(2)
+        .find(|w| w[0] == name)
+        .and_then(|w| w[1].parse::<u64>().ok())
+}
+
+fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|a| a == name)
+}
+
+fn usage() {
+    println!("Usage: bkgm-dataset --output <PATH> [OPTIONS]");
+    println!("  --output <PATH>       Dataset file, CSV if it ends in .csv");
+    println!("  --format <NAME>       binary or csv (default: from the output path)");
+    println!("  --variant <NAME>      Variant (default: backgammon)");
+    println!("  --games <N>           Games to play (default: 1000)");
+    println!("  --seed <N>            Dice seed of the first game (default: 0)");
+    println!("  --max-plies <N>       Games longer than this are left out (default: 10000)");
+    println!("  --policy <NAME>       random, race, pubeval or mlp (default: random)");
+    println!("  --weights <PATH>      Weights of the pubeval or mlp policy, pubeval defaults to");
+    println!("                        WT.race and WT.cont of the working directory");
+    println!("  --ply <N>             Search depth of the policy (default: 0)");
+    println!("  --targets             Store the policy's evaluation of each position");
+    println!("  --table <PATH>        Store exact probabilities from a solved hypergammon table");
+    println!("  --quiet               Don't print progress");
+}
+
+fn exit_with(message: String) -> ! {
+    eprintln!("{message}");
+    std::process::exit(2);
+}
+
+/// Exact targets from a solved table.
+struct TableEvaluator(SolvedTable);
+
+impl Evaluator for TableEvaluator {
+    fn evaluate(&self, position: VariantPosition) -> Probabilities {
+        self.0
+            .lookup_variant(position)
+            .expect("the table is checked against the variant")
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if has_flag(&args, "--help") || has_flag(&args, "-h") {
+        usage();
+        return;
+    }
+
+    let defaults = DatasetOptions::default();
+    let variant = match parse_string_flag(&args, "--variant") {
+        Some(name) => name
+            .parse::<Variant>()
+            .unwrap_or_else(|_| exit_with(format!("unknown variant: {name}"))),
+        None => defaults.variant,
+    };
+    let options = DatasetOptions {
+        variant,
+        games: parse_u64_flag(&args, "--games").unwrap_or(defaults.games),
+        seed: parse_u64_flag(&args, "--seed").unwrap_or(defaults.seed),
+        max_plies: parse_u64_flag(&args, "--max-plies")
+            .map_or(defaults.max_plies, |plies| plies as usize),
+    };
+    let output = parse_string_flag(&args, "--output").unwrap_or_else(|| {
+        usage();
+        std::process::exit(2);
+    });
+    let format = match parse_string_flag(&args, "--format").as_deref() {
+        Some("binary") => DatasetFormat::Binary,
+        Some("csv") => DatasetFormat::Csv,
+        Some(name) => exit_with(format!("unknown format: {name}")),
+        None => DatasetFormat::from_path(&output),
+    };
+    let plies = parse_u64_flag(&args, "--ply").unwrap_or(0) as usize;
+    let weights = parse_string_flag(&args, "--weights");
+    let policy_name = parse_string_flag(&args, "--policy").unwrap_or("random".to_string());
+    let quiet = has_flag(&args, "--quiet");
+
+    let evaluator: Option<Box<dyn Evaluator>> = match policy_name.as_str() {
+        "random" => None,
+        "race" => Some(Box::new(RaceHeuristic)),
+        "pubeval" if weights.is_none() => {
+            let pubeval = Pubeval::load_dir(".").unwrap_or_else(|err| {
+                exit_with(format!(
+                    "--policy pubeval needs --weights or {PUBEVAL_RACE_FILE} and \
+                     {PUBEVAL_CONTACT_FILE} in the working directory: {err}"
+                ))
+            });
+            Some(Box::new(pubeval))
+        }
+        "pubeval" | "mlp" => {
+            let path = weights
+                .unwrap_or_else(|| exit_with(format!("--policy {policy_name} needs --weights")));
+            let loaded: Result<Box<dyn Evaluator>, String> = if policy_name == "pubeval" {
+                Pubeval::load(&path)
+                    .map(|pubeval| Box::new(pubeval) as Box<dyn Evaluator>)
+                    .map_err(|err| err.to_string())
+            } else {
+                Mlp::load(&path)
+                    .map(|mlp| Box::new(mlp) as Box<dyn Evaluator>)
+                    .map_err(|err| err.to_string())
+            };
+            Some(loaded.unwrap_or_else(|err| exit_with(format!("could not load {path}: {err}"))))
+        }
+        name => exit_with(format!("unknown policy: {name}")),
+    };
+    let table = parse_string_flag(&args, "--table").map(|path| {
+        let table = SolvedTable::load(&path)
+            .unwrap_or_else(|err| exit_with(format!("could not load {path}: {err}")));
+        if table.lookup_variant(variant.start_position()).is_none() {
+            exit_with(format!("{path} is not a table of {variant}"));
+        }
+        TableEvaluator(table)
+    });
+    let targets: Option<&dyn Evaluator> = match (&table, &evaluator) {
+        (Some(table), _) => Some(table),
+        (None, Some(evaluator)) if has_flag(&args, "--targets") => Some(evaluator.as_ref()),
+        (None, None) if has_flag(&args, "--targets") => {
+            exit_with("--targets needs an evaluating policy or --table".to_string())
+        }
+        _ => None,
+    };
+
+    let mut random = RandomPlayer::with_seed(options.seed);
+    let mut search = evaluator
+        .as_ref()
+        .map(|evaluator| Search::new(evaluator.as_ref(), SearchOptions::new(plies)));
+    let policy: &mut dyn Policy = match &mut search {
+        Some(search) => search,
+        None => &mut random,
+    };
+
+    let mut writer = DatasetWriter::create(&output, format, variant, targets.is_some())
+        .unwrap_or_else(|err| exit_with(format!("could not create {output}: {err}")));
+    let started = Instant::now();
+    let step = (options.games / 10).max(1);
+    let records = generate_with(&options, policy, targets, &mut writer, |games, records| {
+        if !quiet && (games % step == 0 || games == options.games) {
+            println!(
+                "games={} records={} time_s={:.1}",
+                games,
+                records,
+                started.elapsed().as_secs_f64()
+            );
+        }
+    })
+    .unwrap_or_else(|err| {
+        eprintln!("generation failed: {err}");
+        std::process::exit(1);
+    });
+    writer.finish().unwrap_or_else(|err| {
+        eprintln!("could not write {output}: {err}");
+        std::process::exit(1);
+    });
+
+    println!("variant={variant} records={records} output={output}");
+    println!("time_s={:.3}", started.elapsed().as_secs_f64());
+}
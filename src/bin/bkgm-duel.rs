@@ -0,0 +1,181 @@
+use std::env;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use bkgm::duel::{builtin_player, duel_with, DuelOptions, DuelPlayer, Z_95};
+use bkgm::ubgi::UbgiClient;
+use bkgm::{format_engine_spec, parse_engine_spec, EngineRegistry, Variant};
+use mimalloc::MiMalloc;
+
+#[global_allocator]
+static GLOBAL: MiMalloc = MiMalloc;
+
+fn parse_string_flag(args: &[String], name: &str) -> Option<String> {
+    args.windows(2).find(|w| w[0] == name).map(|w| w[1].clone())
+}
+
+fn parse_u64_flag(args: &[String], name: &str) -> Option<u64> {
+    args.windows(2)
+        .find(|w| w[0] == name)
+        .and_then(|w| w[1].parse::<u64>().ok())
+}
+
+fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|a| a == name)
+}
+
+fn usage() {
+    println!("Usage: bkgm-duel --a <SPEC> --b <SPEC> [OPTIONS]");
+    println!(
+        "  --a <SPEC>, --b <SPEC>  Engines as <program>[:key=value,...], options are set via UBGI"
+    );
+    println!("  --registry <PATH>       Engine config, specs are then <alias>[@<version>][:key=value,...]");
+    println!(
+        "                          Built-in: bkgm-random, bkgm-race, bkgm-pubeval|bkgm-mlp:weights=<PATH>"
+    );
+    println!(
+        "                          bkgm-pubeval reads WT.race and WT.cont of the working directory"
+    );
+    println!("                          without weights");
+    println!("  --variant <NAME>        Variant (default: backgammon)");
+    println!("  --seeds <N>             Dice seeds, each played from both sides (default: 100)");
+    println!("  --seed <N>              First seed (default: 0)");
+    println!("  --match-length <N>      Play matches instead of money games");
+    println!("  --cubeless              Money games without the cube");
+    println!("  --max-plies <N>         Limit of plies per game (default: 10000)");
+    println!("  --timeout-ms <N>        Timeout for each engine reply (default: 30000)");
+    println!("  --quiet                 Don't print progress");
+}
+
+fn spawn(
+    spec_text: &str,
+    registry: Option<&EngineRegistry>,
+    timeout: Duration,
+) -> Box<dyn DuelPlayer> {
+    let spec = parse_engine_spec(spec_text).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    });
+    match builtin_player(&spec) {
+        Ok(Some(player)) => return player,
+        Ok(None) => {}
+        Err(err) => {
+            eprintln!("could not start {spec_text}: {err}");
+            std::process::exit(1);
+        }
+    }
+    if let Some(registry) = registry {
+        return Box::new(registry.launch(&spec, timeout).unwrap_or_else(|err| {
+            eprintln!("could not start {spec_text}: {err}");
+            std::process::exit(1);
+        }));
+    }
+    let mut client =
+        UbgiClient::spawn_command(Command::new(&spec.alias), timeout).unwrap_or_else(|err| {
+            eprintln!("could not start {}: {err}", spec.alias);
+            std::process::exit(1);
+        });
+    if let Err(err) = client.apply_spec(&spec) {
+        eprintln!("could not configure {}: {err}", format_engine_spec(&spec));
+        std::process::exit(1);
+    }
+    Box::new(client)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if has_flag(&args, "--help") || has_flag(&args, "-h") {
+        usage();
+        return;
+    }
+    let (Some(spec_a), Some(spec_b)) = (
+        parse_string_flag(&args, "--a"),
+        parse_string_flag(&args, "--b"),
+    ) else {
+        usage();
+        std::process::exit(2);
+    };
+
+    let defaults = DuelOptions::default();
+    let variant = match parse_string_flag(&args, "--variant") {
+        Some(name) => name.parse::<Variant>().unwrap_or_else(|_| {
+            eprintln!("unknown variant: {name}");
+            std::process::exit(2);
+        }),
+        None => defaults.variant,
+    };
+    let options = DuelOptions {
+        variant,
+        seeds: parse_u64_flag(&args, "--seeds").map_or(defaults.seeds, |n| n as usize),
+        first_seed: parse_u64_flag(&args, "--seed").unwrap_or(defaults.first_seed),
+        match_length: parse_u64_flag(&args, "--match-length").map(|n| n as u32),
+        cubeful: !has_flag(&args, "--cubeless"),
+        max_plies: parse_u64_flag(&args, "--max-plies").map_or(defaults.max_plies, |n| n as usize),
+    };
+    let timeout = Duration::from_millis(parse_u64_flag(&args, "--timeout-ms").unwrap_or(30_000));
+    let quiet = has_flag(&args, "--quiet");
+    let registry = parse_string_flag(&args, "--registry").map(|path| {
+        EngineRegistry::load(&path).unwrap_or_else(|err| {
+            eprintln!("could not load {path}: {err}");
+            std::process::exit(2);
+        })
+    });
+
+    let mut a = spawn(&spec_a, registry.as_ref(), timeout);
+    let mut b = spawn(&spec_b, registry.as_ref(), timeout);
+    println!("a={spec_a} b={spec_b}");
+
+    let started = Instant::now();
+    let result = duel_with(a.as_mut(), b.as_mut(), &options, |report| {
+        if quiet || report.games.len() % 100 != 0 {
+            return;
+        }
+        let stats = report.stats();
+        println!(
+            "games={} ppg={:+.4} se={:.4} time_s={:.1}",
+            stats.games,
+            stats.points_per_game(),
+            stats.points_error,
+            started.elapsed().as_secs_f64()
+        );
+    });
+    let report = result.unwrap_or_else(|err| {
+        eprintln!("duel failed: {err}");
+        std::process::exit(1);
+    });
+
+    let stats = report.stats();
+    let (low, high) = stats.points_interval(Z_95);
+    println!(
+        "games={} points={:+.1} ppg={:+.4} ci95=[{:+.4},{:+.4}]",
+        stats.games,
+        stats.points,
+        stats.points_per_game(),
+        low,
+        high
+    );
+    println!(
+        "a: win={:.2}% gammon={:.2}% backgammon={:.2}%",
+        100.0 * stats.win_rate(),
+        100.0 * stats.gammon_rate(),
+        100.0 * stats.backgammon_rate()
+    );
+    println!(
+        "b: win={:.2}% gammon={:.2}% backgammon={:.2}%",
+        100.0 * (1.0 - stats.win_rate()),
+        100.0 * stats.gammon_loss_rate(),
+        100.0 * stats.backgammon_loss_rate()
+    );
+    if stats.matches > 0 {
+        let (low, high) = stats.match_interval(Z_95);
+        println!(
+            "matches={} a_won={} rate={:.2}% ci95=[{:.2}%,{:.2}%]",
+            stats.matches,
+            stats.matches_won,
+            100.0 * stats.match_win_rate(),
+            100.0 * low,
+            100.0 * high
+        );
+    }
+    println!("time_s={:.3}", started.elapsed().as_secs_f64());
+}
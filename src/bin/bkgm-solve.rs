@@ -0,0 +1,106 @@
+use std::env;
+use std::time::Instant;
+
+use bkgm::solver::{SolveOptions, SolvedTable};
+use bkgm::ClassicRules;
+use mimalloc::MiMalloc;
+
+#[global_allocator]
+static GLOBAL: MiMalloc = MiMalloc;
+
+fn parse_string_flag(args: &[String], name: &str) -> Option<String> {
+    args.windows(2).find(|w| w[0] == name).map(|w| w[1].clone())
+}
+
+fn parse_usize_flag(args: &[String], name: &str) -> Option<usize> {
+    args.windows(2)
+        .find(|w| w[0] == name)
+        .and_then(|w| w[1].parse::<usize>().ok())
+}
+
+fn parse_f32_flag(args: &[String], name: &str) -> Option<f32> {
+    args.windows(2)
+        .find(|w| w[0] == name)
+        .and_then(|w| w[1].parse::<f32>().ok())
+}
+
+fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|a| a == name)
+}
+
+fn usage() {
+    println!("Usage: bkgm-solve [OPTIONS]");
+    println!(
+        "  --variant <NAME>        hypergammon2, hypergammon or hypergammon4 (default: hypergammon2)"
+    );
+    println!("                          hypergammon4 needs about 7 GB of memory, 11 GB cubeful");
+    println!("  --output <PATH>         Output file (default: <variant>.bin)");
+    println!("  --tolerance <F>         Stop when values change less than this (default: 1e-6)");
+    println!("  --max-iterations <N>   Maximum number of iterations (default: 1000)");
+    println!("  --threads <N>           Worker threads (default: available parallelism)");
+    println!("  --cubeful               Also solve cubeful money equities");
+    println!("  --jacoby                Use the Jacoby rule for cubeful equities");
+    println!("  --quiet                 Don't print progress");
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if has_flag(&args, "--help") || has_flag(&args, "-h") {
+        usage();
+        return;
+    }
+
+    let defaults = SolveOptions::default();
+    let options = SolveOptions {
+        tolerance: parse_f32_flag(&args, "--tolerance").unwrap_or(defaults.tolerance),
+        max_iterations: parse_usize_flag(&args, "--max-iterations")
+            .unwrap_or(defaults.max_iterations),
+        threads: parse_usize_flag(&args, "--threads").unwrap_or(defaults.threads),
+        cubeful: has_flag(&args, "--cubeful"),
+        jacoby: has_flag(&args, "--jacoby"),
+    };
+    let variant = parse_string_flag(&args, "--variant").unwrap_or("hypergammon2".to_string());
+    let output = parse_string_flag(&args, "--output")
+        .or_else(|| parse_string_flag(&args, "-o"))
+        .unwrap_or(format!("{variant}.bin"));
+    let quiet = has_flag(&args, "--quiet");
+
+    let started = Instant::now();
+    let progress = |iteration: usize, delta: f32| {
+        if !quiet {
+            println!(
+                "iteration={} delta={:.3e} time_s={:.3}",
+                iteration,
+                delta,
+                started.elapsed().as_secs_f64()
+            );
+        }
+    };
+    let table = match variant.as_str() {
+        "hypergammon2" => SolvedTable::solve_with::<ClassicRules, 2, _>(&options, progress),
+        "hypergammon" | "hypergammon3" => {
+            SolvedTable::solve_with::<ClassicRules, 3, _>(&options, progress)
+        }
+        "hypergammon4" => SolvedTable::solve_with::<ClassicRules, 4, _>(&options, progress),
+        "hypergammon5" => {
+            eprintln!("{variant} has too many positions to solve in memory");
+            std::process::exit(2);
+        }
+        _ => {
+            eprintln!("unknown variant: {variant}");
+            std::process::exit(2);
+        }
+    }
+    .expect("could not solve variant");
+    table.save(&output).expect("could not write table");
+    println!(
+        "variant={} checkers={} cubeful={} jacoby={} entries={} output={}",
+        variant,
+        table.checkers(),
+        if table.is_cubeful() { 1 } else { 0 },
+        if table.is_jacoby() { 1 } else { 0 },
+        table.len(),
+        output
+    );
+    println!("time_s={:.6}", started.elapsed().as_secs_f64());
+}
@@ -0,0 +1,165 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use bkgm::duel::{builtin_player, DuelError, DuelOptions, DuelPlayer};
+use bkgm::tournament::{run_tournament, TournamentFormat, TournamentOptions};
+use bkgm::ubgi::UbgiClient;
+use bkgm::{expand_engine_spec, EngineRegistry, EngineSpec, Variant};
+use mimalloc::MiMalloc;
+
+#[global_allocator]
+static GLOBAL: MiMalloc = MiMalloc;
+
+fn parse_string_flag(args: &[String], name: &str) -> Option<String> {
+    args.windows(2).find(|w| w[0] == name).map(|w| w[1].clone())
+}
+
+fn parse_string_flags(args: &[String], name: &str) -> Vec<String> {
+    args.windows(2)
+        .filter(|w| w[0] == name)
+        .map(|w| w[1].clone())
+        .collect()
+}
+
+fn parse_u64_flag(args: &[String], name: &str) -> Option<u64> {
+    args.windows(2)
+        .find(|w| w[0] == name)
+        .and_then(|w| w[1].parse::<u64>().ok())
+}
+
+fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|a| a == name)
+}
+
+fn usage() {
+    println!("Usage: bkgm-tournament --engine <SPEC> --engine <SPEC> ... [OPTIONS]");
+    println!(
+        "  --engine <SPEC>         Engine as <program>[:key=value,...], options are set via UBGI"
+    );
+    println!("  --registry <PATH>       Engine config, specs are then <alias>[@<version>][:key=value,...]");
+    println!(
+        "                          Built-in: bkgm-random, bkgm-race, bkgm-pubeval|bkgm-mlp:weights=<PATH>"
+    );
+    println!(
+        "                          bkgm-pubeval reads WT.race and WT.cont of the working directory"
+    );
+    println!("                          without weights");
+    println!(
+        "                          Alternatives like ply=1|2|3,top_k=4|8 expand to one engine each"
+    );
+    println!("  --gauntlet              First engine plays all others instead of a round robin");
+    println!("  --variant <NAME>        Variant (default: backgammon)");
+    println!("  --seeds <N>             Dice seeds per pairing, each played from both sides (default: 100)");
+    println!("  --seed <N>              First seed (default: 0)");
+    println!("  --match-length <N>      Play matches instead of money games");
+    println!("  --cubeless              Money games without the cube");
+    println!("  --max-plies <N>         Limit of plies per game (default: 10000)");
+    println!("  --threads <N>           Worker threads (default: available parallelism)");
+    println!("  --results <PATH>        Results file, finished seeds are skipped when resuming");
+    println!("  --timeout-ms <N>        Timeout for each engine reply (default: 30000)");
+}
+
+fn spawn(
+    spec: &EngineSpec,
+    registry: Option<&EngineRegistry>,
+    timeout: Duration,
+) -> Result<Box<dyn DuelPlayer>, DuelError> {
+    if let Some(player) = builtin_player(spec)? {
+        return Ok(player);
+    }
+    if let Some(registry) = registry {
+        let client = registry
+            .launch(spec, timeout)
+            .map_err(|err| DuelError::Engine(err.to_string()))?;
+        return Ok(Box::new(client));
+    }
+    let mut client = UbgiClient::spawn_command(Command::new(&spec.alias), timeout)?;
+    client.apply_spec(spec)?;
+    Ok(Box::new(client))
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if has_flag(&args, "--help") || has_flag(&args, "-h") {
+        usage();
+        return;
+    }
+    let specs: Vec<EngineSpec> = parse_string_flags(&args, "--engine")
+        .iter()
+        .flat_map(|text| {
+            expand_engine_spec(text).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(2);
+            })
+        })
+        .collect();
+    if specs.len() < 2 {
+        usage();
+        std::process::exit(2);
+    }
+
+    let defaults = TournamentOptions::default();
+    let duel_defaults = DuelOptions::default();
+    let variant = match parse_string_flag(&args, "--variant") {
+        Some(name) => name.parse::<Variant>().unwrap_or_else(|_| {
+            eprintln!("unknown variant: {name}");
+            std::process::exit(2);
+        }),
+        None => duel_defaults.variant,
+    };
+    let match_length = parse_u64_flag(&args, "--match-length").map(|n| n as u32);
+    let options = TournamentOptions {
+        format: if has_flag(&args, "--gauntlet") {
+            TournamentFormat::Gauntlet
+        } else {
+            TournamentFormat::RoundRobin
+        },
+        duel: DuelOptions {
+            variant,
+            seeds: parse_u64_flag(&args, "--seeds").map_or(duel_defaults.seeds, |n| n as usize),
+            first_seed: parse_u64_flag(&args, "--seed").unwrap_or(duel_defaults.first_seed),
+            match_length,
+            cubeful: !has_flag(&args, "--cubeless"),
+            max_plies: parse_u64_flag(&args, "--max-plies")
+                .map_or(duel_defaults.max_plies, |n| n as usize),
+        },
+        threads: parse_u64_flag(&args, "--threads").map_or(defaults.threads, |n| n as usize),
+        results: parse_string_flag(&args, "--results").map(PathBuf::from),
+    };
+    let timeout = Duration::from_millis(parse_u64_flag(&args, "--timeout-ms").unwrap_or(30_000));
+    let registry = parse_string_flag(&args, "--registry").map(|path| {
+        EngineRegistry::load(&path).unwrap_or_else(|err| {
+            eprintln!("could not load {path}: {err}");
+            std::process::exit(2);
+        })
+    });
+
+    let started = Instant::now();
+    let results = run_tournament(&specs, &options, |spec| {
+        spawn(spec, registry.as_ref(), timeout)
+    })
+    .unwrap_or_else(|err| {
+        eprintln!("tournament failed: {err}");
+        std::process::exit(1);
+    });
+
+    let elo = results.elo();
+    let fibs = results.fibs();
+    let mut order: Vec<usize> = (0..results.labels.len()).collect();
+    order.sort_by(|&a, &b| elo[b].total_cmp(&elo[a]));
+    println!("rank engine elo fibs");
+    for (rank, &i) in order.iter().enumerate() {
+        println!(
+            "{} {} {:.0} {:.0}",
+            rank + 1,
+            results.labels[i],
+            elo[i],
+            fibs[i]
+        );
+    }
+    println!();
+    print!("{}", results.cross_table());
+    println!("time_s={:.3}", started.elapsed().as_secs_f64());
+}
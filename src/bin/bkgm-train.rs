@@ -0,0 +1,196 @@
+use std::env;
+use std::path::Path;
+use std::time::Instant;
+
+use bkgm::duel::builtin_player;
+use bkgm::features::Encoding;
+use bkgm::mlp::{Activation, Mlp};
+use bkgm::solver::SolvedTable;
+use bkgm::train::{equity_error, TdTrainer, TrainOptions};
+use bkgm::{parse_engine_spec, Variant};
+use mimalloc::MiMalloc;
+
+#[global_allocator]
+static GLOBAL: MiMalloc = MiMalloc;
+
+fn parse_string_flag(args: &[String], name: &str) -> Option<String> {
+    args.windows(2).find(|w| w[0] == name).map(|w| w[1].clone())
+}
+
+fn parse_u64_flag(args: &[String], name: &str) -> Option<u64> {
+    args.windows(2)
+        .find(|w| w[0] == name)
+        .and_then(|w| w[1].parse::<u64>().ok())
+}
+
+fn parse_f32_flag(args: &[String], name: &str) -> Option<f32> {
+    args.windows(2)
+        .find(|w| w[0] == name)
+        .and_then(|w| w[1].parse::<f32>().ok())
+}
+
+fn has_flag(args: &[String], name: &str) -> bool {
+    args.iter().any(|a| a == name)
+}
+
+fn usage() {
+    println!("Usage: bkgm-train [OPTIONS]");
+    println!("  --variant <NAME>          Variant (default: hypergammon)");
+    println!("  --encoding <NAME>         td-gammon, gnubg, gnubg-contact, gnubg-race or wildbg (default: td-gammon)");
+    println!("  --hidden <N,...>          Hidden layer sizes (default: 40)");
+    println!("  --relu                    ReLU hidden layers instead of sigmoid");
+    println!("  --init-seed <N>           Seed of the initial weights (default: 0)");
+    println!("  --alpha <F>               Learning rate (default: 0.1)");
+    println!("  --lambda <F>              Trace decay (default: 0.7)");
+    println!("  --games <N>               Games to train in total (default: 10000)");
+    println!("  --seed <N>                Dice seed of the first game (default: 0)");
+    println!("  --checkpoint <PATH>       Checkpoint file, resumed from if it exists");
+    println!("  --checkpoint-every <N>    Games between checkpoints (default: 1000)");
+    println!("  --output <PATH>           Trained network (default: <variant>.bknn)");
+    println!(
+        "  --benchmark-every <N>     Games between benchmark duels, 0 for none (default: 1000)"
+    );
+    println!("  --benchmark-seeds <N>     Dice seeds per benchmark (default: 100)");
+    println!("  --baseline <SPEC>         Built-in benchmark opponent (default: bkgm-race)");
+    println!("  --table <PATH>            Solved hypergammon table, reports the equity error");
+    println!("  --quiet                   Don't print progress");
+}
+
+fn exit_with(message: String) -> ! {
+    eprintln!("{message}");
+    std::process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if has_flag(&args, "--help") || has_flag(&args, "-h") {
+        usage();
+        return;
+    }
+
+    let defaults = TrainOptions::default();
+    let variant = match parse_string_flag(&args, "--variant") {
+        Some(name) => name
+            .parse::<Variant>()
+            .unwrap_or_else(|_| exit_with(format!("unknown variant: {name}"))),
+        None => defaults.variant,
+    };
+    let options = TrainOptions {
+        variant,
+        alpha: parse_f32_flag(&args, "--alpha").unwrap_or(defaults.alpha),
+        lambda: parse_f32_flag(&args, "--lambda").unwrap_or(defaults.lambda),
+        seed: parse_u64_flag(&args, "--seed").unwrap_or(defaults.seed),
+        max_plies: defaults.max_plies,
+    };
+    let encoding = match parse_string_flag(&args, "--encoding") {
+        Some(name) => name
+            .parse::<Encoding>()
+            .unwrap_or_else(|_| exit_with(format!("unknown encoding: {name}"))),
+        None => Encoding::TdGammon,
+    };
+    let hidden: Vec<usize> = parse_string_flag(&args, "--hidden")
+        .unwrap_or("40".to_string())
+        .split(',')
+        .map(|size| {
+            size.trim()
+                .parse()
+                .unwrap_or_else(|_| exit_with(format!("invalid layer size: {size}")))
+        })
+        .collect();
+    let activation = if has_flag(&args, "--relu") {
+        Activation::Relu
+    } else {
+        Activation::Sigmoid
+    };
+    let games = parse_u64_flag(&args, "--games").unwrap_or(10_000);
+    let checkpoint = parse_string_flag(&args, "--checkpoint");
+    let checkpoint_every = parse_u64_flag(&args, "--checkpoint-every").unwrap_or(1000);
+    let output = parse_string_flag(&args, "--output").unwrap_or(format!("{variant}.bknn"));
+    let benchmark_every = parse_u64_flag(&args, "--benchmark-every").unwrap_or(1000);
+    let benchmark_seeds = parse_u64_flag(&args, "--benchmark-seeds").unwrap_or(100) as usize;
+    let baseline_spec = parse_string_flag(&args, "--baseline").unwrap_or("bkgm-race".to_string());
+    let quiet = has_flag(&args, "--quiet");
+
+    let mut baseline = parse_engine_spec(&baseline_spec)
+        .map_err(|err| err.to_string())
+        .and_then(|spec| builtin_player(&spec).map_err(|err| err.to_string()))
+        .unwrap_or_else(|err| exit_with(format!("invalid baseline {baseline_spec}: {err}")))
+        .unwrap_or_else(|| exit_with(format!("{baseline_spec} is not a built-in player")));
+    let table = parse_string_flag(&args, "--table").map(|path| {
+        SolvedTable::load(&path)
+            .unwrap_or_else(|err| exit_with(format!("could not load {path}: {err}")))
+    });
+
+    let mut trainer = match &checkpoint {
+        Some(path) if Path::new(path).exists() => TdTrainer::load(path, options.clone())
+            .unwrap_or_else(|err| exit_with(format!("could not resume from {path}: {err}"))),
+        _ => TdTrainer::new(
+            Mlp::random(
+                encoding,
+                &hidden,
+                activation,
+                parse_u64_flag(&args, "--init-seed").unwrap_or(0),
+            ),
+            options.clone(),
+        )
+        .expect("random networks have the trained outputs"),
+    };
+    if !quiet {
+        println!(
+            "variant={} encoding={} games={} alpha={} lambda={}",
+            variant,
+            trainer.mlp().encoding(),
+            trainer.games(),
+            options.alpha,
+            options.lambda
+        );
+    }
+
+    let started = Instant::now();
+    let mut plies = 0;
+    while trainer.games() < games {
+        plies += trainer.train_game().unwrap_or_else(|err| {
+            eprintln!("training failed: {err}");
+            std::process::exit(1);
+        });
+        let done = trainer.games();
+        if let Some(path) = &checkpoint {
+            if done % checkpoint_every == 0 || done == games {
+                trainer.save(path).unwrap_or_else(|err| {
+                    eprintln!("could not write {path}: {err}");
+                    std::process::exit(1);
+                });
+            }
+        }
+        if quiet || benchmark_every == 0 || done % benchmark_every != 0 {
+            continue;
+        }
+        let stats = trainer
+            .benchmark(baseline.as_mut(), benchmark_seeds, u64::MAX / 2)
+            .unwrap_or_else(|err| {
+                eprintln!("benchmark failed: {err}");
+                std::process::exit(1);
+            });
+        let error = table.as_ref().map_or(String::new(), |table| {
+            let error = equity_error(trainer.mlp(), table, variant, 100, u64::MAX / 2)
+                .expect("benchmark games finish");
+            format!(" equity_error={error:.4}")
+        });
+        println!(
+            "games={} plies={} ppg={:+.4} se={:.4}{} time_s={:.1}",
+            done,
+            plies,
+            stats.points_per_game(),
+            stats.points_error,
+            error,
+            started.elapsed().as_secs_f64()
+        );
+    }
+
+    trainer.mlp().save(&output).unwrap_or_else(|err| {
+        eprintln!("could not write {output}: {err}");
+        std::process::exit(1);
+    });
+    println!("games={} output={}", trainer.games(), output);
+    println!("time_s={:.3}", started.elapsed().as_secs_f64());
+}
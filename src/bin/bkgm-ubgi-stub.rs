@@ -0,0 +1,67 @@
+//! Minimal UBGI engine which plays the first legal move, or a random one with `engine.seed`.
+//!
+//! It is used to test UBGI clients and tooling without a real engine.
+
+use bkgm::codecs::move_text;
+use bkgm::ubgi::{run_ubgi_stdio, OptionSpec, OptionValue, UbgiEngine, UbgiError, UbgiResult};
+use bkgm::{Dice, Game};
+use mimalloc::MiMalloc;
+
+#[global_allocator]
+static GLOBAL: MiMalloc = MiMalloc;
+
+struct StubEngine {
+    seed: i64,
+    rng: fastrand::Rng,
+}
+
+impl UbgiEngine for StubEngine {
+    fn id_name(&self) -> &'static str {
+        "bkgm-ubgi-stub"
+    }
+
+    fn id_version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn options(&self) -> Vec<OptionSpec> {
+        vec![OptionSpec::integer_unbounded("engine.seed", 0)]
+    }
+
+    fn get(&self, key: &str) -> Option<OptionValue> {
+        (key == "engine.seed").then_some(OptionValue::Int(self.seed))
+    }
+
+    fn set(&mut self, key: &str, value: &OptionValue) -> UbgiResult<()> {
+        match (key, value) {
+            ("engine.seed", OptionValue::Int(seed)) => {
+                self.seed = *seed;
+                self.rng = fastrand::Rng::with_seed(*seed as u64);
+                Ok(())
+            }
+            _ => Err(UbgiError::unsupported("key")),
+        }
+    }
+
+    fn choose_move(&mut self, game: &Game, dice: Dice) -> UbgiResult<bkgm::ubgi::UbgiMove> {
+        let legal = move_text::legal_steps(game.position(), dice)
+            .map_err(|err| UbgiError::bad_state(err.to_string()))?;
+        if legal.is_empty() {
+            return Ok(Vec::new());
+        }
+        let index = if self.seed == 0 {
+            0
+        } else {
+            self.rng.usize(..legal.len())
+        };
+        Ok(legal[index].0.clone())
+    }
+}
+
+fn main() {
+    let mut engine = StubEngine {
+        seed: 0,
+        rng: fastrand::Rng::with_seed(0),
+    };
+    run_ubgi_stdio(&mut engine);
+}
@@ -57,6 +57,17 @@ impl Xgid {
     pub fn format(self) -> String {
         format_xgid(self)
     }
+
+    /// The board with the side to move on roll. `x` has turn `true`, so the position is flipped
+    /// if `move_flag` gives the move to `o`.
+    pub fn position(&self, variant: Variant) -> Result<VariantPosition, XgidError> {
+        let position = decode(variant, &self.board.format())?;
+        Ok(if self.move_flag {
+            position
+        } else {
+            position.flip()
+        })
+    }
 }
 
 impl XgidBoard {
@@ -0,0 +1,309 @@
+use crate::equity::CubeOwnership;
+use crate::position::GameResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CubeAction {
+    /// The player on roll offers the cube before rolling.
+    Double,
+    Take,
+    /// The opponent gives up and pays the current cube value.
+    Pass,
+    /// The opponent takes and immediately redoubles, keeping the cube.
+    Beaver,
+    /// The player who doubled redoubles a beaver right away, the opponent still owns the cube.
+    Raccoon,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Pending {
+    None,
+    Double,
+    Beaver,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("illegal cube action {0:?}")]
+pub struct IllegalCubeAction(pub CubeAction);
+
+/// Doubling cube of a game.
+///
+/// Players are identified by their [crate::State::turn], all actions are described from the
+/// player on roll, who is the one that doubles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CubeState {
+    value: u32,
+    owner: Option<bool>,
+    available: bool,
+    max_value: Option<u32>,
+    beavers: bool,
+    raccoons: bool,
+    jacoby: bool,
+    pending: Pending,
+}
+
+impl Default for CubeState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CubeState {
+    /// Centered cube with value 1, no limit and without beavers.
+    pub fn new() -> Self {
+        Self {
+            value: 1,
+            owner: None,
+            available: true,
+            max_value: None,
+            beavers: false,
+            raccoons: false,
+            jacoby: false,
+            pending: Pending::None,
+        }
+    }
+
+    /// A cube which can't be turned, for cubeless play or the Crawford game.
+    pub fn unavailable() -> Self {
+        Self {
+            available: false,
+            ..Self::new()
+        }
+    }
+
+    /// Centered cube with value 1 for a new game, keeping the limit and beaver rules.
+    pub fn reset(&mut self) {
+        self.value = 1;
+        self.owner = None;
+        self.pending = Pending::None;
+    }
+
+    pub fn with_value(mut self, value: u32, owner: Option<bool>) -> Self {
+        self.value = value;
+        self.owner = owner;
+        self
+    }
+
+    pub fn with_max_value(mut self, max_value: Option<u32>) -> Self {
+        self.max_value = max_value;
+        self
+    }
+
+    /// Allow beavers, and if `raccoons` is set also raccoons.
+    pub fn with_beavers(mut self, beavers: bool, raccoons: bool) -> Self {
+        self.beavers = beavers;
+        self.raccoons = beavers && raccoons;
+        self
+    }
+
+    /// With the Jacoby rule gammons and backgammons only count once the cube was turned.
+    pub fn with_jacoby(mut self, jacoby: bool) -> Self {
+        self.jacoby = jacoby;
+        self
+    }
+
+    pub fn is_jacoby(&self) -> bool {
+        self.jacoby
+    }
+
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// Turn of the player owning the cube, `None` if it is centered.
+    pub fn owner(&self) -> Option<bool> {
+        self.owner
+    }
+
+    pub fn is_centered(&self) -> bool {
+        self.owner.is_none()
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.available
+    }
+
+    pub fn max_value(&self) -> Option<u32> {
+        self.max_value
+    }
+
+    /// A double was offered and the opponent has not answered yet.
+    pub fn is_double_offered(&self) -> bool {
+        self.pending == Pending::Double
+    }
+
+    /// Whether the cube may be played with the rolled dice, so there is no open double.
+    pub fn is_settled(&self) -> bool {
+        self.pending != Pending::Double
+    }
+
+    /// Who can double, seen from the player with turn `on_roll`.
+    pub fn ownership(&self, on_roll: bool) -> CubeOwnership {
+        match self.owner {
+            None => CubeOwnership::Centered,
+            Some(owner) if owner == on_roll => CubeOwnership::Owned,
+            Some(_) => CubeOwnership::Unavailable,
+        }
+    }
+
+    /// Whether the cube may be multiplied by `factor`, without passing the limit or overflowing.
+    fn allows(&self, factor: u32) -> bool {
+        self.value
+            .checked_mul(factor)
+            .is_some_and(|value| self.max_value.is_none_or(|max| value <= max))
+    }
+
+    /// Cube actions which can be taken now, while the player with turn `on_roll` is on roll.
+    ///
+    /// Without an open double this is the player on roll's choice to double, otherwise the
+    /// opponent's answer. After a beaver, the player on roll may raccoon before rolling.
+    pub fn legal_actions(&self, on_roll: bool) -> Vec<CubeAction> {
+        match self.pending {
+            Pending::None => {
+                if self.available
+                    && self.owner.is_none_or(|owner| owner == on_roll)
+                    && self.allows(2)
+                {
+                    vec![CubeAction::Double]
+                } else {
+                    Vec::new()
+                }
+            }
+            Pending::Double => {
+                let mut actions = vec![CubeAction::Take, CubeAction::Pass];
+                if self.beavers && self.allows(4) {
+                    actions.push(CubeAction::Beaver);
+                }
+                actions
+            }
+            Pending::Beaver => {
+                if self.raccoons && self.allows(2) {
+                    vec![CubeAction::Raccoon]
+                } else {
+                    Vec::new()
+                }
+            }
+        }
+    }
+
+    /// Applies a cube action. After a pass the player on roll wins the current value.
+    pub fn apply(&mut self, action: CubeAction, on_roll: bool) -> Result<(), IllegalCubeAction> {
+        if !self.legal_actions(on_roll).contains(&action) {
+            return Err(IllegalCubeAction(action));
+        }
+        match action {
+            CubeAction::Double => self.pending = Pending::Double,
+            CubeAction::Take => {
+                self.value *= 2;
+                self.owner = Some(!on_roll);
+                self.pending = Pending::None;
+            }
+            CubeAction::Pass => self.pending = Pending::None,
+            CubeAction::Beaver => {
+                self.value *= 4;
+                self.owner = Some(!on_roll);
+                self.pending = Pending::Beaver;
+            }
+            CubeAction::Raccoon => {
+                self.value *= 2;
+                self.pending = Pending::None;
+            }
+        }
+        Ok(())
+    }
+
+    /// The player on roll rolls the dice, closing the chance to raccoon.
+    pub(crate) fn roll(&mut self) {
+        debug_assert!(self.is_settled());
+        self.pending = Pending::None;
+    }
+
+    /// Points for a game result, which is worth 1, 2 or 3 times the cube.
+    pub fn points(&self, result: &GameResult) -> f32 {
+        if self.jacoby && self.owner.is_none() {
+            return result.value().signum() * self.value as f32;
+        }
+        result.value() * self.value as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CubeAction, CubeState, IllegalCubeAction};
+    use crate::equity::CubeOwnership;
+    use crate::position::GameResult;
+
+    #[test]
+    fn double_and_take_gives_cube_to_opponent() {
+        let mut cube = CubeState::new();
+        assert_eq!(cube.legal_actions(true), vec![CubeAction::Double]);
+        cube.apply(CubeAction::Double, true).unwrap();
+        assert!(cube.is_double_offered());
+        assert_eq!(
+            cube.legal_actions(true),
+            vec![CubeAction::Take, CubeAction::Pass]
+        );
+        cube.apply(CubeAction::Take, true).unwrap();
+        assert_eq!(cube.value(), 2);
+        assert_eq!(cube.owner(), Some(false));
+        assert_eq!(cube.ownership(true), CubeOwnership::Unavailable);
+        assert_eq!(cube.ownership(false), CubeOwnership::Owned);
+        assert!(cube.legal_actions(true).is_empty());
+        assert_eq!(cube.legal_actions(false), vec![CubeAction::Double]);
+    }
+
+    #[test]
+    fn beaver_and_raccoon() {
+        let mut cube = CubeState::new().with_beavers(true, true);
+        cube.apply(CubeAction::Double, true).unwrap();
+        cube.apply(CubeAction::Beaver, true).unwrap();
+        assert_eq!(cube.value(), 4);
+        assert_eq!(cube.legal_actions(true), vec![CubeAction::Raccoon]);
+        cube.apply(CubeAction::Raccoon, true).unwrap();
+        assert_eq!(cube.value(), 8);
+        assert_eq!(cube.owner(), Some(false));
+        assert!(cube.legal_actions(true).is_empty());
+    }
+
+    #[test]
+    fn respects_max_value_and_availability() {
+        let mut cube = CubeState::new()
+            .with_max_value(Some(2))
+            .with_beavers(true, false);
+        cube.apply(CubeAction::Double, true).unwrap();
+        assert_eq!(
+            cube.apply(CubeAction::Beaver, true),
+            Err(IllegalCubeAction(CubeAction::Beaver))
+        );
+        cube.apply(CubeAction::Take, true).unwrap();
+        assert!(cube.legal_actions(false).is_empty());
+
+        assert!(CubeState::unavailable().legal_actions(true).is_empty());
+    }
+
+    #[test]
+    fn refuses_doubles_that_overflow() {
+        let mut cube = CubeState::new()
+            .with_value(1 << 30, Some(true))
+            .with_beavers(true, true);
+        cube.apply(CubeAction::Double, true).unwrap();
+        assert_eq!(
+            cube.legal_actions(true),
+            vec![CubeAction::Take, CubeAction::Pass]
+        );
+        cube.apply(CubeAction::Take, true).unwrap();
+        assert_eq!(cube.value(), 1 << 31);
+        assert!(cube.legal_actions(false).is_empty());
+    }
+
+    #[test]
+    fn points_are_multiplied_by_cube() {
+        let cube = CubeState::new().with_value(4, Some(true));
+        assert_eq!(cube.points(&GameResult::WinGammon), 8.0);
+        assert_eq!(cube.points(&GameResult::LoseNormal), -4.0);
+
+        let jacoby = CubeState::new().with_jacoby(true);
+        assert_eq!(jacoby.points(&GameResult::LoseBackgammon), -1.0);
+        let turned = jacoby.with_value(2, Some(false));
+        assert_eq!(turned.points(&GameResult::WinGammon), 4.0);
+    }
+}
@@ -0,0 +1,661 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::codecs::gnuid::{self, GnuidError};
+use crate::codecs::move_text::{self, MoveTextError};
+use crate::cube::CubeState;
+use crate::dice::Dice;
+use crate::dice_gen::FastrandDice;
+use crate::equity::Probabilities;
+use crate::evaluator::{Evaluator, Policy};
+use crate::game::{Game, GameError};
+use crate::position::{GameResult, GameState, Position};
+use crate::variants::{with_position, Variant, VariantPosition, VARIANTS};
+
+const MAGIC: [u8; 4] = *b"BKDS";
+const VERSION: u32 = 1;
+const FLAG_TARGETS: u8 = 1;
+/// Bytes of a binary record without targets: two position ranks, two dice and the result.
+const RECORD_BYTES: usize = 16 + 2 + 16 + 1;
+const TARGET_BYTES: usize = 5 * 4;
+const CSV_COLUMNS: &str = "position,dice,move,next,result";
+const CSV_TARGET_COLUMNS: &str = ",win,win_gammon,win_backgammon,lose_gammon,lose_backgammon";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DatasetError {
+    #[error("dataset i/o failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid dataset header")]
+    InvalidHeader,
+    #[error("unsupported dataset version {0}")]
+    UnsupportedVersion(u32),
+    #[error("invalid record {index}: {reason}")]
+    InvalidRecord { index: u64, reason: &'static str },
+    #[error("record of {found} in a {expected} dataset")]
+    VariantMismatch { expected: Variant, found: Variant },
+    #[error(transparent)]
+    Game(#[from] GameError),
+    #[error(transparent)]
+    MoveText(#[from] MoveTextError),
+}
+
+/// One move of a self-play game.
+///
+/// Positions are seen from the player on roll, their `turn` is not stored.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Position before rolling.
+    pub position: VariantPosition,
+    pub dice: Dice,
+    /// Position after the chosen move, seen from the opponent.
+    pub next: VariantPosition,
+    /// Final result of the game for the player on roll in `position`.
+    pub result: GameResult,
+    /// Probabilities of `position` by an evaluator or an exact table.
+    pub targets: Option<Probabilities>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatasetFormat {
+    /// Header and length-prefixed records, see [DatasetWriter].
+    Binary,
+    /// A comment line with the variant, a header line and one line per record.
+    Csv,
+}
+
+impl DatasetFormat {
+    /// [Self::Csv] for paths ending in `.csv`, otherwise [Self::Binary].
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => Self::Csv,
+            _ => Self::Binary,
+        }
+    }
+}
+
+/// Writes [Record]s of one variant.
+///
+/// The binary format starts with a header, all numbers are little endian:
+///
+/// | bytes | content                      |
+/// |-------|------------------------------|
+/// | 4     | magic `BKDS`                 |
+/// | 4     | format version               |
+/// | 1     | variant, index in [VARIANTS] |
+/// | 1     | flags, bit 0 = targets       |
+///
+/// Each record is a `u32` length followed by that many bytes: the [Position::rank] of the
+/// position (16), the dice, bigger first (2), the rank of the next position (16), the result as
+/// [GameResult::value] (1, signed) and with targets the five [Probabilities] as `f32` (20).
+/// Readers skip bytes after the known fields.
+///
+/// CSV positions are GNUbg position IDs and the move is in FIBS notation.
+pub struct DatasetWriter<W: Write> {
+    writer: W,
+    format: DatasetFormat,
+    variant: Variant,
+    targets: bool,
+    records: u64,
+}
+
+impl<W: Write> DatasetWriter<W> {
+    /// Writes the header, `targets` is whether every record has them.
+    pub fn new(
+        mut writer: W,
+        format: DatasetFormat,
+        variant: Variant,
+        targets: bool,
+    ) -> Result<Self, DatasetError> {
+        match format {
+            DatasetFormat::Binary => {
+                writer.write_all(&MAGIC)?;
+                writer.write_all(&VERSION.to_le_bytes())?;
+                writer.write_all(&[variant_index(variant), targets as u8 * FLAG_TARGETS])?;
+            }
+            DatasetFormat::Csv => {
+                writeln!(
+                    writer,
+                    "# bkgm-dataset version={VERSION} variant={variant} targets={}",
+                    targets as u8
+                )?;
+                let targets = if targets { CSV_TARGET_COLUMNS } else { "" };
+                writeln!(writer, "{CSV_COLUMNS}{targets}")?;
+            }
+        }
+        Ok(Self {
+            writer,
+            format,
+            variant,
+            targets,
+            records: 0,
+        })
+    }
+
+    /// Records written so far.
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    pub fn write(&mut self, record: &Record) -> Result<(), DatasetError> {
+        for position in [record.position, record.next] {
+            if position.variant() != self.variant {
+                return Err(DatasetError::VariantMismatch {
+                    expected: self.variant,
+                    found: position.variant(),
+                });
+            }
+        }
+        if record.targets.is_some() != self.targets {
+            return Err(DatasetError::InvalidRecord {
+                index: self.records,
+                reason: "targets don't match the header",
+            });
+        }
+        match self.format {
+            DatasetFormat::Binary => self.write_binary(record)?,
+            DatasetFormat::Csv => self.write_csv(record)?,
+        }
+        self.records += 1;
+        Ok(())
+    }
+
+    fn write_binary(&mut self, record: &Record) -> Result<(), DatasetError> {
+        let mut bytes = Vec::with_capacity(4 + RECORD_BYTES + TARGET_BYTES);
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&rank(record.position).to_le_bytes());
+        let (big, small) = faces(&record.dice);
+        bytes.extend_from_slice(&[big as u8, small as u8]);
+        bytes.extend_from_slice(&rank(record.next).to_le_bytes());
+        bytes.push(record.result.value() as i8 as u8);
+        if let Some(targets) = &record.targets {
+            for value in targets.to_array() {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        let length = (bytes.len() - 4) as u32;
+        bytes[..4].copy_from_slice(&length.to_le_bytes());
+        self.writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn write_csv(&mut self, record: &Record) -> Result<(), DatasetError> {
+        let (big, small) = faces(&record.dice);
+        let text = move_text::encode(record.position, record.next, record.dice)?;
+        write!(
+            self.writer,
+            "{},{big}{small},{text},{},{}",
+            gnuid::encode(record.position),
+            gnuid::encode(record.next),
+            record.result.value()
+        )?;
+        if let Some(targets) = &record.targets {
+            for value in targets.to_array() {
+                write!(self.writer, ",{value}")?;
+            }
+        }
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    /// Flushes and returns the inner writer.
+    pub fn finish(mut self) -> Result<W, DatasetError> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl DatasetWriter<BufWriter<File>> {
+    pub fn create(
+        path: impl AsRef<Path>,
+        format: DatasetFormat,
+        variant: Variant,
+        targets: bool,
+    ) -> Result<Self, DatasetError> {
+        Self::new(
+            BufWriter::new(File::create(path)?),
+            format,
+            variant,
+            targets,
+        )
+    }
+}
+
+/// Streams the [Record]s of a binary or CSV dataset, the format is detected from the header.
+pub struct DatasetReader<R: BufRead> {
+    reader: R,
+    format: DatasetFormat,
+    variant: Variant,
+    targets: bool,
+    records: u64,
+    line: String,
+}
+
+impl<R: BufRead> DatasetReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, DatasetError> {
+        let (format, variant, targets) = if reader.fill_buf()?.first() == Some(&b'#') {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let (variant, targets) = parse_csv_header(&line)?;
+            line.clear();
+            reader.read_line(&mut line)?;
+            if !line.starts_with(CSV_COLUMNS) {
+                return Err(DatasetError::InvalidHeader);
+            }
+            (DatasetFormat::Csv, variant, targets)
+        } else {
+            let mut header = [0u8; 10];
+            reader.read_exact(&mut header)?;
+            if header[0..4] != MAGIC {
+                return Err(DatasetError::InvalidHeader);
+            }
+            let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+            if version != VERSION {
+                return Err(DatasetError::UnsupportedVersion(version));
+            }
+            let variant = *VARIANTS
+                .get(header[8] as usize)
+                .ok_or(DatasetError::InvalidHeader)?;
+            (
+                DatasetFormat::Binary,
+                variant,
+                header[9] & FLAG_TARGETS != 0,
+            )
+        };
+        Ok(Self {
+            reader,
+            format,
+            variant,
+            targets,
+            records: 0,
+            line: String::new(),
+        })
+    }
+
+    pub fn format(&self) -> DatasetFormat {
+        self.format
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn has_targets(&self) -> bool {
+        self.targets
+    }
+
+    /// The next record, `None` at the end of the dataset.
+    pub fn read_record(&mut self) -> Result<Option<Record>, DatasetError> {
+        let record = match self.format {
+            DatasetFormat::Binary => self.read_binary()?,
+            DatasetFormat::Csv => self.read_csv()?,
+        };
+        if record.is_some() {
+            self.records += 1;
+        }
+        Ok(record)
+    }
+
+    fn invalid(&self, reason: &'static str) -> DatasetError {
+        DatasetError::InvalidRecord {
+            index: self.records,
+            reason,
+        }
+    }
+
+    fn read_binary(&mut self) -> Result<Option<Record>, DatasetError> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let mut length = [0u8; 4];
+        self.reader.read_exact(&mut length)?;
+        let mut bytes = vec![0u8; u32::from_le_bytes(length) as usize];
+        self.reader.read_exact(&mut bytes)?;
+        let expected = RECORD_BYTES + if self.targets { TARGET_BYTES } else { 0 };
+        if bytes.len() < expected {
+            return Err(self.invalid("record is too short"));
+        }
+
+        let position = unrank(
+            self.variant,
+            u128::from_le_bytes(bytes[0..16].try_into().unwrap()),
+        )
+        .ok_or_else(|| self.invalid("position rank out of range"))?;
+        let dice = Dice::try_from((bytes[16] as usize, bytes[17] as usize))
+            .map_err(|_| self.invalid("invalid dice"))?;
+        let next = unrank(
+            self.variant,
+            u128::from_le_bytes(bytes[18..34].try_into().unwrap()),
+        )
+        .ok_or_else(|| self.invalid("next position rank out of range"))?;
+        let result = GameResult::from_value(bytes[34] as i8 as f32)
+            .ok_or_else(|| self.invalid("invalid result"))?;
+        let targets = self.targets.then(|| {
+            let mut values = [0.0; 5];
+            for (value, chunk) in values
+                .iter_mut()
+                .zip(bytes[RECORD_BYTES..expected].chunks_exact(4))
+            {
+                *value = f32::from_le_bytes(chunk.try_into().unwrap());
+            }
+            Probabilities::from_array(values)
+        });
+        Ok(Some(Record {
+            position,
+            dice,
+            next,
+            result,
+            targets,
+        }))
+    }
+
+    fn read_csv(&mut self) -> Result<Option<Record>, DatasetError> {
+        self.line.clear();
+        if self.reader.read_line(&mut self.line)? == 0 {
+            return Ok(None);
+        }
+        let fields: Vec<&str> = self.line.trim_end().split(',').collect();
+        let expected = if self.targets { 10 } else { 5 };
+        if fields.len() != expected {
+            return Err(self.invalid("wrong number of fields"));
+        }
+
+        let decode =
+            |id: &str| -> Result<VariantPosition, GnuidError> { gnuid::decode(self.variant, id) };
+        let position = decode(fields[0]).map_err(|_| self.invalid("invalid position ID"))?;
+        let next = decode(fields[3]).map_err(|_| self.invalid("invalid next position ID"))?;
+        let dice = match fields[1].as_bytes() {
+            [big, small] => Dice::try_from((
+                big.wrapping_sub(b'0') as usize,
+                small.wrapping_sub(b'0') as usize,
+            ))
+            .ok(),
+            _ => None,
+        }
+        .ok_or_else(|| self.invalid("invalid dice"))?;
+        let result = fields[4]
+            .parse()
+            .ok()
+            .and_then(GameResult::from_value)
+            .ok_or_else(|| self.invalid("invalid result"))?;
+        let targets = if self.targets {
+            let mut values = [0.0; 5];
+            for (value, field) in values.iter_mut().zip(&fields[5..]) {
+                *value = field.parse().map_err(|_| self.invalid("invalid target"))?;
+            }
+            Some(Probabilities::from_array(values))
+        } else {
+            None
+        };
+        Ok(Some(Record {
+            position,
+            dice,
+            next,
+            result,
+            targets,
+        }))
+    }
+}
+
+impl DatasetReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DatasetError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: BufRead> Iterator for DatasetReader<R> {
+    type Item = Result<Record, DatasetError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetOptions {
+    pub variant: Variant,
+    pub games: u64,
+    /// Dice seed of the first game, game `i` uses `seed + i`.
+    pub seed: u64,
+    /// Games without a result after this many plies are left out.
+    pub max_plies: usize,
+}
+
+impl Default for DatasetOptions {
+    fn default() -> Self {
+        Self {
+            variant: Variant::Backgammon,
+            games: 1000,
+            seed: 0,
+            max_plies: 10_000,
+        }
+    }
+}
+
+/// Plays `options.games` cubeless games with `policy` and writes a [Record] for every move,
+/// with the probabilities of `targets` if given. Returns the number of records.
+pub fn generate<P: Policy + ?Sized, W: Write>(
+    options: &DatasetOptions,
+    policy: &mut P,
+    targets: Option<&dyn Evaluator>,
+    writer: &mut DatasetWriter<W>,
+) -> Result<u64, DatasetError> {
+    generate_with(options, policy, targets, writer, |_, _| {})
+}
+
+/// Like [generate], with a callback after each game that gets the number of finished games and
+/// records.
+pub fn generate_with<P, W, F>(
+    options: &DatasetOptions,
+    policy: &mut P,
+    targets: Option<&dyn Evaluator>,
+    writer: &mut DatasetWriter<W>,
+    mut progress: F,
+) -> Result<u64, DatasetError>
+where
+    P: Policy + ?Sized,
+    W: Write,
+    F: FnMut(u64, u64),
+{
+    let first = writer.records();
+    let mut moves: Vec<(VariantPosition, Dice, VariantPosition)> = Vec::new();
+    for i in 0..options.games {
+        let mut dice = FastrandDice::with_seed(options.seed.wrapping_add(i));
+        let mut game = Game::with_cube(options.variant, CubeState::unavailable());
+        moves.clear();
+        game.play_episode(&mut dice, options.max_plies, |position, dice, legal| {
+            let index = policy.choose(position, dice, legal);
+            if let Some(&next) = legal.get(index) {
+                moves.push((position, *dice, next));
+            }
+            index
+        })?;
+        if let GameState::GameOver(result) = game.game_state() {
+            let last_turn = game.position().turn();
+            for &(position, dice, next) in &moves {
+                writer.write(&Record {
+                    position,
+                    dice,
+                    next,
+                    result: if position.turn() == last_turn {
+                        result.clone()
+                    } else {
+                        result.reverse()
+                    },
+                    targets: targets.map(|evaluator| evaluator.evaluate(position)),
+                })?;
+            }
+        }
+        progress(i + 1, writer.records() - first);
+    }
+    Ok(writer.records() - first)
+}
+
+fn variant_index(variant: Variant) -> u8 {
+    VARIANTS
+        .iter()
+        .position(|&v| v == variant)
+        .expect("all variants are listed") as u8
+}
+
+fn rank(position: VariantPosition) -> u128 {
+    with_position!(position, p => p.rank())
+}
+
+fn unrank(variant: Variant, index: u128) -> Option<VariantPosition> {
+    match variant {
+        Variant::Backgammon => Position::<15>::unrank(index).map(VariantPosition::Backgammon),
+        Variant::Nackgammon => Position::<15>::unrank(index).map(VariantPosition::Nackgammon),
+        Variant::Longgammon => Position::<15>::unrank(index).map(VariantPosition::Longgammon),
+        Variant::Hypergammon => Position::<3>::unrank(index).map(VariantPosition::Hypergammon),
+        Variant::Hypergammon2 => Position::<2>::unrank(index).map(VariantPosition::Hypergammon2),
+        Variant::Hypergammon4 => Position::<4>::unrank(index).map(VariantPosition::Hypergammon4),
+        Variant::Hypergammon5 => Position::<5>::unrank(index).map(VariantPosition::Hypergammon5),
+    }
+}
+
+/// The dice, bigger first.
+fn faces(dice: &Dice) -> (usize, usize) {
+    match dice {
+        Dice::Mixed(dice) => (dice.big(), dice.small()),
+        Dice::Double(die) => (*die, *die),
+    }
+}
+
+fn parse_csv_header(line: &str) -> Result<(Variant, bool), DatasetError> {
+    let mut fields = line.trim_end().split(' ');
+    if fields.next() != Some("#") || fields.next() != Some("bkgm-dataset") {
+        return Err(DatasetError::InvalidHeader);
+    }
+    let mut variant = None;
+    let mut targets = false;
+    for field in fields {
+        match field.split_once('=') {
+            Some(("version", version)) => {
+                let version = u32::from_str(version).map_err(|_| DatasetError::InvalidHeader)?;
+                if version != VERSION {
+                    return Err(DatasetError::UnsupportedVersion(version));
+                }
+            }
+            Some(("variant", name)) => variant = name.parse().ok(),
+            Some(("targets", flag)) => targets = flag == "1",
+            _ => {}
+        }
+    }
+    Ok((variant.ok_or(DatasetError::InvalidHeader)?, targets))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        generate, variant_index, DatasetError, DatasetFormat, DatasetOptions, DatasetReader,
+        DatasetWriter, Record, TARGET_BYTES,
+    };
+    use crate::codecs::gnuid;
+    use crate::dice::Dice;
+    use crate::evaluator::{Evaluator, RaceHeuristic, RandomPlayer};
+    use crate::position::{GameResult, GameState};
+    use crate::variants::{Variant, VARIANTS};
+
+    fn generate_records(format: DatasetFormat, targets: bool) -> (Vec<u8>, Vec<Record>) {
+        let options = DatasetOptions {
+            variant: Variant::Hypergammon,
+            games: 5,
+            seed: 7,
+            ..Default::default()
+        };
+        let evaluator = RaceHeuristic;
+        let mut writer = DatasetWriter::new(Vec::new(), format, options.variant, targets).unwrap();
+        let count = generate(
+            &options,
+            &mut RandomPlayer::with_seed(3),
+            targets.then_some(&evaluator as &dyn Evaluator),
+            &mut writer,
+        )
+        .unwrap();
+        let bytes = writer.finish().unwrap();
+        let reader = DatasetReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.format(), format);
+        assert_eq!(reader.variant(), Variant::Hypergammon);
+        assert_eq!(reader.has_targets(), targets);
+        let records: Vec<Record> = reader.map(Result::unwrap).collect();
+        assert_eq!(records.len() as u64, count);
+        (bytes, records)
+    }
+
+    #[test]
+    fn binary_and_csv_hold_the_same_records() {
+        let (binary, records) = generate_records(DatasetFormat::Binary, true);
+        let (_, csv_records) = generate_records(DatasetFormat::Csv, true);
+        assert!(records.len() > 20);
+        assert_eq!(records, csv_records);
+        assert_eq!(records[0].position, Variant::Hypergammon.start_position());
+        let (plain, plain_records) = generate_records(DatasetFormat::Binary, false);
+        assert_eq!(binary.len() - plain.len(), records.len() * TARGET_BYTES);
+        assert!(plain_records.iter().all(|record| record.targets.is_none()));
+
+        // Consecutive records of a game alternate the player on roll.
+        for pair in records.windows(2) {
+            if pair[0].next == pair[1].position {
+                assert_eq!(pair[0].result, pair[1].result.reverse());
+            }
+        }
+        let last = records.last().unwrap();
+        match last.next.game_state() {
+            GameState::GameOver(result) => assert_eq!(last.result, result.reverse()),
+            GameState::Ongoing => panic!("the last record ends the game"),
+        }
+    }
+
+    #[test]
+    fn rejects_damaged_datasets() {
+        let (mut binary, _) = generate_records(DatasetFormat::Binary, false);
+        binary[8] = VARIANTS.len() as u8;
+        assert!(matches!(
+            DatasetReader::new(binary.as_slice()),
+            Err(DatasetError::InvalidHeader)
+        ));
+        binary[8] = variant_index(Variant::Hypergammon);
+        binary[10] = 3;
+        let mut reader = DatasetReader::new(binary.as_slice()).unwrap();
+        assert!(matches!(
+            reader.read_record(),
+            Err(DatasetError::InvalidRecord { index: 0, .. })
+        ));
+
+        let start = gnuid::encode(Variant::Hypergammon.start_position());
+        let csv = format!(
+            "# bkgm-dataset version=1 variant=hypergammon targets=0\n\
+            position,dice,move,next,result\n\
+            {start},71,pass,{start},1\n"
+        );
+        let mut reader = DatasetReader::new(csv.as_bytes()).unwrap();
+        assert!(matches!(
+            reader.read_record(),
+            Err(DatasetError::InvalidRecord {
+                reason: "invalid dice",
+                ..
+            })
+        ));
+
+        let mut writer = DatasetWriter::new(
+            Vec::new(),
+            DatasetFormat::Binary,
+            Variant::Backgammon,
+            false,
+        )
+        .unwrap();
+        let record = Record {
+            position: Variant::Hypergammon.start_position(),
+            dice: Dice::new(3, 1),
+            next: Variant::Hypergammon.start_position(),
+            result: GameResult::WinNormal,
+            targets: None,
+        };
+        assert!(matches!(
+            writer.write(&record),
+            Err(DatasetError::VariantMismatch { .. })
+        ));
+    }
+}
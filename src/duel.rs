@@ -0,0 +1,723 @@
+use std::collections::BTreeMap;
+
+use crate::codecs::move_text;
+use crate::codecs::xgid::{self, Xgid, XgidBoard, XgidDice};
+use crate::cube::{CubeAction, CubeState};
+use crate::dice::Dice;
+use crate::dice_gen::{DiceGen, FastrandDice};
+use crate::engine_spec::EngineSpec;
+use crate::evaluator::{
+    EvaluatorEngine, Pubeval, RaceHeuristic, RandomPlayer, PUBEVAL_CONTACT_FILE, PUBEVAL_RACE_FILE,
+};
+use crate::game::{Game, GameError};
+use crate::match_play::{Match, MatchError, XGID_CRAWFORD};
+use crate::mlp::Mlp;
+use crate::position::{GameResult, GameState};
+use crate::ubgi::{MatchScore, UbgiClient, UbgiClientError, UbgiEngine, UbgiMove};
+use crate::variants::Variant;
+
+/// z value of a 95% confidence interval.
+pub const Z_95: f64 = 1.959964;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DuelError {
+    #[error("engine failed: {0}")]
+    Engine(String),
+    #[error(transparent)]
+    Client(#[from] UbgiClientError),
+    #[error("illegal move '{text}' for dice {dice}")]
+    IllegalMove { text: String, dice: Dice },
+    #[error("game did not end after {0} plies")]
+    TooLong(usize),
+    #[error(transparent)]
+    Game(#[from] GameError),
+    #[error(transparent)]
+    Match(#[from] MatchError),
+}
+
+/// A player of a duel, an in-process [UbgiEngine] or an engine process behind a [UbgiClient].
+///
+/// `score` is the match score from the player on roll, `None` in money games.
+pub trait DuelPlayer {
+    fn name(&self) -> String;
+    /// Called before each game.
+    fn new_game(&mut self, _variant: Variant) -> Result<(), DuelError> {
+        Ok(())
+    }
+    fn choose_move(
+        &mut self,
+        game: &Game,
+        score: Option<MatchScore>,
+        dice: Dice,
+    ) -> Result<UbgiMove, DuelError>;
+    fn offer_double(&mut self, game: &Game, score: Option<MatchScore>) -> Result<bool, DuelError>;
+    /// Answer of the opponent of the player on roll to the offered double.
+    fn respond_to_double(
+        &mut self,
+        game: &Game,
+        score: Option<MatchScore>,
+    ) -> Result<CubeAction, DuelError>;
+}
+
+impl<E: UbgiEngine> DuelPlayer for E {
+    fn name(&self) -> String {
+        format!("{} {}", self.id_name(), self.id_version())
+    }
+
+    fn choose_move(
+        &mut self,
+        game: &Game,
+        score: Option<MatchScore>,
+        dice: Dice,
+    ) -> Result<UbgiMove, DuelError> {
+        self.set_match_score(score)
+            .and_then(|_| UbgiEngine::choose_move(self, game, dice))
+            .map_err(|err| DuelError::Engine(err.to_string()))
+    }
+
+    fn offer_double(&mut self, game: &Game, score: Option<MatchScore>) -> Result<bool, DuelError> {
+        self.set_match_score(score)
+            .and_then(|_| UbgiEngine::offer_double(self, game))
+            .map_err(|err| DuelError::Engine(err.to_string()))
+    }
+
+    fn respond_to_double(
+        &mut self,
+        game: &Game,
+        score: Option<MatchScore>,
+    ) -> Result<CubeAction, DuelError> {
+        self.set_match_score(score)
+            .and_then(|_| UbgiEngine::respond_to_double(self, game))
+            .map_err(|err| DuelError::Engine(err.to_string()))
+    }
+}
+
+impl DuelPlayer for UbgiClient {
+    fn name(&self) -> String {
+        format!("{} {}", UbgiClient::name(self), self.version())
+    }
+
+    fn new_game(&mut self, variant: Variant) -> Result<(), DuelError> {
+        self.set_variant(variant)?;
+        self.new_game()?;
+        Ok(())
+    }
+
+    fn choose_move(
+        &mut self,
+        game: &Game,
+        score: Option<MatchScore>,
+        dice: Dice,
+    ) -> Result<UbgiMove, DuelError> {
+        let (d1, d2) = match dice {
+            Dice::Double(die) => (die, die),
+            Dice::Mixed(mixed) => (mixed.big(), mixed.small()),
+        };
+        let xgid = to_xgid(game, score, XgidDice::Rolled(d1 as u8, d2 as u8));
+        self.set_xgid(&xgid.format())?;
+        Ok(self.best_move()?)
+    }
+
+    fn offer_double(&mut self, game: &Game, score: Option<MatchScore>) -> Result<bool, DuelError> {
+        self.set_xgid(&to_xgid(game, score, XgidDice::Rolled(0, 0)).format())?;
+        Ok(self.cube_decision()? == Some(CubeAction::Double))
+    }
+
+    fn respond_to_double(
+        &mut self,
+        game: &Game,
+        score: Option<MatchScore>,
+    ) -> Result<CubeAction, DuelError> {
+        self.set_xgid(&to_xgid(game, score, XgidDice::DoubleOffered).format())?;
+        self.cube_decision()?
+            .ok_or_else(|| DuelError::Engine("no answer to double".to_string()))
+    }
+}
+
+/// In-process players for the aliases `bkgm-random`, `bkgm-race`, `bkgm-pubeval` and `bkgm-mlp`,
+/// `None` for any other alias. `bkgm-pubeval` and `bkgm-mlp` read their weights from
+/// `engine.weights`, without it `bkgm-pubeval` reads `WT.race` and `WT.cont` of the working
+/// directory like `pubeval.c`. The other options of the spec are set like UBGI options.
+pub fn builtin_player(spec: &EngineSpec) -> Result<Option<Box<dyn DuelPlayer>>, DuelError> {
+    let mut options = spec.options.clone();
+    let player = match spec.alias.as_str() {
+        "bkgm-random" => configure(RandomPlayer::new(), &options)?,
+        "bkgm-race" => configure(EvaluatorEngine::new("bkgm-race", RaceHeuristic), &options)?,
+        "bkgm-pubeval" => {
+            let pubeval = if options.contains_key("engine.weights") {
+                load_weights(spec, &mut options, Pubeval::load)?
+            } else {
+                Pubeval::load_dir(".").map_err(|err| {
+                    DuelError::Engine(format!(
+                        "bkgm-pubeval needs engine.weights or {PUBEVAL_RACE_FILE} and \
+                         {PUBEVAL_CONTACT_FILE} in the working directory: {err}"
+                    ))
+                })?
+            };
+            configure(EvaluatorEngine::new("bkgm-pubeval", pubeval), &options)?
+        }
+        "bkgm-mlp" => {
+            let mlp = load_weights(spec, &mut options, Mlp::load)?;
+            configure(EvaluatorEngine::new("bkgm-mlp", mlp), &options)?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(player))
+}
+
+/// Loads the file of the `engine.weights` option and removes it from `options`.
+fn load_weights<T, E: std::fmt::Display>(
+    spec: &EngineSpec,
+    options: &mut BTreeMap<String, String>,
+    load: impl FnOnce(String) -> Result<T, E>,
+) -> Result<T, DuelError> {
+    let path = options
+        .remove("engine.weights")
+        .ok_or_else(|| DuelError::Engine(format!("{} needs engine.weights", spec.alias)))?;
+    load(path.clone()).map_err(|err| DuelError::Engine(format!("could not load {path}: {err}")))
+}
+
+fn configure<E: UbgiEngine + 'static>(
+    mut engine: E,
+    options: &BTreeMap<String, String>,
+) -> Result<Box<dyn DuelPlayer>, DuelError> {
+    let specs = engine.options();
+    for (key, raw) in options {
+        let value = specs
+            .iter()
+            .find(|option| option.key() == key)
+            .ok_or_else(|| DuelError::Engine(format!("{} has no option {key}", engine.id_name())))?
+            .parse_value(raw)
+            .map_err(|reason| DuelError::Engine(format!("{key}: {reason}")))?;
+        engine
+            .set(key, &value)
+            .map_err(|err| DuelError::Engine(err.to_string()))?;
+    }
+    Ok(Box::new(engine))
+}
+
+/// XGID of a game from the player on roll, who is `x`.
+fn to_xgid(game: &Game, score: Option<MatchScore>, dice: XgidDice) -> Xgid {
+    let cube = game.cube();
+    let on_roll = game.position().turn();
+    let board = XgidBoard::parse(&xgid::encode(game.position())).expect("encoded board parses");
+    let score = score.unwrap_or(MatchScore {
+        length: 0,
+        score: 0,
+        opponent_score: 0,
+        crawford: false,
+    });
+    Xgid {
+        board,
+        max_cube: cube.max_value().map_or(0, |max| max.trailing_zeros() as u8),
+        match_length: score.length as u16,
+        rules: if score.crawford { XGID_CRAWFORD } else { 0 },
+        score_x: score.score as u16,
+        score_o: score.opponent_score as u16,
+        dice,
+        move_flag: true,
+        cube_owner: match cube.owner() {
+            None => 0,
+            Some(owner) if owner == on_roll => 1,
+            Some(_) => -1,
+        },
+        cube_power: cube.value().trailing_zeros() as u8,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuelOptions {
+    pub variant: Variant,
+    /// Number of dice seeds, each is played twice with the players swapped.
+    pub seeds: usize,
+    pub first_seed: u64,
+    /// Play matches to this length instead of money games.
+    pub match_length: Option<u32>,
+    /// Money games with the doubling cube. Matches always use the cube.
+    pub cubeful: bool,
+    /// Limit of plies per game.
+    pub max_plies: usize,
+}
+
+impl Default for DuelOptions {
+    fn default() -> Self {
+        Self {
+            variant: Variant::Backgammon,
+            seeds: 100,
+            first_seed: 0,
+            match_length: None,
+            cubeful: true,
+            max_plies: 10_000,
+        }
+    }
+}
+
+/// A finished game, seen from player `a`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    pub seed: u64,
+    /// `a` moved first.
+    pub a_first: bool,
+    /// Index of the match in [DuelReport::matches].
+    pub match_index: Option<usize>,
+    /// Points won by `a`, including the cube.
+    pub points: f32,
+    pub result: GameResult,
+    pub plies: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchRecord {
+    pub seed: u64,
+    pub a_first: bool,
+    pub a_won: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DuelReport {
+    pub games: Vec<GameRecord>,
+    pub matches: Vec<MatchRecord>,
+}
+
+/// Plays a duel, calling `progress` after each game.
+pub fn duel_with<F>(
+    a: &mut dyn DuelPlayer,
+    b: &mut dyn DuelPlayer,
+    options: &DuelOptions,
+    mut progress: F,
+) -> Result<DuelReport, DuelError>
+where
+    F: FnMut(&DuelReport),
+{
+    let mut report = DuelReport::default();
+    for seed in (0..options.seeds as u64).map(|i| options.first_seed.wrapping_add(i)) {
+        for a_first in [true, false] {
+            // Same dice in the same order, `a` and `b` swap seats.
+            let mut dice = FastrandDice::with_seed(seed);
+            let mut players: [&mut dyn DuelPlayer; 2] = if a_first {
+                [&mut *a, &mut *b]
+            } else {
+                [&mut *b, &mut *a]
+            };
+            match options.match_length {
+                None => {
+                    let cube = if options.cubeful {
+                        CubeState::new()
+                    } else {
+                        CubeState::unavailable()
+                    };
+                    let mut game = Game::with_cube(options.variant, cube);
+                    let (points, result, plies) =
+                        play_game(&mut game, None, &mut players, &mut dice, options)?;
+                    report.games.push(GameRecord {
+                        seed,
+                        a_first,
+                        match_index: None,
+                        points: if a_first { points } else { -points },
+                        result: if a_first { result } else { result.reverse() },
+                        plies,
+                    });
+                    progress(&report);
+                }
+                Some(length) => {
+                    let match_index = report.matches.len();
+                    let mut m = Match::new(options.variant, length)?;
+                    while !m.is_over() {
+                        let score = Some(MatchScore {
+                            length,
+                            score: m.score(true),
+                            opponent_score: m.score(false),
+                            crawford: m.is_crawford(),
+                        });
+                        let (points, result, plies) =
+                            play_game(m.game_mut(), score, &mut players, &mut dice, options)?;
+                        m.finish_game()?;
+                        report.games.push(GameRecord {
+                            seed,
+                            a_first,
+                            match_index: Some(match_index),
+                            points: if a_first { points } else { -points },
+                            result: if a_first { result } else { result.reverse() },
+                            plies,
+                        });
+                        progress(&report);
+                    }
+                    report.matches.push(MatchRecord {
+                        seed,
+                        a_first,
+                        a_won: m.winner() == Some(a_first),
+                    });
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+pub fn duel(
+    a: &mut dyn DuelPlayer,
+    b: &mut dyn DuelPlayer,
+    options: &DuelOptions,
+) -> Result<DuelReport, DuelError> {
+    duel_with(a, b, options, |_| {})
+}
+
+/// Plays one game between `players[0]`, who has turn `true` and moves first, and `players[1]`.
+///
+/// Returns the points and result of `players[0]` and the number of plies. `score` is the match
+/// score of `players[0]`.
+fn play_game(
+    game: &mut Game,
+    score: Option<MatchScore>,
+    players: &mut [&mut dyn DuelPlayer; 2],
+    dice_gen: &mut FastrandDice,
+    options: &DuelOptions,
+) -> Result<(f32, GameResult, usize), DuelError> {
+    for player in players.iter_mut() {
+        player.new_game(options.variant)?;
+    }
+    let seen_by = |turn: bool| {
+        score.map(|score| {
+            if turn {
+                score
+            } else {
+                MatchScore {
+                    score: score.opponent_score,
+                    opponent_score: score.score,
+                    ..score
+                }
+            }
+        })
+    };
+    let mut plies = 0;
+    while game.game_state() == GameState::Ongoing {
+        if plies >= options.max_plies {
+            return Err(DuelError::TooLong(plies));
+        }
+        let on_roll = game.position().turn();
+        let (player, opponent) = if on_roll {
+            let [x, o] = players;
+            (x, o)
+        } else {
+            let [x, o] = players;
+            (o, x)
+        };
+        if plies > 0
+            && game.legal_cube_actions().contains(&CubeAction::Double)
+            && player.offer_double(game, seen_by(on_roll))?
+        {
+            game.apply_cube_action(CubeAction::Double)?;
+            let answer = opponent.respond_to_double(game, seen_by(!on_roll))?;
+            game.apply_cube_action(answer)?;
+            if game.game_state() != GameState::Ongoing {
+                break;
+            }
+        }
+        let dice = if plies == 0 {
+            dice_gen.roll_mixed()
+        } else {
+            dice_gen.roll()
+        };
+        let steps = player.choose_move(game, seen_by(on_roll), dice)?;
+        let text = move_text::format_move_steps(&steps);
+        let illegal = || DuelError::IllegalMove {
+            text: text.clone(),
+            dice,
+        };
+        let next = move_text::apply(game.position(), dice, &text).ok_or_else(illegal)?;
+        let index = game
+            .legal_positions(&dice)
+            .iter()
+            .position(|position| *position == next)
+            .ok_or_else(illegal)?;
+        game.apply_nth_legal_position(&dice, index)?;
+        plies += 1;
+    }
+    let GameState::GameOver(result) = game.game_state() else {
+        unreachable!("loop ends with the game over");
+    };
+    let points = game.points().unwrap_or_default();
+    if game.position().turn() {
+        Ok((points, result, plies))
+    } else {
+        Ok((-points, result.reverse(), plies))
+    }
+}
+
+/// Mean and standard error of the mean.
+fn mean_and_error(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let mean = values.iter().sum::<f64>() / n;
+    if values.len() < 2 {
+        return (mean, 0.0);
+    }
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, (variance / n).sqrt())
+}
+
+/// Summary of a duel from player `a`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DuelStats {
+    pub games: usize,
+    pub points: f64,
+    pub wins: usize,
+    /// Gammons include backgammons.
+    pub gammons: usize,
+    pub backgammons: usize,
+    pub losses: usize,
+    pub gammons_lost: usize,
+    pub backgammons_lost: usize,
+    /// Standard error of the points per game. Both games of a seed count as one sample.
+    pub points_error: f64,
+    pub matches: usize,
+    pub matches_won: usize,
+    pub matches_error: f64,
+}
+
+impl DuelReport {
+    pub fn stats(&self) -> DuelStats {
+        let mut stats = DuelStats {
+            games: self.games.len(),
+            matches: self.matches.len(),
+            matches_won: self.matches.iter().filter(|m| m.a_won).count(),
+            ..Default::default()
+        };
+        for game in &self.games {
+            stats.points += game.points as f64;
+            let won = game.result.is_win();
+            let kind = game.result.value().abs() as usize;
+            let (count, gammons, backgammons) = if won {
+                (&mut stats.wins, &mut stats.gammons, &mut stats.backgammons)
+            } else {
+                (
+                    &mut stats.losses,
+                    &mut stats.gammons_lost,
+                    &mut stats.backgammons_lost,
+                )
+            };
+            *count += 1;
+            if kind >= 2 {
+                *gammons += 1;
+            }
+            if kind == 3 {
+                *backgammons += 1;
+            }
+        }
+        stats.points_error = mean_and_error(&per_seed(
+            self.games.iter().map(|g| (g.seed, g.points as f64)),
+        ))
+        .1;
+        stats.matches_error = mean_and_error(&per_seed(
+            self.matches
+                .iter()
+                .map(|m| (m.seed, if m.a_won { 1.0 } else { 0.0 })),
+        ))
+        .1;
+        stats
+    }
+}
+
+/// Averages of the values of each seed, in order of first appearance.
+fn per_seed(values: impl Iterator<Item = (u64, f64)>) -> Vec<f64> {
+    let mut groups: Vec<(u64, f64, usize)> = Vec::new();
+    for (seed, value) in values {
+        match groups.last_mut() {
+            Some((last, sum, count)) if *last == seed => {
+                *sum += value;
+                *count += 1;
+            }
+            _ => groups.push((seed, value, 1)),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(_, sum, count)| sum / count as f64)
+        .collect()
+}
+
+fn rate(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+impl DuelStats {
+    pub fn points_per_game(&self) -> f64 {
+        if self.games == 0 {
+            0.0
+        } else {
+            self.points / self.games as f64
+        }
+    }
+
+    /// Confidence interval of the points per game, for example with [Z_95].
+    pub fn points_interval(&self, z: f64) -> (f64, f64) {
+        let ppg = self.points_per_game();
+        (ppg - z * self.points_error, ppg + z * self.points_error)
+    }
+
+    pub fn win_rate(&self) -> f64 {
+        rate(self.wins, self.games)
+    }
+
+    pub fn gammon_rate(&self) -> f64 {
+        rate(self.gammons, self.games)
+    }
+
+    pub fn backgammon_rate(&self) -> f64 {
+        rate(self.backgammons, self.games)
+    }
+
+    pub fn gammon_loss_rate(&self) -> f64 {
+        rate(self.gammons_lost, self.games)
+    }
+
+    pub fn backgammon_loss_rate(&self) -> f64 {
+        rate(self.backgammons_lost, self.games)
+    }
+
+    pub fn match_win_rate(&self) -> f64 {
+        rate(self.matches_won, self.matches)
+    }
+
+    pub fn match_interval(&self, z: f64) -> (f64, f64) {
+        let p = self.match_win_rate();
+        (p - z * self.matches_error, p + z * self.matches_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{builtin_player, duel, DuelError, DuelOptions};
+    use crate::cube::CubeAction;
+    use crate::engine_spec::parse_engine_spec;
+    use crate::evaluator::RandomPlayer;
+    use crate::features::Encoding;
+    use crate::mlp::{Activation, Mlp};
+    use crate::ubgi::FirstMoveEngine;
+    use crate::Variant;
+
+    #[test]
+    fn duplicate_dice_cancel_out_for_equal_players() {
+        let options = DuelOptions {
+            variant: Variant::Hypergammon,
+            seeds: 5,
+            cubeful: false,
+            ..Default::default()
+        };
+        let mut a = FirstMoveEngine::new(false, CubeAction::Take);
+        let mut b = FirstMoveEngine::new(false, CubeAction::Take);
+        let stats = duel(&mut a, &mut b, &options).unwrap().stats();
+        assert_eq!(stats.games, 10);
+        assert_eq!(stats.points_per_game(), 0.0);
+        assert_eq!(stats.points_interval(super::Z_95), (0.0, 0.0));
+        assert_eq!(stats.wins, stats.losses);
+        assert_eq!(stats.gammons, stats.gammons_lost);
+    }
+
+    #[test]
+    fn both_games_of_a_seed_replay_the_same_dice() {
+        let options = DuelOptions {
+            variant: Variant::Hypergammon,
+            seeds: 1,
+            first_seed: 7,
+            cubeful: false,
+            ..Default::default()
+        };
+        let mut a = FirstMoveEngine::new(false, CubeAction::Take);
+        let mut b = FirstMoveEngine::new(false, CubeAction::Take);
+        let report = duel(&mut a, &mut b, &options).unwrap();
+        let plies = report.games[0].plies;
+        assert_eq!(report.games[1].plies, plies);
+        // The first mover gets the odd plies, `a` moves first in game 1 and `b` in game 2.
+        let (first, second) = (plies.div_ceil(2), plies / 2);
+        assert_eq!(a.rolls.len(), plies);
+        assert_eq!(a.rolls[..first], b.rolls[second..]);
+        assert_eq!(b.rolls[..second], a.rolls[first..]);
+    }
+
+    #[test]
+    fn doubling_out_a_passing_player() {
+        let options = DuelOptions {
+            variant: Variant::Hypergammon,
+            seeds: 3,
+            ..Default::default()
+        };
+        let mut a = FirstMoveEngine::new(true, CubeAction::Take);
+        let mut b = FirstMoveEngine::new(false, CubeAction::Pass);
+        let report = duel(&mut a, &mut b, &options).unwrap();
+        let stats = report.stats();
+        assert_eq!(stats.games, 6);
+        assert_eq!(stats.points_per_game(), 1.0);
+        assert_eq!(stats.win_rate(), 1.0);
+        assert!(report.games.iter().all(|g| g.plies <= 2));
+
+        let options = DuelOptions {
+            match_length: Some(3),
+            ..options
+        };
+        let report = duel(&mut a, &mut b, &options).unwrap();
+        let stats = report.stats();
+        assert_eq!(stats.matches, 6);
+        assert_eq!(stats.match_win_rate(), 1.0);
+        // Two games won by a pass and the Crawford game.
+        assert!(stats.games >= 18);
+    }
+
+    #[test]
+    fn builtin_race_player_beats_random_moves() {
+        let options = DuelOptions {
+            variant: Variant::Hypergammon,
+            seeds: 20,
+            cubeful: false,
+            ..Default::default()
+        };
+        let builtin = |spec: &str| builtin_player(&parse_engine_spec(spec).unwrap());
+        let mut race = builtin("bkgm-race:ply=0").unwrap().unwrap();
+        let mut random = builtin("bkgm-random:seed=1").unwrap().unwrap();
+        let stats = duel(race.as_mut(), random.as_mut(), &options)
+            .unwrap()
+            .stats();
+        assert!(stats.points_per_game() > 0.5, "{}", stats.points_per_game());
+
+        assert!(builtin("gnubg").unwrap().is_none());
+        assert!(matches!(
+            builtin("bkgm-race:ply=9"),
+            Err(DuelError::Engine(_))
+        ));
+        assert!(matches!(builtin("bkgm-pubeval"), Err(DuelError::Engine(_))));
+    }
+
+    #[test]
+    fn builtin_network_player_loads_its_weights() {
+        let path = std::env::temp_dir().join(format!("bkgm-duel-{}.bin", std::process::id()));
+        Mlp::random(Encoding::TdGammon, &[8], Activation::Sigmoid, 1)
+            .save(&path)
+            .unwrap();
+        let spec = format!("bkgm-mlp:weights={},ply=1", path.display());
+        let mut mlp = builtin_player(&parse_engine_spec(&spec).unwrap())
+            .unwrap()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(mlp.name().starts_with("bkgm-mlp "));
+        let mut random = RandomPlayer::with_seed(2);
+        let options = DuelOptions {
+            variant: Variant::Hypergammon,
+            seeds: 2,
+            cubeful: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            duel(mlp.as_mut(), &mut random, &options)
+                .unwrap()
+                .games
+                .len(),
+            4
+        );
+    }
+}
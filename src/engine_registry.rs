@@ -0,0 +1,405 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::engine_spec::{format_engine_spec, EngineSpec};
+use crate::ubgi::{UbgiClient, UbgiClientError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegistryError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("line {line}: {message}")]
+    Parse { line: usize, message: String },
+    #[error("no engine registered as '{0}'")]
+    UnknownEngine(String),
+    #[error("'{0}' has several versions, pick one with @<version>")]
+    AmbiguousVersion(String),
+    #[error("{engine}: {source}")]
+    Client {
+        engine: String,
+        #[source]
+        source: UbgiClientError,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EngineEntry {
+    pub alias: String,
+    pub version: Option<String>,
+    pub path: PathBuf,
+    pub args: Vec<String>,
+    pub cwd: Option<PathBuf>,
+    /// Default options with `engine.` keys, overridden by the options of a spec.
+    pub options: BTreeMap<String, String>,
+}
+
+impl EngineEntry {
+    pub fn new(alias: &str, version: Option<&str>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            alias: alias.to_string(),
+            version: version.map(str::to_string),
+            path: path.into(),
+            args: Vec::new(),
+            cwd: None,
+            options: BTreeMap::new(),
+        }
+    }
+
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.path);
+        command.args(&self.args);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(cwd);
+        }
+        command
+    }
+
+    /// `spec` with the default options of this entry added.
+    pub fn with_defaults(&self, spec: &EngineSpec) -> EngineSpec {
+        let mut options = self.options.clone();
+        options.extend(spec.options.clone());
+        EngineSpec {
+            options,
+            ..spec.clone()
+        }
+    }
+}
+
+/// Engines by alias and version, read from a small TOML-like config:
+///
+/// ```text
+/// # engines.toml
+/// [hawk@v1.3]
+/// path = "engines/hawk-1.3"
+/// args = ["--ubgi", "--threads", "1"]
+/// cwd = "engines"
+/// options.ply = 2
+///
+/// [gnubg]
+/// path = gnubg-ubgi
+/// args = --quiet
+/// ```
+///
+/// Values may be quoted strings, arrays of quoted strings or bare text, where bare `args` are
+/// split on whitespace. Relative paths containing a separator are relative to the config file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineRegistry {
+    entries: Vec<EngineEntry>,
+}
+
+impl EngineRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a config file, resolving relative paths against its directory. The resolved paths
+    /// are absolute, so an engine `path` doesn't depend on its `cwd`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, RegistryError> {
+        let path = std::fs::canonicalize(path)?;
+        let text = std::fs::read_to_string(&path)?;
+        Self::parse_in(&text, path.parent().unwrap_or(Path::new("/")))
+    }
+
+    /// Parses a config, keeping relative paths as they are.
+    pub fn parse(text: &str) -> Result<Self, RegistryError> {
+        Self::parse_in(text, Path::new(""))
+    }
+
+    fn parse_in(text: &str, base: &Path) -> Result<Self, RegistryError> {
+        let mut registry = Self::new();
+        let mut current: Option<(usize, EngineEntry)> = None;
+        for (index, raw) in text.lines().enumerate() {
+            let line_no = index + 1;
+            let error = |message: &str| RegistryError::Parse {
+                line: line_no,
+                message: message.to_string(),
+            };
+            let line = raw.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let name = header
+                    .split_once(']')
+                    .map(|(name, _)| name.trim().trim_matches('"'))
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| error("expected [<alias>@<version>]"))?;
+                if let Some((start, entry)) = current.take() {
+                    registry.finish(start, entry)?;
+                }
+                let (alias, version) = match name.split_once('@') {
+                    Some((alias, version)) => (alias.trim(), Some(version.trim())),
+                    None => (name, None),
+                };
+                current = Some((line_no, EngineEntry::new(alias, version, "")));
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected <key> = <value>"))?;
+            let key = key.trim();
+            let Some((_, entry)) = &mut current else {
+                return Err(error("key outside of an [<alias>] section"));
+            };
+            match key {
+                "path" => entry.path = resolve_path(base, &parse_string(value).map_err(error)?),
+                "cwd" => {
+                    entry.cwd = Some(base.join(parse_string(value).map_err(error)?));
+                }
+                "args" => entry.args = parse_args(value).map_err(error)?,
+                _ => {
+                    let Some(option) = key.strip_prefix("options.") else {
+                        return Err(error(&format!("unknown key '{key}'")));
+                    };
+                    let option = option.trim();
+                    let option = if option.starts_with("engine.") {
+                        option.to_string()
+                    } else {
+                        format!("engine.{option}")
+                    };
+                    entry
+                        .options
+                        .insert(option, parse_string(value).map_err(error)?);
+                }
+            }
+        }
+        if let Some((start, entry)) = current {
+            registry.finish(start, entry)?;
+        }
+        Ok(registry)
+    }
+
+    fn finish(&mut self, line: usize, entry: EngineEntry) -> Result<(), RegistryError> {
+        if entry.path.as_os_str().is_empty() {
+            return Err(RegistryError::Parse {
+                line,
+                message: format!("engine '{}' has no path", entry.alias),
+            });
+        }
+        self.insert(entry);
+        Ok(())
+    }
+
+    /// Adds an engine, replacing one with the same alias and version.
+    pub fn insert(&mut self, entry: EngineEntry) {
+        self.entries
+            .retain(|e| (&e.alias, &e.version) != (&entry.alias, &entry.version));
+        self.entries.push(entry);
+    }
+
+    pub fn entries(&self) -> &[EngineEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, alias: &str, version: Option<&str>) -> Option<&EngineEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.alias == alias && e.version.as_deref() == version)
+    }
+
+    /// Entry of a spec. Without a version, the unversioned entry is used, or the only version
+    /// of the alias.
+    pub fn resolve(&self, spec: &EngineSpec) -> Result<&EngineEntry, RegistryError> {
+        if let Some(entry) = self.get(&spec.alias, spec.version.as_deref()) {
+            return Ok(entry);
+        }
+        let unknown = || RegistryError::UnknownEngine(format_engine_spec(spec));
+        if spec.version.is_some() {
+            return Err(unknown());
+        }
+        let mut versions = self.entries.iter().filter(|e| e.alias == spec.alias);
+        match (versions.next(), versions.next()) {
+            (Some(entry), None) => Ok(entry),
+            (Some(_), Some(_)) => Err(RegistryError::AmbiguousVersion(spec.alias.clone())),
+            (None, _) => Err(unknown()),
+        }
+    }
+
+    /// Starts the engine of `spec` and sets its options, checked against the engine's keys.
+    pub fn launch(
+        &self,
+        spec: &EngineSpec,
+        timeout: Duration,
+    ) -> Result<UbgiClient, RegistryError> {
+        let entry = self.resolve(spec)?;
+        let spec = entry.with_defaults(spec);
+        let client_error = |source| RegistryError::Client {
+            engine: format_engine_spec(&spec),
+            source,
+        };
+        let mut client =
+            UbgiClient::spawn_command(entry.command(), timeout).map_err(client_error)?;
+        client.apply_spec(&spec).map_err(client_error)?;
+        Ok(client)
+    }
+}
+
+fn resolve_path(base: &Path, path: &str) -> PathBuf {
+    let path = Path::new(path);
+    if path.components().count() > 1 && path.is_relative() {
+        base.join(path)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// A quoted string with `\"` and `\\` escapes, or bare text up to a `#` comment.
+fn parse_string(value: &str) -> Result<String, &'static str> {
+    let value = value.trim();
+    let Some(quoted) = value.strip_prefix('"') else {
+        let bare = value.split(" #").next().unwrap_or_default().trim();
+        return Ok(bare.to_string());
+    };
+    let (text, rest) = parse_quoted(quoted)?;
+    let rest = rest.trim();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(text)
+    } else {
+        Err("unexpected text after string")
+    }
+}
+
+/// Parses the rest of a string after its opening quote.
+fn parse_quoted(quoted: &str) -> Result<(String, &str), &'static str> {
+    let mut text = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((text, &quoted[i + 1..])),
+            '\\' => match chars.next() {
+                Some((_, '"')) => text.push('"'),
+                Some((_, '\\')) => text.push('\\'),
+                Some((_, 'n')) => text.push('\n'),
+                Some((_, 't')) => text.push('\t'),
+                _ => return Err("invalid escape"),
+            },
+            c => text.push(c),
+        }
+    }
+    Err("unterminated string")
+}
+
+fn parse_args(value: &str) -> Result<Vec<String>, &'static str> {
+    let value = value.trim();
+    let Some(mut rest) = value.strip_prefix('[') else {
+        return Ok(parse_string(value)?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect());
+    };
+    let mut args = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix(']') {
+            let after = after.trim();
+            return if after.is_empty() || after.starts_with('#') {
+                Ok(args)
+            } else {
+                Err("unexpected text after array")
+            };
+        }
+        let quoted = rest.strip_prefix('"').ok_or("expected a quoted string")?;
+        let (arg, after) = parse_quoted(quoted)?;
+        args.push(arg);
+        rest = after.trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after;
+        } else if !rest.starts_with(']') {
+            return Err("expected , or ]");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EngineRegistry, RegistryError};
+    use crate::parse_engine_spec;
+    use std::path::{Path, PathBuf};
+
+    const CONFIG: &str = r#"
+# engines
+[hawk@v1.3]
+path = "engines/hawk"   # relative to the config
+args = ["--ubgi", "--name", "a \"b\""]
+cwd = "engines"
+options.ply = 2
+
+[hawk@v1.4]
+path = /opt/hawk-1.4
+args = --ubgi --fast
+
+[gnubg]
+path = gnubg
+options.engine.top_k = 8
+"#;
+
+    #[test]
+    fn parses_config_and_resolves_specs() {
+        let registry = EngineRegistry::parse_in(CONFIG, Path::new("/etc/bkgm")).unwrap();
+        assert_eq!(registry.entries().len(), 3);
+
+        let spec = parse_engine_spec("hawk@v1.3:top_k=4").unwrap();
+        let hawk = registry.resolve(&spec).unwrap();
+        assert_eq!(hawk.path, PathBuf::from("/etc/bkgm/engines/hawk"));
+        assert_eq!(hawk.args, ["--ubgi", "--name", "a \"b\""]);
+        assert_eq!(hawk.cwd, Some(PathBuf::from("/etc/bkgm/engines")));
+        let merged = hawk.with_defaults(&spec);
+        assert_eq!(merged.options["engine.ply"], "2");
+        assert_eq!(merged.options["engine.top_k"], "4");
+
+        let newer = registry
+            .resolve(&parse_engine_spec("hawk@v1.4").unwrap())
+            .unwrap();
+        assert_eq!(newer.args, ["--ubgi", "--fast"]);
+        assert_eq!(newer.path, PathBuf::from("/opt/hawk-1.4"));
+
+        let gnubg = registry
+            .resolve(&parse_engine_spec("gnubg").unwrap())
+            .unwrap();
+        assert_eq!(gnubg.path, PathBuf::from("gnubg"));
+        assert_eq!(gnubg.options["engine.top_k"], "8");
+
+        assert!(matches!(
+            registry.resolve(&parse_engine_spec("hawk").unwrap()),
+            Err(RegistryError::AmbiguousVersion(_))
+        ));
+        assert!(matches!(
+            registry.resolve(&parse_engine_spec("gnubg@v2").unwrap()),
+            Err(RegistryError::UnknownEngine(_))
+        ));
+    }
+
+    #[test]
+    fn loads_relative_config_paths_as_absolute() {
+        let dir = PathBuf::from(format!("target/bkgm-registry-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("engines.toml");
+        std::fs::write(&config, CONFIG).unwrap();
+        let registry = EngineRegistry::load(&config);
+        let absolute = std::fs::canonicalize(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let registry = registry.unwrap();
+        let hawk = registry
+            .resolve(&parse_engine_spec("hawk@v1.3").unwrap())
+            .unwrap();
+        assert_eq!(hawk.path, absolute.join("engines/hawk"));
+        assert_eq!(hawk.cwd, Some(absolute.join("engines")));
+        assert_eq!(hawk.command().get_program(), absolute.join("engines/hawk"));
+    }
+
+    #[test]
+    fn reports_config_errors_with_lines() {
+        let err = EngineRegistry::parse("path = x").unwrap_err();
+        assert!(matches!(err, RegistryError::Parse { line: 1, .. }));
+        let err = EngineRegistry::parse("[a]\nargs = [\"x\"\n").unwrap_err();
+        assert!(matches!(err, RegistryError::Parse { line: 2, .. }));
+        let err = EngineRegistry::parse("[a]\npath = a\n[b]\nplies = 2\n").unwrap_err();
+        assert!(matches!(err, RegistryError::Parse { line: 4, .. }));
+        let err = EngineRegistry::parse("[a]\ncwd = /tmp\n").unwrap_err();
+        assert!(matches!(err, RegistryError::Parse { line: 1, .. }));
+    }
+}
@@ -80,6 +80,41 @@ pub fn format_engine_spec(spec: &EngineSpec) -> String {
     }
 }
 
+/// Expands a spec with alternatives such as `hawk:ply=1|2|3,top_k=4|8` into the Cartesian
+/// product of concrete specs, ordered by option key with the last key varying fastest.
+pub fn expand_engine_spec(spec: &str) -> Result<Vec<EngineSpec>, EngineSpecError> {
+    let base = parse_engine_spec(spec)?;
+    let mut specs = vec![EngineSpec {
+        options: BTreeMap::new(),
+        ..base.clone()
+    }];
+    for (key, raw) in &base.options {
+        let mut values: Vec<&str> = Vec::new();
+        for value in raw.split('|').map(str::trim) {
+            if value.is_empty() {
+                return Err(EngineSpecError::InvalidOverride {
+                    part: format!("{}={raw}", key.trim_start_matches("engine.")),
+                    spec: spec.to_string(),
+                });
+            }
+            if !values.contains(&value) {
+                values.push(value);
+            }
+        }
+        specs = specs
+            .into_iter()
+            .flat_map(|partial| {
+                values.iter().map(move |value| {
+                    let mut next = partial.clone();
+                    next.options.insert(key.clone(), value.to_string());
+                    next
+                })
+            })
+            .collect();
+    }
+    Ok(specs)
+}
+
 fn parse_alias_version(head: &str) -> (&str, Option<String>) {
     match head.split_once('@') {
         Some((alias, version)) if !version.trim().is_empty() => {
@@ -91,7 +126,7 @@ fn parse_alias_version(head: &str) -> (&str, Option<String>) {
 
 #[cfg(test)]
 mod tests {
-    use super::{format_engine_spec, parse_engine_spec, EngineSpec};
+    use super::{expand_engine_spec, format_engine_spec, parse_engine_spec, EngineSpec};
     use std::collections::BTreeMap;
 
     #[test]
@@ -120,4 +155,27 @@ mod tests {
         };
         assert_eq!(format_engine_spec(&spec), "gnubg:ply=1");
     }
+
+    #[test]
+    fn expands_parameter_matrix() {
+        let specs = expand_engine_spec("hawk@v1:top_k=4|8,ply=1|2|3").expect("expand spec");
+        let labels: Vec<String> = specs.iter().map(format_engine_spec).collect();
+        assert_eq!(
+            labels,
+            [
+                "hawk@v1:ply=1,top_k=4",
+                "hawk@v1:ply=1,top_k=8",
+                "hawk@v1:ply=2,top_k=4",
+                "hawk@v1:ply=2,top_k=8",
+                "hawk@v1:ply=3,top_k=4",
+                "hawk@v1:ply=3,top_k=8",
+            ]
+        );
+        assert_eq!(
+            expand_engine_spec("hawk:ply=2").unwrap(),
+            vec![parse_engine_spec("hawk:ply=2").unwrap()]
+        );
+        assert_eq!(expand_engine_spec("hawk:ply=1|1").unwrap().len(), 1);
+        assert!(expand_engine_spec("hawk:ply=1||2").is_err());
+    }
 }
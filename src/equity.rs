@@ -0,0 +1,633 @@
+use crate::met::MatchEquityTable;
+use crate::position::GameResult;
+use crate::position::GameResult::*;
+
+/// Cube efficiency commonly used for contact positions. Races are closer to 0.6 to 0.7.
+pub const DEFAULT_CUBE_EFFICIENCY: f32 = 0.68;
+
+/// Cubeless probabilities of all game results, seen from the player on roll.
+///
+/// Gammons include backgammons, so `win >= win_gammon >= win_backgammon`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Probabilities {
+    pub win: f32,
+    pub win_gammon: f32,
+    pub win_backgammon: f32,
+    pub lose_gammon: f32,
+    pub lose_backgammon: f32,
+}
+
+impl Probabilities {
+    pub fn from_result(result: &GameResult) -> Self {
+        let (win, win_gammon, win_backgammon, lose_gammon, lose_backgammon) = match result {
+            WinNormal => (1.0, 0.0, 0.0, 0.0, 0.0),
+            WinGammon => (1.0, 1.0, 0.0, 0.0, 0.0),
+            WinBackgammon => (1.0, 1.0, 1.0, 0.0, 0.0),
+            LoseNormal => (0.0, 0.0, 0.0, 0.0, 0.0),
+            LoseGammon => (0.0, 0.0, 0.0, 1.0, 0.0),
+            LoseBackgammon => (0.0, 0.0, 0.0, 1.0, 1.0),
+        };
+        Self {
+            win,
+            win_gammon,
+            win_backgammon,
+            lose_gammon,
+            lose_backgammon,
+        }
+    }
+
+    /// `win`, `win_gammon`, `win_backgammon`, `lose_gammon` and `lose_backgammon`, in this order.
+    pub fn to_array(&self) -> [f32; 5] {
+        [
+            self.win,
+            self.win_gammon,
+            self.win_backgammon,
+            self.lose_gammon,
+            self.lose_backgammon,
+        ]
+    }
+
+    /// Inverse of [Probabilities::to_array].
+    pub fn from_array(values: [f32; 5]) -> Self {
+        Self {
+            win: values[0],
+            win_gammon: values[1],
+            win_backgammon: values[2],
+            lose_gammon: values[3],
+            lose_backgammon: values[4],
+        }
+    }
+
+    pub fn lose(&self) -> f32 {
+        1.0 - self.win
+    }
+
+    /// The same probabilities seen from the opponent.
+    pub fn flip(&self) -> Self {
+        Self {
+            win: 1.0 - self.win,
+            win_gammon: self.lose_gammon,
+            win_backgammon: self.lose_backgammon,
+            lose_gammon: self.win_gammon,
+            lose_backgammon: self.win_backgammon,
+        }
+    }
+
+    /// Cubeless money equity, between -3 and 3.
+    pub fn equity(&self) -> f32 {
+        self.win - self.lose() + self.win_gammon - self.lose_gammon + self.win_backgammon
+            - self.lose_backgammon
+    }
+
+    /// Average number of points of a win.
+    fn average_win(&self) -> f32 {
+        if self.win > 0.0 {
+            (self.win + self.win_gammon + self.win_backgammon) / self.win
+        } else {
+            1.0
+        }
+    }
+
+    /// Average number of points of a loss.
+    fn average_loss(&self) -> f32 {
+        if self.lose() > 0.0 {
+            (self.lose() + self.lose_gammon + self.lose_backgammon) / self.lose()
+        } else {
+            1.0
+        }
+    }
+
+    /// Winning chance at which the opponent, when doubled, takes a live cube in a money game.
+    pub fn take_point(&self) -> f32 {
+        let (w, l) = (self.average_win(), self.average_loss());
+        (l - 0.5) / (w + l + 0.5)
+    }
+
+    /// Winning chance from which the player's double is a pass in a money game.
+    pub fn cash_point(&self) -> f32 {
+        let (w, l) = (self.average_win(), self.average_loss());
+        (l + 1.0) / (w + l + 0.5)
+    }
+
+    /// Cubeful money equity for a cube value of 1 with the Janowski model.
+    ///
+    /// The live cube equity is piecewise linear in the winning chance, through `-L` at 0, the
+    /// take and cash points and `W` at 1 like gnubg's `Cl2CfMoney`, where `W` and `L` are the
+    /// average wins and losses. `cube_efficiency` mixes it (1) with the cubeless equity (0).
+    pub fn money_cubeful_equity(&self, ownership: CubeOwnership, cube_efficiency: f32) -> f32 {
+        let p = self.win;
+        let (w, l) = (self.average_win(), self.average_loss());
+        let take = self.take_point();
+        let cash = self.cash_point();
+        // From (0, -L) to the take point, where the opponent can double us out.
+        let lost = || -l + (l - 1.0) * p / take;
+        // From the cash point, where we could cash, to (1, W).
+        let won = || 1.0 + (w - 1.0) * (p - cash) / (1.0 - cash);
+        let live = match ownership {
+            CubeOwnership::Owned => {
+                if p < cash {
+                    -l + (1.0 + l) * p / cash
+                } else {
+                    won()
+                }
+            }
+            CubeOwnership::Centered => {
+                if p <= take {
+                    lost()
+                } else if p < cash {
+                    -1.0 + 2.0 * (p - take) / (cash - take)
+                } else {
+                    won()
+                }
+            }
+            CubeOwnership::Unavailable => {
+                if p <= take {
+                    lost()
+                } else {
+                    -1.0 + (w + 1.0) * (p - take) / (1.0 - take)
+                }
+            }
+        };
+        cube_efficiency * live + (1.0 - cube_efficiency) * self.equity()
+    }
+
+    /// Money cube decision of the player on roll, for a cube value of 1.
+    pub fn money_cube_equities(
+        &self,
+        ownership: CubeOwnership,
+        cube_efficiency: f32,
+    ) -> CubeEquities {
+        CubeEquities {
+            ownership,
+            no_double: self.money_cubeful_equity(ownership, cube_efficiency),
+            double_take: 2.0
+                * self.money_cubeful_equity(CubeOwnership::Unavailable, cube_efficiency),
+            double_pass: 1.0,
+        }
+    }
+
+    /// Cubeful match winning chances with the Janowski model, `None` if the score is beyond
+    /// the match equity table.
+    ///
+    /// The live cube equity is interpolated in match winning chances between the take and cash
+    /// points of all higher cube values, which come from the match equity table.
+    pub fn match_cubeful_mwc(
+        &self,
+        context: &MatchContext,
+        ownership: CubeOwnership,
+        cube_efficiency: f32,
+    ) -> Option<f32> {
+        let dead = context.cubeless_mwc(self)?;
+        if context.crawford {
+            return Some(dead);
+        }
+        let model = MatchModel::new(self, context);
+        let (a, b, c) = (context.away, context.opponent_away, context.cube as i32);
+        let p = self.win;
+        let live = match ownership {
+            CubeOwnership::Owned => model.owned_line(a, b, c, true).at(p),
+            CubeOwnership::Unavailable => 1.0 - model.owned_line(b, a, c, false).at(1.0 - p),
+            CubeOwnership::Centered => {
+                // Below `low` the opponent doubles us out, above `high` we cash.
+                let (low, lose_value) = if b - c > 0 {
+                    let pass = model.mwc(a, b - c, true);
+                    (model.take_point(a, b, 2 * c, true, pass), pass)
+                } else {
+                    (0.0, model.lose(a, b, c, true))
+                };
+                let (high, win_value) = if a - c > 0 {
+                    let cash = model.mwc(a - c, b, true);
+                    (1.0 - model.take_point(b, a, 2 * c, false, 1.0 - cash), cash)
+                } else {
+                    (1.0, model.win(a, b, c, true))
+                };
+                if p <= low {
+                    lose_value
+                } else if p >= high {
+                    win_value
+                } else {
+                    lose_value + (win_value - lose_value) * (p - low) / (high - low)
+                }
+            }
+        };
+        Some(cube_efficiency * live + (1.0 - cube_efficiency) * dead)
+    }
+
+    /// Match cube decision of the player on roll, in match winning chances. `None` if the score
+    /// is beyond the match equity table.
+    pub fn match_cube_equities(
+        &self,
+        context: &MatchContext,
+        ownership: CubeOwnership,
+        cube_efficiency: f32,
+    ) -> Option<CubeEquities> {
+        let doubled = MatchContext {
+            cube: context.cube * 2,
+            ..*context
+        };
+        let c = context.cube as i32;
+        Some(CubeEquities {
+            ownership: if context.crawford {
+                CubeOwnership::Unavailable
+            } else {
+                ownership
+            },
+            no_double: self.match_cubeful_mwc(context, ownership, cube_efficiency)?,
+            double_take: self.match_cubeful_mwc(
+                &doubled,
+                CubeOwnership::Unavailable,
+                cube_efficiency,
+            )?,
+            double_pass: context.mwc(context.away - c, context.opponent_away),
+        })
+    }
+}
+
+/// Score of a match game from the player on roll.
+#[derive(Debug, Clone, Copy)]
+pub struct MatchContext<'a> {
+    pub met: &'a MatchEquityTable,
+    pub away: i32,
+    pub opponent_away: i32,
+    pub cube: u32,
+    /// This is the Crawford game, the cube can't be used.
+    pub crawford: bool,
+    pub post_crawford: bool,
+}
+
+impl MatchContext<'_> {
+    /// Chances at a score no higher than the context's, once [Self::cubeless_mwc] found it in the
+    /// table.
+    fn mwc(&self, away: i32, opponent_away: i32) -> f32 {
+        self.met
+            .mwc(away, opponent_away, self.crawford || self.post_crawford)
+            .expect("lower scores are in the table")
+    }
+
+    /// Match winning chances if the game is played to the end with a dead cube, `None` if the
+    /// score is beyond the table.
+    pub fn cubeless_mwc(&self, probabilities: &Probabilities) -> Option<f32> {
+        self.met.cubeless_mwc(
+            probabilities,
+            self.away,
+            self.opponent_away,
+            self.cube,
+            self.crawford || self.post_crawford,
+        )
+    }
+}
+
+/// Linear equity of a live cube between winning chance 0 and a cash point.
+struct Line {
+    cash_point: f32,
+    lose: f32,
+    cash: f32,
+}
+
+impl Line {
+    fn at(&self, p: f32) -> f32 {
+        if p >= self.cash_point {
+            self.cash
+        } else {
+            self.lose + (self.cash - self.lose) * p / self.cash_point
+        }
+    }
+}
+
+/// Match winning chances of wins and losses at every cube value, with the gammon shares of the
+/// position.
+struct MatchModel<'a> {
+    context: &'a MatchContext<'a>,
+    /// Shares of single wins, gammons and backgammons among the wins of the player on roll.
+    wins: [f32; 3],
+    losses: [f32; 3],
+}
+
+impl<'a> MatchModel<'a> {
+    fn new(p: &Probabilities, context: &'a MatchContext<'a>) -> Self {
+        let shares = |total: f32, gammon: f32, backgammon: f32| {
+            if total > 0.0 {
+                [
+                    (total - gammon) / total,
+                    (gammon - backgammon) / total,
+                    backgammon / total,
+                ]
+            } else {
+                [1.0, 0.0, 0.0]
+            }
+        };
+        Self {
+            context,
+            wins: shares(p.win, p.win_gammon, p.win_backgammon),
+            losses: shares(p.lose(), p.lose_gammon, p.lose_backgammon),
+        }
+    }
+
+    /// Average match winning chances of `me` (the player on roll or not), `a` away against `b`,
+    /// when winning a cube of `c`.
+    fn win(&self, a: i32, b: i32, c: i32, me: bool) -> f32 {
+        let shares = if me { self.wins } else { self.losses };
+        (1..=3)
+            .zip(shares)
+            .map(|(k, share)| share * self.mwc(a - k * c, b, me))
+            .sum()
+    }
+
+    fn lose(&self, a: i32, b: i32, c: i32, me: bool) -> f32 {
+        let shares = if me { self.losses } else { self.wins };
+        (1..=3)
+            .zip(shares)
+            .map(|(k, share)| share * self.mwc(a, b - k * c, me))
+            .sum()
+    }
+
+    /// Chances of `me` (or the opponent) `a` away against `b`.
+    fn mwc(&self, a: i32, b: i32, me: bool) -> f32 {
+        if me {
+            self.context.mwc(a, b)
+        } else {
+            1.0 - self.context.mwc(b, a)
+        }
+    }
+
+    fn owned_line(&self, a: i32, b: i32, c: i32, me: bool) -> Line {
+        let lose = self.lose(a, b, c, me);
+        let win = self.win(a, b, c, me);
+        if a - c <= 0 {
+            return Line {
+                cash_point: 1.0,
+                lose,
+                cash: win,
+            };
+        }
+        let cash = self.mwc(a - c, b, me);
+        let take = self.take_point(b, a, 2 * c, !me, 1.0 - cash);
+        if take <= 0.0 {
+            return Line {
+                cash_point: 1.0,
+                lose,
+                cash: win,
+            };
+        }
+        Line {
+            cash_point: 1.0 - take,
+            lose,
+            cash,
+        }
+    }
+
+    /// Lowest winning chance at which a player `a` away takes a cube of `c` instead of
+    /// passing for `pass`.
+    fn take_point(&self, a: i32, b: i32, c: i32, me: bool, pass: f32) -> f32 {
+        let line = self.owned_line(a, b, c, me);
+        if line.cash <= line.lose {
+            return 0.0;
+        }
+        (line.cash_point * (pass - line.lose) / (line.cash - line.lose)).clamp(0.0, line.cash_point)
+    }
+}
+
+/// Equities, or match winning chances, of the three outcomes of a cube decision.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CubeEquities {
+    pub ownership: CubeOwnership,
+    pub no_double: f32,
+    pub double_take: f32,
+    pub double_pass: f32,
+}
+
+impl CubeEquities {
+    pub fn decision(&self) -> CubeDecision {
+        if self.ownership == CubeOwnership::Unavailable {
+            return CubeDecision::NoDouble;
+        }
+        if self.double_take >= self.double_pass {
+            if self.no_double > self.double_pass {
+                CubeDecision::TooGoodToDouble
+            } else {
+                CubeDecision::DoublePass
+            }
+        } else if self.double_take > self.no_double {
+            CubeDecision::DoubleTake
+        } else {
+            CubeDecision::NoDouble
+        }
+    }
+
+    /// Equity with optimal cube decisions of both players.
+    pub fn equity(&self) -> f32 {
+        if self.ownership == CubeOwnership::Unavailable {
+            self.no_double
+        } else {
+            self.no_double.max(self.double_take.min(self.double_pass))
+        }
+    }
+
+    /// Whether the opponent should take a double.
+    pub fn should_take(&self) -> bool {
+        self.double_take < self.double_pass
+    }
+}
+
+/// Who can double, seen from the player on roll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CubeOwnership {
+    Centered,
+    Owned,
+    /// The opponent owns the cube.
+    Unavailable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CubeDecision {
+    NoDouble,
+    DoubleTake,
+    DoublePass,
+    /// The player would win more by playing on for a gammon than by cashing.
+    TooGoodToDouble,
+}
+
+impl CubeDecision {
+    pub fn is_double(&self) -> bool {
+        matches!(self, CubeDecision::DoubleTake | CubeDecision::DoublePass)
+    }
+
+    /// Whether the opponent should take if doubled.
+    pub fn is_take(&self) -> bool {
+        matches!(self, CubeDecision::NoDouble | CubeDecision::DoubleTake)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CubeDecision, CubeOwnership, MatchContext, Probabilities};
+    use crate::met::MatchEquityTable;
+    use crate::position::GameResult;
+
+    fn gammonless(win: f32) -> Probabilities {
+        Probabilities {
+            win,
+            ..Default::default()
+        }
+    }
+
+    fn context(away: i32, opponent_away: i32) -> MatchContext<'static> {
+        MatchContext {
+            met: MatchEquityTable::builtin(),
+            away,
+            opponent_away,
+            cube: 1,
+            crawford: false,
+            post_crawford: false,
+        }
+    }
+
+    #[test]
+    fn equity_of_results() {
+        assert_eq!(
+            Probabilities::from_result(&GameResult::WinNormal).equity(),
+            1.0
+        );
+        assert_eq!(
+            Probabilities::from_result(&GameResult::LoseGammon).equity(),
+            -2.0
+        );
+        assert_eq!(
+            Probabilities::from_result(&GameResult::WinBackgammon).equity(),
+            3.0
+        );
+    }
+
+    #[test]
+    fn flip_matches_reversed_result() {
+        for result in [GameResult::WinGammon, GameResult::LoseBackgammon] {
+            let flipped = Probabilities::from_result(&result).flip();
+            assert_eq!(flipped, Probabilities::from_result(&result.reverse()));
+        }
+        let probabilities = Probabilities::from_result(&GameResult::LoseBackgammon);
+        assert_eq!(probabilities.to_array(), [0.0, 0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(
+            Probabilities::from_array(probabilities.to_array()),
+            probabilities
+        );
+    }
+
+    #[test]
+    fn janowski_money_without_gammons() {
+        let p = gammonless(0.8);
+        assert!((p.take_point() - 0.2).abs() < 1e-6);
+        assert!((p.cash_point() - 0.8).abs() < 1e-6);
+
+        let live = p.money_cube_equities(CubeOwnership::Centered, 1.0);
+        assert!((live.no_double - 1.0).abs() < 1e-5);
+        assert!((live.double_take - 1.0).abs() < 1e-5);
+        assert_eq!(live.decision(), CubeDecision::DoublePass);
+
+        let p = gammonless(0.75);
+        let dead = p.money_cubeful_equity(CubeOwnership::Owned, 0.0);
+        assert!((dead - p.equity()).abs() < 1e-6);
+        let equities = p.money_cube_equities(CubeOwnership::Centered, 0.68);
+        assert!((equities.no_double - 0.7267).abs() < 1e-3);
+        assert!((equities.double_take - 0.83).abs() < 1e-3);
+        assert_eq!(equities.decision(), CubeDecision::DoubleTake);
+        assert!(equities.should_take());
+        assert_eq!(
+            p.money_cube_equities(CubeOwnership::Unavailable, 0.68)
+                .decision(),
+            CubeDecision::NoDouble
+        );
+    }
+
+    #[test]
+    fn janowski_money_outside_the_take_and_cash_points() {
+        let lost = Probabilities {
+            lose_gammon: 0.5,
+            ..Default::default()
+        };
+        for ownership in [CubeOwnership::Centered, CubeOwnership::Unavailable] {
+            assert!((lost.money_cubeful_equity(ownership, 1.0) + 1.5).abs() < 1e-6);
+        }
+
+        let gammon = Probabilities::from_result(&GameResult::WinGammon);
+        let equities = gammon.money_cube_equities(CubeOwnership::Centered, 1.0);
+        assert!((equities.no_double - 2.0).abs() < 1e-6);
+        assert_eq!(equities.decision(), CubeDecision::TooGoodToDouble);
+
+        let gammonish = Probabilities {
+            win: 0.85,
+            win_gammon: 0.5,
+            ..Default::default()
+        };
+        assert!(gammonish.win > gammonish.cash_point());
+        for ownership in [CubeOwnership::Centered, CubeOwnership::Owned] {
+            let equities = gammonish.money_cube_equities(ownership, 1.0);
+            assert!(equities.no_double > 1.0);
+            assert_eq!(equities.decision(), CubeDecision::TooGoodToDouble);
+        }
+    }
+
+    #[test]
+    fn janowski_match_is_symmetric() {
+        let p = Probabilities {
+            win: 0.6,
+            win_gammon: 0.2,
+            win_backgammon: 0.01,
+            lose_gammon: 0.1,
+            lose_backgammon: 0.0,
+        };
+        for (a, b) in [(2, 2), (3, 5), (7, 4)] {
+            for (mine, theirs) in [
+                (CubeOwnership::Centered, CubeOwnership::Centered),
+                (CubeOwnership::Owned, CubeOwnership::Unavailable),
+            ] {
+                let x = p.match_cubeful_mwc(&context(a, b), mine, 0.7).unwrap();
+                let o = p
+                    .flip()
+                    .match_cubeful_mwc(&context(b, a), theirs, 0.7)
+                    .unwrap();
+                assert!((x + o - 1.0).abs() < 1e-5, "{a}-{b}: {x} {o}");
+            }
+        }
+        let owned = p
+            .match_cubeful_mwc(&context(5, 5), CubeOwnership::Owned, 0.7)
+            .unwrap();
+        let centered = p
+            .match_cubeful_mwc(&context(5, 5), CubeOwnership::Centered, 0.7)
+            .unwrap();
+        let unavailable = p
+            .match_cubeful_mwc(&context(5, 5), CubeOwnership::Unavailable, 0.7)
+            .unwrap();
+        assert!(owned > centered && centered > unavailable);
+    }
+
+    #[test]
+    fn janowski_match_cube_decisions() {
+        let crawford = MatchContext {
+            crawford: true,
+            ..context(1, 3)
+        };
+        let p = gammonless(0.9);
+        let equities = p
+            .match_cube_equities(&crawford, CubeOwnership::Centered, 0.7)
+            .unwrap();
+        assert_eq!(equities.decision(), CubeDecision::NoDouble);
+        assert_eq!(Some(equities.no_double), crawford.cubeless_mwc(&p));
+
+        // 2-away 2-away without gammons: the leader can't lose more by doubling.
+        let equities =
+            gammonless(0.6).match_cube_equities(&context(2, 2), CubeOwnership::Centered, 0.7);
+        let equities = equities.unwrap();
+        assert_eq!(equities.decision(), CubeDecision::DoubleTake);
+        assert!((equities.double_take - 0.6).abs() < 1e-6);
+
+        let equities =
+            gammonless(0.95).match_cube_equities(&context(4, 4), CubeOwnership::Centered, 0.7);
+        assert_eq!(equities.unwrap().decision(), CubeDecision::DoublePass);
+
+        // The built-in table ends at 25 points.
+        assert_eq!(
+            gammonless(0.6).match_cube_equities(&context(30, 2), CubeOwnership::Centered, 0.7),
+            None
+        );
+    }
+}
@@ -0,0 +1,83 @@
+mod engine;
+mod pubeval;
+mod race;
+mod random;
+
+pub use engine::{EvaluatorEngine, MAX_ENGINE_PLIES};
+pub use pubeval::{Pubeval, PubevalError, PUBEVAL_CONTACT_FILE, PUBEVAL_INPUTS, PUBEVAL_RACE_FILE};
+pub use race::{keith_counts, RaceHeuristic};
+pub use random::RandomPlayer;
+
+use crate::dice::Dice;
+use crate::equity::Probabilities;
+use crate::position::GameState;
+use crate::variants::VariantPosition;
+
+/// Cubeless evaluation of a position, seen from the player on roll before rolling.
+///
+/// Only called for ongoing games, finished positions are scored by [evaluate_or_result].
+pub trait Evaluator {
+    fn evaluate(&self, position: VariantPosition) -> Probabilities;
+}
+
+impl<E: Evaluator + ?Sized> Evaluator for &E {
+    fn evaluate(&self, position: VariantPosition) -> Probabilities {
+        (**self).evaluate(position)
+    }
+}
+
+impl<E: Evaluator + ?Sized> Evaluator for Box<E> {
+    fn evaluate(&self, position: VariantPosition) -> Probabilities {
+        (**self).evaluate(position)
+    }
+}
+
+/// The result of a finished game, otherwise the evaluation of `evaluator`.
+pub fn evaluate_or_result<E: Evaluator + ?Sized>(
+    evaluator: &E,
+    position: VariantPosition,
+) -> Probabilities {
+    match position.game_state() {
+        GameState::GameOver(result) => Probabilities::from_result(&result),
+        GameState::Ongoing => evaluator.evaluate(position),
+    }
+}
+
+/// Winning chances from the pip counts, ignoring gammons.
+#[cfg(test)]
+pub(crate) struct PipEvaluator;
+
+#[cfg(test)]
+impl Evaluator for PipEvaluator {
+    fn evaluate(&self, position: VariantPosition) -> Probabilities {
+        let difference = crate::variants::with_position!(position, p => {
+            p.pips
+                .iter()
+                .enumerate()
+                .map(|(pip, &count)| {
+                    let count = count as i32;
+                    if count > 0 {
+                        count * pip as i32
+                    } else {
+                        count * (25 - pip as i32)
+                    }
+                })
+                .sum::<i32>()
+        });
+        Probabilities {
+            win: 1.0 / (1.0 + ((difference as f32 - 8.0) / 10.0).exp()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Chooses one of the `legal` positions after rolling `dice`, like the policies of
+/// [crate::Game::play_episode_with].
+pub trait Policy {
+    fn choose(
+        &mut self,
+        position: VariantPosition,
+        dice: &Dice,
+        legal: &[VariantPosition],
+    ) -> usize;
+}
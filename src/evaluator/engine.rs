@@ -0,0 +1,73 @@
+use crate::codecs::move_text;
+use crate::dice::Dice;
+use crate::evaluator::Evaluator;
+use crate::game::Game;
+use crate::search::{Search, SearchOptions};
+use crate::ubgi::{OptionSpec, OptionValue, UbgiEngine, UbgiError, UbgiMove, UbgiResult};
+
+/// Highest `engine.ply` of an [EvaluatorEngine].
+pub const MAX_ENGINE_PLIES: i64 = 3;
+
+/// A cubeless in-process [UbgiEngine] playing the best move of a [Search] with `evaluator`.
+pub struct EvaluatorEngine<E> {
+    name: &'static str,
+    evaluator: E,
+    search: SearchOptions,
+}
+
+impl<E: Evaluator> EvaluatorEngine<E> {
+    pub fn new(name: &'static str, evaluator: E) -> Self {
+        Self {
+            name,
+            evaluator,
+            search: SearchOptions::default(),
+        }
+    }
+
+    pub fn evaluator(&self) -> &E {
+        &self.evaluator
+    }
+}
+
+impl<E: Evaluator> UbgiEngine for EvaluatorEngine<E> {
+    fn id_name(&self) -> &'static str {
+        self.name
+    }
+
+    fn id_version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn options(&self) -> Vec<OptionSpec> {
+        vec![OptionSpec::integer("engine.ply", 0, MAX_ENGINE_PLIES, 0)]
+    }
+
+    fn get(&self, key: &str) -> Option<OptionValue> {
+        (key == "engine.ply").then_some(OptionValue::Int(self.search.plies as i64))
+    }
+
+    fn set(&mut self, key: &str, value: &OptionValue) -> UbgiResult<()> {
+        match (key, value) {
+            ("engine.ply", OptionValue::Int(plies)) if (0..=MAX_ENGINE_PLIES).contains(plies) => {
+                self.search = SearchOptions::new(*plies as usize);
+                Ok(())
+            }
+            ("engine.ply", _) => Err(UbgiError::bad_value(key)),
+            _ => Err(UbgiError::unsupported("key")),
+        }
+    }
+
+    fn choose_move(&mut self, game: &Game, dice: Dice) -> UbgiResult<UbgiMove> {
+        let legal = move_text::legal_steps(game.position(), dice)
+            .map_err(|err| UbgiError::bad_state(err.to_string()))?;
+        let search = Search::new(&self.evaluator, self.search.clone());
+        let Some(best) = search.best_move(game.position(), &dice) else {
+            return Ok(Vec::new());
+        };
+        legal
+            .into_iter()
+            .find(|(_, position)| *position == best.position)
+            .map(|(steps, _)| steps)
+            .ok_or_else(|| UbgiError::bad_state("best move has no steps"))
+    }
+}
@@ -0,0 +1,250 @@
+use std::io;
+use std::path::Path;
+
+use crate::equity::Probabilities;
+use crate::evaluator::Evaluator;
+use crate::position::{GamePhase, OngoingPhase, Position, State};
+use crate::variants::{with_position, VariantPosition};
+
+pub const PUBEVAL_INPUTS: usize = 122;
+/// Files of the race and contact weights, which `pubeval.c` reads from the working directory.
+pub const PUBEVAL_RACE_FILE: &str = "WT.race";
+pub const PUBEVAL_CONTACT_FILE: &str = "WT.cont";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PubevalError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("expected {expected} weights, found {found}")]
+    WeightCount { expected: usize, found: usize },
+    #[error("invalid weight '{0}'")]
+    InvalidWeight(String),
+}
+
+/// Tesauro's public evaluation function: a linear score over 122 inputs, with separate weights
+/// for races and contact positions.
+///
+/// The weights are Tesauro's `wr` and `wc` tables from `pubeval.c`, which are not bundled here.
+/// [Pubeval::parse] reads both tables, race first, so the C arrays can be pasted as they are.
+/// [Pubeval::load_dir] reads the `WT.race` and `WT.cont` files which come with `pubeval.c`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pubeval {
+    race: [f32; PUBEVAL_INPUTS],
+    contact: [f32; PUBEVAL_INPUTS],
+}
+
+impl Pubeval {
+    pub fn new(race: [f32; PUBEVAL_INPUTS], contact: [f32; PUBEVAL_INPUTS]) -> Self {
+        Self { race, contact }
+    }
+
+    /// Reads 244 numbers separated by whitespace, commas or braces. Lines starting with `#` or
+    /// `//` are skipped.
+    pub fn parse(text: &str) -> Result<Self, PubevalError> {
+        let weights = parse_weights(text, 2 * PUBEVAL_INPUTS)?;
+        let mut race = [0.0; PUBEVAL_INPUTS];
+        let mut contact = [0.0; PUBEVAL_INPUTS];
+        race.copy_from_slice(&weights[..PUBEVAL_INPUTS]);
+        contact.copy_from_slice(&weights[PUBEVAL_INPUTS..]);
+        Ok(Self { race, contact })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PubevalError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Reads the 122 race weights and the 122 contact weights from separate files.
+    pub fn load_tables(
+        race: impl AsRef<Path>,
+        contact: impl AsRef<Path>,
+    ) -> Result<Self, PubevalError> {
+        let table = |path: &Path| -> Result<[f32; PUBEVAL_INPUTS], PubevalError> {
+            let weights = parse_weights(&std::fs::read_to_string(path)?, PUBEVAL_INPUTS)?;
+            Ok(weights.try_into().expect("the count is checked"))
+        };
+        Ok(Self {
+            race: table(race.as_ref())?,
+            contact: table(contact.as_ref())?,
+        })
+    }
+
+    /// Reads [PUBEVAL_RACE_FILE] and [PUBEVAL_CONTACT_FILE] of `dir`, like `pubeval.c`.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self, PubevalError> {
+        let dir = dir.as_ref();
+        Self::load_tables(dir.join(PUBEVAL_RACE_FILE), dir.join(PUBEVAL_CONTACT_FILE))
+    }
+
+    /// Inputs of `pubeval.c` for `x`, who has just moved: for each point seen from `x` an
+    /// opponent blot, a blot, a point, a third checker and half of the checkers beyond three,
+    /// then half the opponent's checkers on the bar and the share of `x`'s checkers off.
+    pub fn inputs<const N: u8>(position: &Position<N>) -> [f32; PUBEVAL_INPUTS] {
+        let mut inputs = [0.0; PUBEVAL_INPUTS];
+        for j in 1..=24 {
+            let n = position.pip(25 - j);
+            let unit = &mut inputs[5 * (j - 1)..5 * j];
+            if n == -1 {
+                unit[0] = 1.0;
+            }
+            if n == 1 {
+                unit[1] = 1.0;
+            }
+            if n >= 2 {
+                unit[2] = 1.0;
+            }
+            if n == 3 {
+                unit[3] = 1.0;
+            }
+            if n >= 4 {
+                unit[4] = (n - 3) as f32 / 2.0;
+            }
+        }
+        inputs[120] = position.o_bar() as f32 / 2.0;
+        inputs[121] = position.x_off() as f32 / 15.0;
+        inputs
+    }
+
+    /// Score of `position` for `x`, who has just moved. Higher is better for `x`.
+    pub fn score<const N: u8>(&self, position: &Position<N>) -> f32 {
+        if position.x_off() == N {
+            return 99_999_999.0;
+        }
+        let race = matches!(position.phase(), GamePhase::Ongoing(OngoingPhase::Race));
+        let weights = if race { &self.race } else { &self.contact };
+        Self::inputs(position)
+            .iter()
+            .zip(weights)
+            .map(|(input, weight)| input * weight)
+            .sum()
+    }
+}
+
+/// The score of the opponent, who has just moved, squashed into winning chances. Gammons are
+/// not estimated.
+impl Evaluator for Pubeval {
+    fn evaluate(&self, position: VariantPosition) -> Probabilities {
+        let score = with_position!(position, p => self.score(&p.flip()));
+        Probabilities {
+            win: 1.0 / (1.0 + score.clamp(-50.0, 50.0).exp()),
+            ..Default::default()
+        }
+    }
+}
+
+/// Exactly `expected` weights of [Pubeval::parse]'s format.
+fn parse_weights(text: &str, expected: usize) -> Result<Vec<f32>, PubevalError> {
+    let mut weights = Vec::with_capacity(expected);
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+        for token in line.split(|c: char| c.is_whitespace() || matches!(c, ',' | '{' | '}' | ';')) {
+            if token.is_empty() {
+                continue;
+            }
+            let weight = token
+                .parse::<f32>()
+                .map_err(|_| PubevalError::InvalidWeight(token.to_string()))?;
+            weights.push(weight);
+        }
+    }
+    if weights.len() != expected {
+        return Err(PubevalError::WeightCount {
+            expected,
+            found: weights.len(),
+        });
+    }
+    Ok(weights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pubeval, PubevalError, PUBEVAL_CONTACT_FILE, PUBEVAL_INPUTS, PUBEVAL_RACE_FILE};
+    use crate::evaluator::Evaluator;
+    use crate::position::Position;
+    use crate::variants::{Variant, BACKGAMMON};
+
+    #[test]
+    fn encodes_the_starting_position() {
+        let inputs = Pubeval::inputs(&BACKGAMMON);
+        // Point 24 is encoded first: two checkers of x.
+        assert_eq!(inputs[..5], [0.0, 0.0, 1.0, 0.0, 0.0]);
+        // Point 13 has five checkers.
+        assert_eq!(inputs[5 * 11..5 * 12], [0.0, 0.0, 1.0, 0.0, 1.0]);
+        // Point 8 has three.
+        assert_eq!(inputs[5 * 16..5 * 17], [0.0, 0.0, 1.0, 1.0, 0.0]);
+        // The opponent's points are not encoded, only its blots.
+        assert_eq!(inputs[5 * 23..5 * 24], [0.0; 5]);
+        assert_eq!(inputs[120..], [0.0, 0.0]);
+
+        let mut pips = [0; 26];
+        pips[0] = -2;
+        pips[3] = -1;
+        pips[6] = 6;
+        let position = Position::<15> {
+            turn: true,
+            pips,
+            x_off: 9,
+            o_off: 12,
+        };
+        let inputs = Pubeval::inputs(&position);
+        assert_eq!(inputs[5 * 18..5 * 19], [0.0, 0.0, 1.0, 0.0, 1.5]);
+        assert_eq!(inputs[5 * 21], 1.0);
+        assert_eq!(inputs[120..], [1.0, 0.6]);
+    }
+
+    #[test]
+    fn parses_weights_and_scores() {
+        let mut text = String::from("// race\n{");
+        for i in 0..PUBEVAL_INPUTS {
+            text.push_str(&format!("{}, ", i as f32 / 100.0));
+        }
+        text.push_str("};\n# contact\n");
+        text.push_str(&"1.0 ".repeat(PUBEVAL_INPUTS));
+        let pubeval = Pubeval::parse(&text).unwrap();
+        assert_eq!(pubeval.race[121], 1.21);
+        // The starting position is contact, with four points and two spares each on 13 and 6.
+        assert_eq!(pubeval.score(&BACKGAMMON), 7.0);
+        let start = Variant::Backgammon.start_position();
+        assert!(pubeval.evaluate(start).win < 0.5);
+
+        assert!(matches!(
+            Pubeval::parse("1 2 3"),
+            Err(PubevalError::WeightCount {
+                expected: 244,
+                found: 3
+            })
+        ));
+        assert!(matches!(
+            Pubeval::parse("1 x"),
+            Err(PubevalError::InvalidWeight(token)) if token == "x"
+        ));
+    }
+
+    #[test]
+    fn loads_the_tables_of_pubeval_c() {
+        let dir = std::env::temp_dir().join(format!("bkgm-pubeval-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(PUBEVAL_RACE_FILE), "0.5\n".repeat(PUBEVAL_INPUTS)).unwrap();
+        std::fs::write(
+            dir.join(PUBEVAL_CONTACT_FILE),
+            "1.0\n".repeat(PUBEVAL_INPUTS),
+        )
+        .unwrap();
+        let loaded = Pubeval::load_dir(&dir);
+        std::fs::write(dir.join(PUBEVAL_CONTACT_FILE), "1.0\n".repeat(2)).unwrap();
+        let short = Pubeval::load_dir(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let pubeval = loaded.unwrap();
+        assert_eq!(pubeval.race, [0.5; PUBEVAL_INPUTS]);
+        assert_eq!(pubeval.score(&BACKGAMMON), 7.0);
+        assert!(matches!(
+            short,
+            Err(PubevalError::WeightCount {
+                expected: PUBEVAL_INPUTS,
+                found: 2
+            })
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::equity::Probabilities;
+use crate::evaluator::Evaluator;
+use crate::position::{Position, State};
+use crate::variants::{with_position, VariantPosition};
+
+/// Keith counts of the player on roll and of the opponent: the pip count plus 2 for each checker
+/// beyond the first on the 1-point, 1 for each beyond the first on the 2-point, 1 for each beyond
+/// the third on the 3-point and 1 for each empty 4, 5 and 6-point.
+pub fn keith_counts(position: VariantPosition) -> (u32, u32) {
+    with_position!(position, p => counts(&p))
+}
+
+fn counts<const N: u8>(position: &Position<N>) -> (u32, u32) {
+    let mut own = 0;
+    let mut other = 0;
+    for pip in 0..26 {
+        let count = position.pip(pip) as i32;
+        if count > 0 {
+            own += count * pip as i32;
+        } else {
+            other -= count * (25 - pip as i32);
+        }
+    }
+    // The home points of `o` are the same points seen from the other side.
+    let home = |point: usize| {
+        (
+            position.pip(point).max(0) as i32,
+            (-position.pip(25 - point)).max(0) as i32,
+        )
+    };
+    let wastage = |count: i32, point: usize| match point {
+        1 => 2 * (count - 1).max(0),
+        2 => (count - 1).max(0),
+        3 => (count - 3).max(0),
+        _ => (count == 0) as i32,
+    };
+    for point in 1..=6 {
+        let (x, o) = home(point);
+        own += wastage(x, point);
+        other += wastage(o, point);
+    }
+    (own as u32, other as u32)
+}
+
+/// Race evaluation from Keith counts, with Kleinman's normal approximation of the winning
+/// chances. Contact positions are treated as races. Gammons are not estimated.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RaceHeuristic;
+
+impl Evaluator for RaceHeuristic {
+    fn evaluate(&self, position: VariantPosition) -> Probabilities {
+        let (own, other) = keith_counts(position);
+        // Being on roll is worth about 4 pips.
+        let lead = other as f32 - own as f32 + 4.0;
+        let spread = (2.0 * (own + other) as f32 - 8.0).max(1.0).sqrt();
+        Probabilities {
+            win: normal_cdf(lead / spread),
+            ..Default::default()
+        }
+    }
+}
+
+fn normal_cdf(x: f32) -> f32 {
+    0.5 * (1.0 + erf(x / std::f32::consts::SQRT_2))
+}
+
+/// Abramowitz and Stegun 7.1.26, accurate to 1.5e-7.
+fn erf(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let poly = t
+        * (0.254_829_6
+            + t * (-0.284_496_74 + t * (1.421_413_8 + t * (-1.453_152_1 + t * 1.061_405_4))));
+    let y = 1.0 - poly * (-x * x).exp();
+    y.copysign(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{erf, keith_counts, RaceHeuristic};
+    use crate::evaluator::Evaluator;
+    use crate::position::Position;
+    use crate::variants::{Variant, VariantPosition};
+
+    #[test]
+    fn counts_pips_and_wastage() {
+        // 167 pips each and the 4 and 5-points are empty.
+        let start = Variant::Backgammon.start_position();
+        assert_eq!(keith_counts(start), (169, 169));
+
+        let mut pips = [0; 26];
+        pips[1] = 3;
+        pips[2] = 2;
+        pips[3] = 4;
+        pips[24] = -5;
+        pips[19] = -1;
+        let position = VariantPosition::Backgammon(Position {
+            turn: true,
+            pips,
+            x_off: 6,
+            o_off: 9,
+        });
+        // x: 3 + 4 + 12 pips, 4 + 1 + 1 wastage, 3 empty points.
+        // o: 5 + 6 pips, 8 wastage, the 4 and 5-points are empty.
+        assert_eq!(keith_counts(position), (28, 21));
+    }
+
+    #[test]
+    fn estimates_race_chances() {
+        assert!((erf(0.5) - 0.520_499_9).abs() < 1e-6);
+        assert!((erf(-1.0) + 0.842_700_8).abs() < 1e-6);
+
+        let start = Variant::Backgammon.start_position();
+        let win = RaceHeuristic.evaluate(start).win;
+        assert!(win > 0.5 && win < 0.6, "{win}");
+
+        // 6 pips ahead and on roll in a long race is a clear favourite.
+        let mut pips = [0; 26];
+        pips[6] = 10;
+        pips[5] = 5;
+        pips[19] = -15;
+        let position = VariantPosition::Backgammon(Position {
+            turn: true,
+            pips,
+            x_off: 0,
+            o_off: 0,
+        });
+        let (own, other) = keith_counts(position);
+        assert_eq!((own, other), (86, 92));
+        let win = RaceHeuristic.evaluate(position).win;
+        assert!(win > 0.65 && win < 0.8, "{win}");
+    }
+}
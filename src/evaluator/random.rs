@@ -0,0 +1,104 @@
+use crate::codecs::move_text;
+use crate::dice::Dice;
+use crate::evaluator::Policy;
+use crate::game::Game;
+use crate::ubgi::{OptionSpec, OptionValue, UbgiEngine, UbgiError, UbgiMove, UbgiResult};
+use crate::variants::VariantPosition;
+
+/// Plays a uniformly random legal move, as a [Policy] or as a cubeless [UbgiEngine] whose
+/// `engine.seed` reseeds the generator.
+#[derive(Debug, Clone)]
+pub struct RandomPlayer {
+    seed: i64,
+    rng: fastrand::Rng,
+}
+
+impl RandomPlayer {
+    pub fn new() -> Self {
+        Self::with_seed(fastrand::u64(..))
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            seed: seed as i64,
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+}
+
+impl Default for RandomPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Policy for RandomPlayer {
+    fn choose(
+        &mut self,
+        _position: VariantPosition,
+        _dice: &Dice,
+        legal: &[VariantPosition],
+    ) -> usize {
+        self.rng.usize(..legal.len().max(1))
+    }
+}
+
+impl UbgiEngine for RandomPlayer {
+    fn id_name(&self) -> &'static str {
+        "bkgm-random"
+    }
+
+    fn id_version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    fn options(&self) -> Vec<OptionSpec> {
+        vec![OptionSpec::integer_unbounded("engine.seed", self.seed)]
+    }
+
+    fn get(&self, key: &str) -> Option<OptionValue> {
+        (key == "engine.seed").then_some(OptionValue::Int(self.seed))
+    }
+
+    fn set(&mut self, key: &str, value: &OptionValue) -> UbgiResult<()> {
+        match (key, value) {
+            ("engine.seed", OptionValue::Int(seed)) => {
+                *self = Self::with_seed(*seed as u64);
+                Ok(())
+            }
+            _ => Err(UbgiError::unsupported("key")),
+        }
+    }
+
+    fn choose_move(&mut self, game: &Game, dice: Dice) -> UbgiResult<UbgiMove> {
+        let mut legal = move_text::legal_steps(game.position(), dice)
+            .map_err(|err| UbgiError::bad_state(err.to_string()))?;
+        if legal.is_empty() {
+            return Ok(Vec::new());
+        }
+        let index = self.rng.usize(..legal.len());
+        Ok(legal.swap_remove(index).0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RandomPlayer;
+    use crate::dice::Dice;
+    use crate::evaluator::Policy;
+    use crate::rules::{ClassicRules, VariantRules};
+    use crate::variants::Variant;
+
+    #[test]
+    fn seeded_players_repeat_their_moves() {
+        let start = Variant::Backgammon.start_position();
+        let dice = Dice::new(6, 4);
+        let legal = <ClassicRules as VariantRules>::legal_positions(start, &dice);
+        let mut a = RandomPlayer::with_seed(3);
+        let mut b = RandomPlayer::with_seed(3);
+        let choices: Vec<usize> = (0..20).map(|_| a.choose(start, &dice, &legal)).collect();
+        assert!(choices.iter().all(|&i| i < legal.len()));
+        assert!(choices.iter().any(|&i| i != choices[0]));
+        assert!(choices.iter().all(|&i| i == b.choose(start, &dice, &legal)));
+    }
+}
@@ -0,0 +1,369 @@
+use core::str::FromStr;
+use std::fmt;
+
+use crate::position::{GamePhase, OngoingPhase, Position, State};
+use crate::variants::{with_position, VariantPosition};
+
+pub const TD_GAMMON_INPUTS: usize = 196;
+pub const GNUBG_CONTACT_INPUTS: usize = 220;
+pub const GNUBG_RACE_INPUTS: usize = 214;
+pub const WILDBG_INPUTS: usize = 202;
+
+/// Units of the checkers off in [gnubg_race], enough for 15 checkers.
+const RACE_OFF_UNITS: usize = 14;
+
+/// The checkers of one side, seen from that side: `points[1..=24]` are its points and
+/// `points[25]` is its bar.
+struct Side {
+    points: [u8; 26],
+    off: u8,
+}
+
+impl Side {
+    fn count(&self, point: usize) -> f32 {
+        self.points[point] as f32
+    }
+
+    /// Rearmost point with a checker, 25 for the bar, 0 if all checkers are off.
+    fn back(&self) -> usize {
+        (1..=25).rev().find(|&p| self.points[p] > 0).unwrap_or(0)
+    }
+
+    fn made(&self, point: usize) -> bool {
+        self.points[point] >= 2
+    }
+}
+
+/// `x`, the player on roll, and `o`.
+fn sides<const N: u8>(position: &Position<N>) -> [Side; 2] {
+    let mut x = Side {
+        points: [0; 26],
+        off: position.x_off(),
+    };
+    let mut o = Side {
+        points: [0; 26],
+        off: position.o_off(),
+    };
+    for point in 1..=24 {
+        x.points[point] = position.pip(point).max(0) as u8;
+        o.points[point] = (-position.pip(25 - point)).max(0) as u8;
+    }
+    x.points[25] = position.x_bar();
+    o.points[25] = position.o_bar();
+    [x, o]
+}
+
+/// Tesauro's TD-Gammon encoding: four units per point and side for at least one, two and three
+/// checkers and half the checkers beyond three, `x` first, then half the checkers on each bar
+/// and the share of each side's checkers off.
+pub fn td_gammon<const N: u8>(position: &Position<N>, inputs: &mut [f32]) {
+    assert_eq!(inputs.len(), TD_GAMMON_INPUTS);
+    let sides = sides(position);
+    for (side, units) in sides.iter().zip(inputs[..192].chunks_exact_mut(96)) {
+        for (point, unit) in (1..=24).zip(units.chunks_exact_mut(4)) {
+            let n = side.count(point);
+            unit[0] = (n >= 1.0) as u8 as f32;
+            unit[1] = (n >= 2.0) as u8 as f32;
+            unit[2] = (n >= 3.0) as u8 as f32;
+            unit[3] = ((n - 3.0) / 2.0).max(0.0);
+        }
+    }
+    for (i, side) in sides.iter().enumerate() {
+        inputs[192 + i] = side.count(25) / 2.0;
+        inputs[194 + i] = side.off as f32 / N as f32;
+    }
+}
+
+/// gnubg's race inputs: per side four units for one, two, three and more checkers on each of
+/// the 23 points a race can use, a thermometer of the checkers off and the home board crossings
+/// still to make.
+pub fn gnubg_race<const N: u8>(position: &Position<N>, inputs: &mut [f32]) {
+    assert_eq!(inputs.len(), GNUBG_RACE_INPUTS);
+    inputs.fill(0.0);
+    for (side, units) in sides(position)
+        .iter()
+        .zip(inputs.chunks_exact_mut(GNUBG_RACE_INPUTS / 2))
+    {
+        for (point, unit) in (1..=23).zip(units.chunks_exact_mut(4)) {
+            let n = side.points[point];
+            match n {
+                0 => {}
+                1..=3 => unit[n as usize - 1] = 1.0,
+                _ => unit[3] = (n - 3) as f32 / 2.0,
+            }
+        }
+        for unit in &mut units[92..92 + (side.off as usize).min(RACE_OFF_UNITS)] {
+            *unit = 1.0;
+        }
+        let crossings: usize = (7..=24)
+            .map(|point| side.points[point] as usize * ((point - 1) / 6))
+            .sum();
+        units[92 + RACE_OFF_UNITS] = crossings as f32 / 10.0;
+    }
+}
+
+/// gnubg-style contact inputs: per side four units for one, two, at least three and half the
+/// checkers beyond three on each point and the bar, followed by ten positional features:
+///
+/// 0-2. checkers off, in thirds of the checkers
+/// 3. pips to move to break contact with the opponent's rearmost checker, over 152
+/// 4. rearmost checker and 5. rearmost anchor, over 24
+/// 6. most forward anchor in the opponent's home board, from 1/6 on the 24-point to 1 on the
+///    19-point
+/// 7. the share of rolls which fail to enter from the bar
+/// 8. home board points and 9. the longest prime, over 6
+pub fn gnubg_contact<const N: u8>(position: &Position<N>, inputs: &mut [f32]) {
+    assert_eq!(inputs.len(), GNUBG_CONTACT_INPUTS);
+    let sides = sides(position);
+    for (i, units) in inputs
+        .chunks_exact_mut(GNUBG_CONTACT_INPUTS / 2)
+        .enumerate()
+    {
+        let (side, other) = (&sides[i], &sides[1 - i]);
+        for (point, unit) in (1..=25).zip(units[..100].chunks_exact_mut(4)) {
+            let n = side.points[point];
+            unit[0] = (n == 1) as u8 as f32;
+            unit[1] = (n == 2) as u8 as f32;
+            unit[2] = (n >= 3) as u8 as f32;
+            unit[3] = (n.max(3) - 3) as f32 / 2.0;
+        }
+
+        let third = N as f32 / 3.0;
+        let off = side.off as f32;
+        for (k, unit) in units[100..103].iter_mut().enumerate() {
+            *unit = ((off - k as f32 * third) / third).clamp(0.0, 1.0);
+        }
+
+        // The opponent's rearmost checker, on our side of the board.
+        let contact = 25 - other.back().max(1);
+        let break_contact: usize = (contact + 1..=25)
+            .map(|point| side.points[point] as usize * (point + 1 - contact))
+            .sum();
+        units[103] = break_contact as f32 / (152.0 * N as f32 / 15.0);
+
+        units[104] = side.back() as f32 / 24.0;
+        units[105] = (1..=24)
+            .rev()
+            .find(|&p| side.made(p))
+            .map_or(0.0, |p| p as f32 / 24.0);
+        units[106] = (19..=24)
+            .find(|&p| side.made(p))
+            .map_or(0.0, |p| (25 - p) as f32 / 6.0);
+        units[107] = if side.points[25] > 0 {
+            let closed = (1..=6).filter(|&p| other.made(p)).count() as f32;
+            (closed / 6.0).powi(2)
+        } else {
+            0.0
+        };
+        units[108] = (1..=6).filter(|&p| side.made(p)).count() as f32 / 6.0;
+        let mut prime = 0;
+        let mut run = 0;
+        for point in 1..=24 {
+            run = if side.made(point) { run + 1 } else { 0 };
+            prime = prime.max(run);
+        }
+        units[109] = prime.min(6) as f32 / 6.0;
+    }
+}
+
+/// wildbg-style one-hot inputs: per side four units on the bar and each point for exactly one,
+/// two and three checkers and the checkers beyond three, `x` first, then the share of each
+/// side's checkers off.
+pub fn wildbg<const N: u8>(position: &Position<N>, inputs: &mut [f32]) {
+    assert_eq!(inputs.len(), WILDBG_INPUTS);
+    inputs.fill(0.0);
+    let sides = sides(position);
+    for (side, units) in sides.iter().zip(inputs[..200].chunks_exact_mut(100)) {
+        for (point, unit) in [25]
+            .into_iter()
+            .chain(1..=24)
+            .zip(units.chunks_exact_mut(4))
+        {
+            match side.points[point] {
+                0 => {}
+                n @ 1..=3 => unit[n as usize - 1] = 1.0,
+                n => unit[3] = (n - 3) as f32,
+            }
+        }
+    }
+    for (i, side) in sides.iter().enumerate() {
+        inputs[200 + i] = side.off as f32 / N as f32;
+    }
+}
+
+/// The input encodings by name, for tools and weight files which select one at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    TdGammon,
+    /// [gnubg_race] for races, otherwise [gnubg_contact], zero padded to [GNUBG_CONTACT_INPUTS].
+    Gnubg,
+    GnubgContact,
+    GnubgRace,
+    Wildbg,
+}
+
+pub const ENCODINGS: &[Encoding] = &[
+    Encoding::TdGammon,
+    Encoding::Gnubg,
+    Encoding::GnubgContact,
+    Encoding::GnubgRace,
+    Encoding::Wildbg,
+];
+
+impl Encoding {
+    pub fn inputs(self) -> usize {
+        match self {
+            Encoding::TdGammon => TD_GAMMON_INPUTS,
+            Encoding::Gnubg | Encoding::GnubgContact => GNUBG_CONTACT_INPUTS,
+            Encoding::GnubgRace => GNUBG_RACE_INPUTS,
+            Encoding::Wildbg => WILDBG_INPUTS,
+        }
+    }
+
+    /// Writes the inputs of `position`, whose length must be [Self::inputs].
+    pub fn encode<const N: u8>(self, position: &Position<N>, inputs: &mut [f32]) {
+        match self {
+            Encoding::TdGammon => td_gammon(position, inputs),
+            Encoding::Gnubg => {
+                if matches!(position.phase(), GamePhase::Ongoing(OngoingPhase::Race)) {
+                    gnubg_race(position, &mut inputs[..GNUBG_RACE_INPUTS]);
+                    inputs[GNUBG_RACE_INPUTS..].fill(0.0);
+                } else {
+                    gnubg_contact(position, inputs);
+                }
+            }
+            Encoding::GnubgContact => gnubg_contact(position, inputs),
+            Encoding::GnubgRace => gnubg_race(position, inputs),
+            Encoding::Wildbg => wildbg(position, inputs),
+        }
+    }
+
+    pub fn encode_variant(self, position: VariantPosition, inputs: &mut [f32]) {
+        with_position!(position, p => self.encode(&p, inputs))
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Encoding::TdGammon => "td-gammon",
+            Encoding::Gnubg => "gnubg",
+            Encoding::GnubgContact => "gnubg-contact",
+            Encoding::GnubgRace => "gnubg-race",
+            Encoding::Wildbg => "wildbg",
+        };
+        f.write_str(s)
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ENCODINGS
+            .iter()
+            .copied()
+            .find(|encoding| encoding.to_string() == s.trim().to_ascii_lowercase())
+            .ok_or(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        gnubg_contact, gnubg_race, td_gammon, wildbg, Encoding, ENCODINGS, GNUBG_CONTACT_INPUTS,
+        GNUBG_RACE_INPUTS, TD_GAMMON_INPUTS, WILDBG_INPUTS,
+    };
+    use crate::codecs::gnuid::decode_position;
+    use crate::position::{Position, State};
+    use crate::variants::{Variant, BACKGAMMON, HYPERGAMMON};
+
+    #[test]
+    fn encodes_the_starting_position() {
+        let mut inputs = [f32::NAN; TD_GAMMON_INPUTS];
+        td_gammon(&BACKGAMMON, &mut inputs);
+        // Five checkers on the 6-point, three on the 8-point and the two back checkers.
+        assert_eq!(inputs[4 * 5..4 * 6], [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(inputs[4 * 7..4 * 8], [1.0, 1.0, 1.0, 0.0]);
+        assert_eq!(inputs[4 * 23..4 * 24], [1.0, 1.0, 0.0, 0.0]);
+        assert_eq!(inputs[96 + 4 * 12..96 + 4 * 13], [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(inputs[192..], [0.0; 4]);
+        assert_eq!(inputs.iter().sum::<f32>(), 2.0 * (4.0 + 3.0 + 4.0 + 2.0));
+
+        let mut inputs = [f32::NAN; WILDBG_INPUTS];
+        wildbg(&BACKGAMMON, &mut inputs);
+        assert_eq!(inputs[..4], [0.0; 4]);
+        assert_eq!(inputs[4 * 6..4 * 7], [0.0, 0.0, 0.0, 2.0]);
+        assert_eq!(inputs[4 * 8..4 * 9], [0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(inputs[100 + 4 * 24..200], [0.0, 1.0, 0.0, 0.0]);
+
+        let mut inputs = [f32::NAN; GNUBG_CONTACT_INPUTS];
+        gnubg_contact(&BACKGAMMON, &mut inputs);
+        let features = &inputs[100..110];
+        // The opponent's back checkers are on the 1-point, so contact lasts until bearing off.
+        assert_eq!(features[..3], [0.0; 3]);
+        assert_eq!(features[3], 167.0 / 152.0);
+        assert_eq!(features[4..7], [1.0, 1.0, 1.0 / 6.0]);
+        assert_eq!(features[7..], [0.0, 1.0 / 6.0, 1.0 / 6.0]);
+        assert_eq!(inputs[..110], inputs[110..]);
+    }
+
+    #[test]
+    fn sides_swap_when_flipped() {
+        let position = decode_position::<15>("4HPwATDgc/ABMA").unwrap();
+        let flipped = position.flip();
+        // The first half of `x`'s inputs against the matching inputs of `o`.
+        for (encoding, half, o) in [
+            (Encoding::TdGammon, 96, 96),
+            (Encoding::GnubgContact, 110, 110),
+            (Encoding::Wildbg, 100, 100),
+        ] {
+            let mut inputs = vec![f32::NAN; encoding.inputs()];
+            let mut swapped = vec![f32::NAN; encoding.inputs()];
+            encoding.encode(&position, &mut inputs);
+            encoding.encode(&flipped, &mut swapped);
+            assert_eq!(inputs[..half], swapped[o..o + half], "{encoding}");
+            assert_eq!(swapped[..half], inputs[o..o + half], "{encoding}");
+        }
+    }
+
+    #[test]
+    fn encodes_races_and_hypergammon() {
+        let mut pips = [0; 26];
+        pips[1] = 4;
+        pips[2] = 3;
+        pips[9] = 1;
+        pips[13] = 2;
+        pips[24] = -10;
+        let position = Position::<15> {
+            turn: true,
+            pips,
+            x_off: 5,
+            o_off: 5,
+        };
+        let mut inputs = [f32::NAN; GNUBG_RACE_INPUTS];
+        gnubg_race(&position, &mut inputs);
+        assert_eq!(inputs[..4], [0.0, 0.0, 0.0, 0.5]);
+        assert_eq!(inputs[4..8], [0.0, 0.0, 1.0, 0.0]);
+        assert_eq!(inputs[92..106].iter().sum::<f32>(), 5.0);
+        // One crossing for the 9-point, two each for the 13-point.
+        assert_eq!(inputs[106], 0.5);
+        assert_eq!(inputs[107..111], [0.0, 0.0, 0.0, 3.5]);
+
+        let mut padded = [f32::NAN; GNUBG_CONTACT_INPUTS];
+        Encoding::Gnubg.encode(&position, &mut padded);
+        assert_eq!(padded[..GNUBG_RACE_INPUTS], inputs);
+        assert!(padded[GNUBG_RACE_INPUTS..].iter().all(|&x| x == 0.0));
+
+        for encoding in ENCODINGS {
+            let mut inputs = vec![f32::NAN; encoding.inputs()];
+            encoding.encode(&HYPERGAMMON, &mut inputs);
+            assert!(inputs.iter().all(|x| x.is_finite()), "{encoding}");
+            assert_eq!(encoding.to_string().parse(), Ok(*encoding));
+            encoding.encode_variant(Variant::Hypergammon.start_position(), &mut inputs);
+        }
+        let mut inputs = [0.0; TD_GAMMON_INPUTS];
+        td_gammon(&HYPERGAMMON, &mut inputs);
+        assert_eq!(inputs.iter().sum::<f32>(), 2.0 * 3.0);
+    }
+}
@@ -1,6 +1,7 @@
+use crate::cube::{CubeAction, CubeState, IllegalCubeAction};
 use crate::dice::Dice;
 use crate::dice_gen::DiceGen;
-use crate::position::{GamePhase, GameState};
+use crate::position::{GamePhase, GameResult, GameState};
 use crate::rules::{ClassicRules, VariantRules};
 use crate::variants::{Variant, VariantPosition};
 
@@ -12,19 +13,40 @@ pub enum GameError {
     LegalMoveIndexOutOfBounds,
     #[error("policy returned out-of-bounds legal move index")]
     PolicyIndexOutOfBounds,
+    #[error("illegal cube action {0:?}")]
+    IllegalCubeAction(CubeAction),
+    #[error("a double has to be answered first")]
+    CubeActionPending,
+    #[error("game is over")]
+    GameOver,
+}
+
+impl From<IllegalCubeAction> for GameError {
+    fn from(value: IllegalCubeAction) -> Self {
+        Self::IllegalCubeAction(value.0)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Game {
     variant: Variant,
     position: VariantPosition,
+    cube: CubeState,
+    /// The opponent passed a double, so the player on roll won.
+    passed: bool,
 }
 
 impl Game {
     pub fn new(variant: Variant) -> Self {
+        Self::with_cube(variant, CubeState::new())
+    }
+
+    pub fn with_cube(variant: Variant, cube: CubeState) -> Self {
         Self {
             variant,
             position: variant.start_position(),
+            cube,
+            passed: false,
         }
     }
 
@@ -44,8 +66,37 @@ impl Game {
         Ok(())
     }
 
+    pub fn cube(&self) -> CubeState {
+        self.cube
+    }
+
+    pub fn set_cube(&mut self, cube: CubeState) {
+        self.cube = cube;
+    }
+
     pub fn reset(&mut self) {
         self.position = self.variant.start_position();
+        self.cube.reset();
+        self.passed = false;
+    }
+
+    /// Cube actions available now, see [CubeState::legal_actions].
+    pub fn legal_cube_actions(&self) -> Vec<CubeAction> {
+        if self.game_state() != GameState::Ongoing {
+            return Vec::new();
+        }
+        self.cube.legal_actions(self.position.turn())
+    }
+
+    pub fn apply_cube_action(&mut self, action: CubeAction) -> Result<(), GameError> {
+        if self.game_state() != GameState::Ongoing {
+            return Err(GameError::GameOver);
+        }
+        self.cube.apply(action, self.position.turn())?;
+        if action == CubeAction::Pass {
+            self.passed = true;
+        }
+        Ok(())
     }
 
     pub fn legal_positions(&self, dice: &Dice) -> Vec<VariantPosition> {
@@ -65,11 +116,18 @@ impl Game {
         dice: &Dice,
         index: usize,
     ) -> Result<(), GameError> {
+        if self.passed {
+            return Err(GameError::GameOver);
+        }
+        if !self.cube.is_settled() {
+            return Err(GameError::CubeActionPending);
+        }
         let legal = self.legal_positions_with::<R>(dice);
         if index >= legal.len() {
             return Err(GameError::LegalMoveIndexOutOfBounds);
         }
         self.position = legal[index];
+        self.cube.roll();
         Ok(())
     }
 
@@ -77,23 +135,68 @@ impl Game {
         self.position.phase()
     }
 
+    /// State of the game, seen from the player on roll. A passed double is a single win.
     pub fn game_state(&self) -> GameState {
+        if self.passed {
+            return GameState::GameOver(GameResult::WinNormal);
+        }
         self.position.game_state()
     }
 
+    /// Points won by the player on roll once the game is over, including the cube.
+    pub fn points(&self) -> Option<f32> {
+        match self.game_state() {
+            GameState::GameOver(result) => Some(self.cube.points(&result)),
+            GameState::Ongoing => None,
+        }
+    }
+
     pub fn play_episode_with<R: VariantRules, D: DiceGen, P>(
+        &mut self,
+        dice_gen: &mut D,
+        max_plies: usize,
+        pick_index: P,
+    ) -> Result<usize, GameError>
+    where
+        P: FnMut(VariantPosition, &Dice, &[VariantPosition]) -> usize,
+    {
+        self.play_cubeful_episode_with::<R, D, P, _>(dice_gen, max_plies, pick_index, |_, _| None)
+    }
+
+    /// Like [Self::play_episode_with], but before each roll after the opening `cube_action` is
+    /// asked for the cube decisions of both players.
+    ///
+    /// It gets the legal actions and returns the chosen one, `None` means no double or no
+    /// raccoon. A double has to be answered. The result is in [Self::points].
+    pub fn play_cubeful_episode_with<R: VariantRules, D: DiceGen, P, C>(
         &mut self,
         dice_gen: &mut D,
         max_plies: usize,
         mut pick_index: P,
+        mut cube_action: C,
     ) -> Result<usize, GameError>
     where
         P: FnMut(VariantPosition, &Dice, &[VariantPosition]) -> usize,
+        C: FnMut(&Game, &[CubeAction]) -> Option<CubeAction>,
     {
         for ply in 0..max_plies {
             let dice = if ply == 0 {
                 dice_gen.roll_mixed()
             } else {
+                loop {
+                    let actions = self.legal_cube_actions();
+                    if actions.is_empty() {
+                        break;
+                    }
+                    match cube_action(self, &actions) {
+                        Some(action) => self.apply_cube_action(action)?,
+                        None if self.cube.is_settled() => break,
+                        None => return Err(GameError::CubeActionPending),
+                    }
+                    if self.passed {
+                        return Ok(ply);
+                    }
+                }
                 dice_gen.roll()
             };
 
@@ -108,6 +211,7 @@ impl Game {
             }
 
             self.position = legal[index];
+            self.cube.roll();
             if let GameState::GameOver(_) = self.position.game_state() {
                 return Ok(ply + 1);
             }
@@ -127,13 +231,34 @@ impl Game {
     {
         self.play_episode_with::<ClassicRules, D, P>(dice_gen, max_plies, pick_index)
     }
+
+    pub fn play_cubeful_episode<D: DiceGen, P, C>(
+        &mut self,
+        dice_gen: &mut D,
+        max_plies: usize,
+        pick_index: P,
+        cube_action: C,
+    ) -> Result<usize, GameError>
+    where
+        P: FnMut(VariantPosition, &Dice, &[VariantPosition]) -> usize,
+        C: FnMut(&Game, &[CubeAction]) -> Option<CubeAction>,
+    {
+        self.play_cubeful_episode_with::<ClassicRules, D, P, C>(
+            dice_gen,
+            max_plies,
+            pick_index,
+            cube_action,
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{Game, GameError};
+    use crate::cube::{CubeAction, CubeState};
     use crate::dice::Dice;
     use crate::dice_gen::DiceGenMock;
+    use crate::position::{GameResult, GameState};
     use crate::rules::ClassicRules;
     use crate::variants::Variant;
 
@@ -160,4 +285,56 @@ mod tests {
 
         assert_eq!(err, GameError::PolicyIndexOutOfBounds);
     }
+
+    #[test]
+    fn passed_double_wins_cube_value() {
+        let mut game = Game::with_cube(
+            Variant::Backgammon,
+            CubeState::new().with_value(2, Some(true)),
+        );
+        game.apply_cube_action(CubeAction::Double).unwrap();
+        let err = game
+            .apply_nth_legal_position(&Dice::new(6, 1), 0)
+            .unwrap_err();
+        assert_eq!(err, GameError::CubeActionPending);
+        game.apply_cube_action(CubeAction::Pass).unwrap();
+        assert_eq!(
+            game.game_state(),
+            GameState::GameOver(GameResult::WinNormal)
+        );
+        assert_eq!(game.points(), Some(2.0));
+        assert!(game.legal_cube_actions().is_empty());
+        assert_eq!(
+            game.apply_nth_legal_position(&Dice::new(6, 1), 0),
+            Err(GameError::GameOver)
+        );
+    }
+
+    #[test]
+    fn cubeful_episode_doubles_and_takes() {
+        let mut game = Game::new(Variant::Backgammon);
+        let mut dice = DiceGenMock::new(&[Dice::new(6, 1), Dice::new(5, 3)]);
+
+        let plies = game
+            .play_cubeful_episode(
+                &mut dice,
+                2,
+                |_pos, _dice, _legal| 0,
+                |_game, actions| {
+                    if actions.contains(&CubeAction::Take) {
+                        Some(CubeAction::Take)
+                    } else {
+                        Some(CubeAction::Double)
+                    }
+                },
+            )
+            .unwrap();
+
+        assert_eq!(plies, 2);
+        dice.assert_all_dice_were_used();
+        assert_eq!(game.cube().value(), 2);
+        // The second player doubled and the first player took.
+        assert_eq!(game.cube().owner(), Some(true));
+        assert_eq!(game.points(), None);
+    }
 }
@@ -1,15 +1,31 @@
 pub mod bearoff;
 pub mod codecs;
+pub mod cube;
+pub mod dataset;
 pub mod dice;
 pub mod dice_gen;
+pub mod duel;
+pub mod engine_registry;
 pub mod engine_spec;
+pub mod equity;
+pub mod evaluator;
+pub mod features;
 pub mod game;
+pub mod match_play;
+pub mod met;
+pub mod mlp;
 pub mod position;
+pub mod rollout;
 pub mod rules;
+pub mod search;
+pub mod solver;
+pub mod tournament;
+pub mod train;
 pub mod ubgi;
 pub mod utils;
 pub mod variants;
 
+pub use bearoff::{BearoffError, OneSidedBearoff, TwoSidedBearoff};
 pub use codecs::fibs::FibsError;
 pub use codecs::gnuid::GnuidError;
 pub use codecs::move_text::{
@@ -18,13 +34,30 @@ pub use codecs::move_text::{
     normalize as normalize_move_text, parse_move_steps, MoveStep, MoveTextError, MoveTextResult,
 };
 pub use codecs::xgid::{Xgid, XgidBoard, XgidDice, XgidError};
+pub use cube::{CubeAction, CubeState};
+pub use dataset::{DatasetError, DatasetFormat, DatasetReader, DatasetWriter, Record};
 pub use dice::Dice;
-pub use engine_spec::{format_engine_spec, parse_engine_spec, EngineSpec, EngineSpecError};
+pub use duel::{DuelError, DuelOptions, DuelPlayer, DuelReport, DuelStats};
+pub use engine_registry::{EngineEntry, EngineRegistry, RegistryError};
+pub use engine_spec::{
+    expand_engine_spec, format_engine_spec, parse_engine_spec, EngineSpec, EngineSpecError,
+};
+pub use equity::{CubeDecision, CubeEquities, CubeOwnership, MatchContext, Probabilities};
+pub use evaluator::{Evaluator, Policy, Pubeval, RaceHeuristic, RandomPlayer};
+pub use features::Encoding;
 pub use game::{Game, GameError};
+pub use match_play::{GameOutcome, Match, MatchError};
+pub use met::{MatchEquityTable, MetError};
+pub use mlp::{Activation, Mlp, MlpError};
 pub use position::{GameResult, GameState, Position, State, O_BAR, X_BAR};
+pub use rollout::{Rollout, RolloutOptions, RolloutResult};
 pub use rules::{
     legal_positions, legal_positions_with, ClassicRules, NoHitRules, PositionRules, VariantRules,
 };
+pub use search::{Candidate, MoveFilter, Search, SearchOptions};
+pub use solver::{CubefulValues, SolveOptions, SolvedTable, SolverError};
+pub use tournament::{TournamentError, TournamentFormat, TournamentOptions, TournamentResults};
+pub use train::{TdTrainer, TrainError, TrainOptions};
 pub use variants::*;
 
 // pub use backgammon::Backgammon;
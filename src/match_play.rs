@@ -0,0 +1,382 @@
+use crate::codecs::xgid::{Xgid, XgidError};
+use crate::cube::{CubeAction, CubeState};
+use crate::dice::Dice;
+use crate::dice_gen::DiceGen;
+use crate::game::{Game, GameError};
+use crate::rules::VariantRules;
+use crate::variants::{Variant, VariantPosition};
+
+/// Bit in [Xgid::rules] which marks the Crawford game of a match.
+pub const XGID_CRAWFORD: u8 = 1;
+/// Bit in [Xgid::rules] of money games for the Jacoby rule.
+pub const XGID_JACOBY: u8 = 1;
+/// Bit in [Xgid::rules] of money games which allows beavers.
+pub const XGID_BEAVER: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MatchError {
+    #[error("match length must be at least 1")]
+    InvalidLength,
+    #[error("scores must be below the match length")]
+    InvalidScore,
+    #[error("current game is not over")]
+    GameNotOver,
+    #[error("match is over")]
+    MatchOver,
+    #[error("invalid cube value")]
+    InvalidCube,
+    #[error(transparent)]
+    Xgid(#[from] XgidError),
+    #[error(transparent)]
+    Game(#[from] GameError),
+}
+
+/// Winner and points of a finished game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GameOutcome {
+    /// Turn of the winner, see [crate::State::turn].
+    pub winner: bool,
+    pub points: u32,
+}
+
+/// A match to a number of points, made of consecutive games.
+///
+/// Players are identified by their turn: `x` is the player with turn `true`, who is on roll in
+/// the starting position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    variant: Variant,
+    length: u32,
+    score_x: u32,
+    score_o: u32,
+    crawford_rule: bool,
+    crawford: bool,
+    post_crawford: bool,
+    game: Game,
+    games: Vec<GameOutcome>,
+}
+
+impl Match {
+    pub fn new(variant: Variant, length: u32) -> Result<Self, MatchError> {
+        Self::with_score(variant, length, 0, 0, false)
+    }
+
+    /// A match at the given score. `crawford` marks the current game as the Crawford game.
+    ///
+    /// If one player is 1-away and `crawford` is not set, the match is post-Crawford.
+    pub fn with_score(
+        variant: Variant,
+        length: u32,
+        score_x: u32,
+        score_o: u32,
+        crawford: bool,
+    ) -> Result<Self, MatchError> {
+        if length == 0 {
+            return Err(MatchError::InvalidLength);
+        }
+        if score_x >= length || score_o >= length {
+            return Err(MatchError::InvalidScore);
+        }
+        let one_away = score_x + 1 == length || score_o + 1 == length;
+        if crawford && !one_away {
+            return Err(MatchError::InvalidScore);
+        }
+        let mut this = Self {
+            variant,
+            length,
+            score_x,
+            score_o,
+            crawford_rule: true,
+            crawford,
+            post_crawford: one_away && !crawford,
+            game: Game::new(variant),
+            games: Vec::new(),
+        };
+        this.game = Game::with_cube(variant, this.new_cube());
+        Ok(this)
+    }
+
+    /// Match at the score of an XGID, with the cube and position of the XGID as current game.
+    ///
+    /// `x` of the XGID gets turn `true`, the side to move of [Xgid::move_flag] is on roll.
+    pub fn from_xgid(variant: Variant, xgid: &Xgid) -> Result<Self, MatchError> {
+        let mut this = Self::with_score(
+            variant,
+            xgid.match_length as u32,
+            xgid.score_x as u32,
+            xgid.score_o as u32,
+            xgid.rules & XGID_CRAWFORD != 0,
+        )?;
+        if xgid.cube_power > 30 || xgid.max_cube > 30 {
+            return Err(MatchError::InvalidCube);
+        }
+        if !this.crawford {
+            let owner = match xgid.cube_owner {
+                1 => Some(true),
+                -1 => Some(false),
+                _ => None,
+            };
+            let max_value = (xgid.max_cube > 0).then(|| 1u32 << xgid.max_cube);
+            let cube = CubeState::new()
+                .with_value(1 << xgid.cube_power, owner)
+                .with_max_value(max_value);
+            this.game.set_cube(cube);
+        }
+        this.game.set_position(xgid.position(variant)?)?;
+        Ok(this)
+    }
+
+    /// Without the Crawford rule the cube is also available when a player is 1-away.
+    pub fn without_crawford_rule(mut self) -> Self {
+        self.crawford_rule = false;
+        if self.crawford {
+            self.crawford = false;
+            self.post_crawford = true;
+            self.game.set_cube(self.new_cube());
+        }
+        self
+    }
+
+    fn new_cube(&self) -> CubeState {
+        if self.crawford {
+            CubeState::unavailable()
+        } else {
+            CubeState::new()
+        }
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Score of the player with turn `player`.
+    pub fn score(&self, player: bool) -> u32 {
+        if player {
+            self.score_x
+        } else {
+            self.score_o
+        }
+    }
+
+    /// Points the player with turn `player` still needs to win the match.
+    pub fn away(&self, player: bool) -> u32 {
+        self.length.saturating_sub(self.score(player))
+    }
+
+    /// The current game is the Crawford game, the cube can't be used.
+    pub fn is_crawford(&self) -> bool {
+        self.crawford
+    }
+
+    /// The Crawford game has been played, or a player is 1-away without the Crawford rule.
+    pub fn is_post_crawford(&self) -> bool {
+        self.post_crawford
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn game_mut(&mut self) -> &mut Game {
+        &mut self.game
+    }
+
+    /// Outcomes of all finished games.
+    pub fn games(&self) -> &[GameOutcome] {
+        &self.games
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.winner().is_some()
+    }
+
+    pub fn winner(&self) -> Option<bool> {
+        if self.score_x >= self.length {
+            Some(true)
+        } else if self.score_o >= self.length {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Adds the points of the finished current game and starts the next one, unless the match
+    /// is over.
+    pub fn finish_game(&mut self) -> Result<GameOutcome, MatchError> {
+        if self.is_over() {
+            return Err(MatchError::MatchOver);
+        }
+        let points = self.game.points().ok_or(MatchError::GameNotOver)?;
+        let on_roll = self.game.position().turn();
+        let outcome = GameOutcome {
+            winner: if points > 0.0 { on_roll } else { !on_roll },
+            points: points.abs() as u32,
+        };
+        if outcome.winner {
+            self.score_x += outcome.points;
+        } else {
+            self.score_o += outcome.points;
+        }
+        self.games.push(outcome);
+
+        if self.crawford {
+            self.crawford = false;
+            self.post_crawford = true;
+        } else if !self.post_crawford && self.score(outcome.winner) + 1 == self.length {
+            if self.crawford_rule {
+                self.crawford = true;
+            } else {
+                self.post_crawford = true;
+            }
+        }
+        if !self.is_over() {
+            self.game = Game::with_cube(self.variant, self.new_cube());
+        }
+        Ok(outcome)
+    }
+
+    /// Plays the current game with [Game::play_cubeful_episode_with] and finishes it.
+    ///
+    /// Returns `None` if the game is not over after `max_plies`.
+    pub fn play_game_with<R: VariantRules, D: DiceGen, P, C>(
+        &mut self,
+        dice_gen: &mut D,
+        max_plies: usize,
+        pick_index: P,
+        cube_action: C,
+    ) -> Result<Option<GameOutcome>, MatchError>
+    where
+        P: FnMut(VariantPosition, &Dice, &[VariantPosition]) -> usize,
+        C: FnMut(&Game, &[CubeAction]) -> Option<CubeAction>,
+    {
+        if self.is_over() {
+            return Err(MatchError::MatchOver);
+        }
+        self.game.play_cubeful_episode_with::<R, D, P, C>(
+            dice_gen,
+            max_plies,
+            pick_index,
+            cube_action,
+        )?;
+        if self.game.points().is_none() {
+            return Ok(None);
+        }
+        self.finish_game().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameOutcome, Match, MatchError};
+    use crate::codecs::xgid::Xgid;
+    use crate::cube::CubeAction;
+    use crate::position::Position;
+    use crate::variants::{Variant, VariantPosition};
+
+    fn win_by_pass(m: &mut Match) -> GameOutcome {
+        m.game_mut().apply_cube_action(CubeAction::Double).unwrap();
+        m.game_mut().apply_cube_action(CubeAction::Pass).unwrap();
+        m.finish_game().unwrap()
+    }
+
+    #[test]
+    fn crawford_and_post_crawford() {
+        let mut m = Match::new(Variant::Hypergammon, 3).unwrap();
+        assert_eq!(m.finish_game(), Err(MatchError::GameNotOver));
+        win_by_pass(&mut m);
+        let outcome = win_by_pass(&mut m);
+        assert_eq!(
+            outcome,
+            GameOutcome {
+                winner: true,
+                points: 1
+            }
+        );
+        assert_eq!(m.away(true), 1);
+        assert!(m.is_crawford());
+        assert!(m.game().legal_cube_actions().is_empty());
+
+        // The player on roll has lost a gammon in the Crawford game.
+        let mut pips = [0i8; 26];
+        pips[6] = 3;
+        let lost = Position::<3>::try_from(pips).unwrap();
+        m.game_mut()
+            .set_position(VariantPosition::Hypergammon(lost))
+            .unwrap();
+        let outcome = m.finish_game().unwrap();
+        assert_eq!(
+            outcome,
+            GameOutcome {
+                winner: false,
+                points: 2
+            }
+        );
+        assert!(!m.is_crawford());
+        assert!(m.is_post_crawford());
+        assert_eq!(m.game().legal_cube_actions(), vec![CubeAction::Double]);
+
+        win_by_pass(&mut m);
+        assert!(m.is_over());
+        assert_eq!(m.winner(), Some(true));
+        assert_eq!((m.score(true), m.score(false)), (3, 2));
+        assert_eq!(m.games().len(), 4);
+        assert_eq!(m.finish_game(), Err(MatchError::MatchOver));
+    }
+
+    #[test]
+    fn no_crawford_game_without_rule() {
+        let mut m = Match::new(Variant::Hypergammon, 2)
+            .unwrap()
+            .without_crawford_rule();
+        win_by_pass(&mut m);
+        assert!(!m.is_crawford());
+        assert!(m.is_post_crawford());
+        assert_eq!(m.game().legal_cube_actions(), vec![CubeAction::Double]);
+    }
+
+    #[test]
+    fn rejects_invalid_scores() {
+        assert_eq!(
+            Match::new(Variant::Backgammon, 0),
+            Err(MatchError::InvalidLength)
+        );
+        assert_eq!(
+            Match::with_score(Variant::Backgammon, 5, 5, 0, false),
+            Err(MatchError::InvalidScore)
+        );
+        assert_eq!(
+            Match::with_score(Variant::Backgammon, 5, 2, 0, true),
+            Err(MatchError::InvalidScore)
+        );
+    }
+
+    #[test]
+    fn from_xgid_reads_score_and_cube() {
+        let xgid = Xgid::parse("XGID=-b----E-C---eE---c-e----B-:3:7:0:4:2:52:1:-1:1").unwrap();
+        let m = Match::from_xgid(Variant::Backgammon, &xgid).unwrap();
+        assert_eq!(m.length(), 7);
+        assert_eq!((m.score(true), m.score(false)), (4, 2));
+        assert!(!m.is_crawford() && !m.is_post_crawford());
+        assert_eq!(m.game().cube().value(), 2);
+        assert_eq!(m.game().cube().owner(), Some(false));
+        assert_eq!(m.game().cube().max_value(), Some(8));
+        assert!(m.game().legal_cube_actions().is_empty());
+
+        let xgid = Xgid::parse("XGID=-b----E-C---eE---c-e----B-:0:7:1:6:2:52:1:0:0").unwrap();
+        let m = Match::from_xgid(Variant::Backgammon, &xgid).unwrap();
+        assert!(m.is_crawford());
+        assert!(!m.game().cube().is_available());
+
+        // `o` to move: scores and cube stay with their players.
+        let xgid = Xgid::parse("XGID=-b----E-C---eE---c-e----B-:3:7:0:4:2:52:0:-1:1").unwrap();
+        let m = Match::from_xgid(Variant::Backgammon, &xgid).unwrap();
+        assert!(!m.game().position().turn());
+        assert_eq!((m.score(true), m.score(false)), (4, 2));
+        assert_eq!(m.game().cube().owner(), Some(false));
+        assert_eq!(m.game().legal_cube_actions(), vec![CubeAction::Double]);
+    }
+}
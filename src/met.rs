@@ -0,0 +1,515 @@
+use crate::equity::Probabilities;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Length of the built-in table.
+pub const MAX_SCORE: usize = 25;
+
+/// Share of wins which are gammons, used for the built-in table.
+pub const BUILTIN_GAMMON_RATE: f64 = 0.26;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetError {
+    #[error("match equity table i/o failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid match equity table: {0}")]
+    Invalid(String),
+    #[error("unsupported match equity table: {0}")]
+    Unsupported(String),
+}
+
+/// Match winning chances for all scores up to a match length.
+///
+/// Scores are given as points away. The pre-Crawford table also holds the Crawford game, when
+/// one player is 1-away. The post-Crawford entries are the chances of the trailer against a
+/// leader who is 1-away.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchEquityTable {
+    name: String,
+    /// `pre_crawford[i][j]`: player is `i + 1` away, opponent `j + 1` away.
+    pre_crawford: Vec<Vec<f32>>,
+    /// `post_crawford[player][i]`: `player` (0 or 1) trails `i + 1` away post-Crawford.
+    post_crawford: [Vec<f32>; 2],
+}
+
+impl MatchEquityTable {
+    /// Built-in table to 25 points.
+    ///
+    /// It is generated with [Self::generate] from a gammon rate of 26%, it is not one of the
+    /// published tables. Those, like Rockwell-Kazaross or Kazaross-XG2, can be loaded with
+    /// [Self::load].
+    pub fn builtin() -> &'static Self {
+        static TABLE: OnceLock<MatchEquityTable> = OnceLock::new();
+        TABLE.get_or_init(|| Self::generate(MAX_SCORE, BUILTIN_GAMMON_RATE))
+    }
+
+    /// Generates a table from a model of the cube.
+    ///
+    /// Winning chances move continuously during a game, so doubles are given exactly at the
+    /// opponent's take point, as in the Keeler-Spencer model. Both players start each game with
+    /// equal chances and a fixed share `gammon_rate` of all wins are gammons. In the post-Crawford
+    /// games the trailer doubles at the first turn and the leader passes when that is better,
+    /// see [FIRST_DOUBLE_SPREAD].
+    pub fn generate(length: usize, gammon_rate: f64) -> Self {
+        let model = Model::new(length, gammon_rate);
+        let pre_crawford = (1..=length)
+            .map(|a| (1..=length).map(|b| model.pre[a][b] as f32).collect())
+            .collect();
+        let post: Vec<f32> = (1..=length).map(|n| model.post[n] as f32).collect();
+        Self {
+            name: format!("bkgm model, gammon rate {gammon_rate}"),
+            pre_crawford,
+            post_crawford: [post.clone(), post],
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Longest match covered by the table.
+    pub fn length(&self) -> usize {
+        self.pre_crawford.len()
+    }
+
+    /// Loads a table in the XML format of GNU Backgammon. Only explicit tables are supported.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MetError> {
+        Self::from_gnubg_xml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_gnubg_xml(xml: &str) -> Result<Self, MetError> {
+        let xml = strip_comments(xml);
+        let name = element(&xml, "name")
+            .map(|(_, body)| body.trim().to_string())
+            .unwrap_or_default();
+
+        let (attributes, body) = element(&xml, "pre-crawford-table")
+            .ok_or_else(|| MetError::Invalid("missing pre-crawford-table".to_string()))?;
+        check_explicit(attributes)?;
+        let pre_crawford = rows(body)?;
+        let length = pre_crawford.len();
+        if length == 0 || pre_crawford.iter().any(|row| row.len() < length) {
+            return Err(MetError::Invalid(
+                "pre-crawford-table must be square".to_string(),
+            ));
+        }
+        let pre_crawford: Vec<Vec<f32>> = pre_crawford
+            .into_iter()
+            .map(|row| row[..length].to_vec())
+            .collect();
+
+        let mut post_crawford: [Option<Vec<f32>>; 2] = [None, None];
+        let mut rest = xml.as_str();
+        while let Some((attributes, body, after)) = next_element(rest, "post-crawford-table") {
+            check_explicit(attributes)?;
+            let row = rows(body)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| MetError::Invalid("empty post-crawford-table".to_string()))?;
+            if row.len() < length {
+                return Err(MetError::Invalid(
+                    "post-crawford-table too short".to_string(),
+                ));
+            }
+            let row = row[..length].to_vec();
+            match attribute(attributes, "player").as_deref() {
+                Some("0") => post_crawford[0] = Some(row),
+                Some("1") => post_crawford[1] = Some(row),
+                _ => post_crawford = [Some(row.clone()), Some(row)],
+            }
+            rest = after;
+        }
+        let [Some(post0), Some(post1)] = post_crawford else {
+            return Err(MetError::Invalid("missing post-crawford-table".to_string()));
+        };
+        Ok(Self {
+            name,
+            pre_crawford,
+            post_crawford: [post0, post1],
+        })
+    }
+
+    /// Match winning chance of a player `away` points away against an opponent `opponent_away`
+    /// away, before the game starts. `None` if a score is beyond the table.
+    ///
+    /// `crawford_used` means the Crawford game is being played or has been played. At 1-away
+    /// it tells the Crawford game from a post-Crawford game.
+    pub fn mwc(&self, away: i32, opponent_away: i32, crawford_used: bool) -> Option<f32> {
+        if away <= 0 {
+            return Some(1.0);
+        }
+        if opponent_away <= 0 {
+            return Some(0.0);
+        }
+        let (a, b) = (away as usize, opponent_away as usize);
+        if a > self.length() || b > self.length() {
+            return None;
+        }
+        Some(if crawford_used && a == 1 && b > 1 {
+            1.0 - self.post_crawford[1][b - 1]
+        } else if crawford_used && b == 1 && a > 1 {
+            self.post_crawford[0][a - 1]
+        } else {
+            self.pre_crawford[a - 1][b - 1]
+        })
+    }
+
+    /// Match winning chances after the current game, from cubeless probabilities of the player.
+    ///
+    /// `crawford_used` is set in the Crawford game and after it, so the next game is
+    /// post-Crawford.
+    pub fn cubeless_mwc(
+        &self,
+        probabilities: &Probabilities,
+        away: i32,
+        opponent_away: i32,
+        cube: u32,
+        crawford_used: bool,
+    ) -> Option<f32> {
+        let p = probabilities;
+        let c = cube as i32;
+        self.mwc(away, opponent_away, crawford_used)?;
+        // Every result lowers a score, so these are all in the table.
+        let after = |a: i32, b: i32| self.mwc(a, b, crawford_used).unwrap_or_default();
+        let win_single = p.win - p.win_gammon;
+        let win_gammon = p.win_gammon - p.win_backgammon;
+        let lose_single = p.lose() - p.lose_gammon;
+        let lose_gammon = p.lose_gammon - p.lose_backgammon;
+        Some(
+            win_single * after(away - c, opponent_away)
+                + win_gammon * after(away - 2 * c, opponent_away)
+                + p.win_backgammon * after(away - 3 * c, opponent_away)
+                + lose_single * after(away, opponent_away - c)
+                + lose_gammon * after(away, opponent_away - 2 * c)
+                + p.lose_backgammon * after(away, opponent_away - 3 * c),
+        )
+    }
+
+    /// Converts an equity normalized to the cube, where -1 is losing and 1 is winning the
+    /// cube value, to match winning chances.
+    pub fn equity_to_mwc(
+        &self,
+        equity: f32,
+        away: i32,
+        opponent_away: i32,
+        cube: u32,
+        crawford_used: bool,
+    ) -> Option<f32> {
+        let c = cube as i32;
+        self.mwc(away, opponent_away, crawford_used)?;
+        let win = self.mwc(away - c, opponent_away, crawford_used)?;
+        let lose = self.mwc(away, opponent_away - c, crawford_used)?;
+        Some(lose + (win - lose) * (equity + 1.0) / 2.0)
+    }
+
+    /// Inverse of [Self::equity_to_mwc].
+    pub fn mwc_to_equity(
+        &self,
+        mwc: f32,
+        away: i32,
+        opponent_away: i32,
+        cube: u32,
+        crawford_used: bool,
+    ) -> Option<f32> {
+        let c = cube as i32;
+        self.mwc(away, opponent_away, crawford_used)?;
+        let win = self.mwc(away - c, opponent_away, crawford_used)?;
+        let lose = self.mwc(away, opponent_away - c, crawford_used)?;
+        Some(2.0 * (mwc - lose) / (win - lose) - 1.0)
+    }
+}
+
+/// How far from 50% the trailer's winning chances are on average at the first double of a
+/// post-Crawford game.
+///
+/// The leader passes doubles that come too late, the free drop at even scores. With this spread
+/// it is worth 1.2% at 2-away, like in the published tables.
+const FIRST_DOUBLE_SPREAD: f64 = 0.024;
+
+/// Match winning chances of the cube model behind [MatchEquityTable::generate].
+struct Model {
+    gammon_rate: f64,
+    /// `pre[a][b]` for `a, b >= 1`, including the Crawford game at 1-away.
+    pre: Vec<Vec<f64>>,
+    /// `post[n]`: trailer `n` away against a leader 1-away, post-Crawford.
+    post: Vec<f64>,
+}
+
+impl Model {
+    fn new(length: usize, gammon_rate: f64) -> Self {
+        let mut model = Self {
+            gammon_rate,
+            pre: vec![vec![0.0; length + 1]; length + 1],
+            post: vec![0.0; length + 1],
+        };
+        let g = gammon_rate;
+        for n in 1..=length {
+            model.post[n] = if n == 1 {
+                0.5
+            } else {
+                let post = |k: i64| if k <= 0 { 1.0 } else { model.post[k as usize] };
+                let won = (1.0 - g) * post(n as i64 - 2) + g * post(n as i64 - 4);
+                let pass = post(n as i64 - 1);
+                let d = FIRST_DOUBLE_SPREAD;
+                0.5 * (((0.5 + d) * won).min(pass) + ((0.5 - d) * won).min(pass))
+            };
+        }
+        // Scores are filled by ascending sum, every game moves to a smaller sum.
+        for sum in 2..=2 * length {
+            for a in 1..=length {
+                if sum <= a || sum - a > length {
+                    continue;
+                }
+                let b = sum - a;
+                model.pre[a][b] = model.game(a as i64, b as i64);
+            }
+        }
+        model
+    }
+
+    /// Chances after a game of the pre-Crawford table: 1-away means the Crawford game.
+    fn after(&self, a: i64, b: i64, crawford_used: bool) -> f64 {
+        if a <= 0 {
+            1.0
+        } else if b <= 0 {
+            0.0
+        } else if crawford_used && a == 1 && b > 1 {
+            1.0 - self.post[b as usize]
+        } else if crawford_used && b == 1 && a > 1 {
+            self.post[a as usize]
+        } else {
+            self.pre[a as usize][b as usize]
+        }
+    }
+
+    fn win(&self, a: i64, b: i64, c: i64) -> f64 {
+        let g = self.gammon_rate;
+        (1.0 - g) * self.after(a - c, b, false) + g * self.after(a - 2 * c, b, false)
+    }
+
+    fn lose(&self, a: i64, b: i64, c: i64) -> f64 {
+        let g = self.gammon_rate;
+        (1.0 - g) * self.after(a, b - c, false) + g * self.after(a, b - 2 * c, false)
+    }
+
+    /// Equity line of a player who owns a cube of value `c`: it goes from losing at winning
+    /// chance 0 to the value at the returned cash point.
+    fn owned(&self, a: i64, b: i64, c: i64) -> (f64, f64) {
+        let win = self.win(a, b, c);
+        if a - c <= 0 {
+            // Redoubling can't win more.
+            return (1.0, win);
+        }
+        let cash = self.after(a - c, b, false);
+        let take = self.take_point(b, a, 2 * c, 1.0 - cash);
+        if take <= 0.0 {
+            return (1.0, win);
+        }
+        (1.0 - take, cash)
+    }
+
+    /// Lowest winning chance at which a player `a` away takes a cube of value `c`, when passing
+    /// is worth `pass`.
+    fn take_point(&self, a: i64, b: i64, c: i64, pass: f64) -> f64 {
+        let (cash_point, cash) = self.owned(a, b, c);
+        let lose = self.lose(a, b, c);
+        if cash <= lose {
+            return 0.0;
+        }
+        (cash_point * (pass - lose) / (cash - lose)).clamp(0.0, cash_point)
+    }
+
+    fn game(&self, a: i64, b: i64) -> f64 {
+        let g = self.gammon_rate;
+        if a == 1 && b == 1 {
+            return 0.5;
+        }
+        if a == 1 {
+            // Crawford game, no cube.
+            return 0.5
+                + 0.5 * ((1.0 - g) * self.after(1, b - 1, true) + g * self.after(1, b - 2, true));
+        }
+        if b == 1 {
+            return 1.0 - self.game(b, a);
+        }
+        let cash = self.after(a - 1, b, false);
+        let dropped = self.after(a, b - 1, false);
+        let high = 1.0 - self.take_point(b, a, 2, 1.0 - cash);
+        let low = self.take_point(a, b, 2, dropped);
+        if high <= low {
+            return 0.5 * (cash + dropped);
+        }
+        let p = 0.5f64.clamp(low, high);
+        dropped + (cash - dropped) * (p - low) / (high - low)
+    }
+}
+
+fn strip_comments(xml: &str) -> String {
+    let mut out = String::with_capacity(xml.len());
+    let mut rest = xml;
+    while let Some(start) = rest.find("<!--") {
+        out.push_str(&rest[..start]);
+        match rest[start..].find("-->") {
+            Some(end) => rest = &rest[start + end + 3..],
+            None => return out,
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Attributes, body and rest after the first element `name`.
+fn next_element<'a>(xml: &'a str, name: &str) -> Option<(&'a str, &'a str, &'a str)> {
+    let open = format!("<{name}");
+    let close = format!("</{name}>");
+    let mut search = 0;
+    loop {
+        let start = search + xml[search..].find(&open)?;
+        let after_name = start + open.len();
+        // Don't match elements which only start with `name`.
+        if xml[after_name..].starts_with(|c: char| c.is_whitespace() || c == '>') {
+            let tag_end = after_name + xml[after_name..].find('>')?;
+            let attributes = &xml[after_name..tag_end];
+            let body_start = tag_end + 1;
+            let body_end = body_start + xml[body_start..].find(&close)?;
+            return Some((
+                attributes,
+                &xml[body_start..body_end],
+                &xml[body_end + close.len()..],
+            ));
+        }
+        search = after_name;
+    }
+}
+
+fn element<'a>(xml: &'a str, name: &str) -> Option<(&'a str, &'a str)> {
+    next_element(xml, name).map(|(attributes, body, _)| (attributes, body))
+}
+
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let key = format!("{name}=");
+    let start = attributes.find(&key)? + key.len();
+    let rest = &attributes[start..];
+    let quote = rest.chars().next()?;
+    let rest = &rest[1..];
+    Some(rest[..rest.find(quote)?].to_string())
+}
+
+fn check_explicit(attributes: &str) -> Result<(), MetError> {
+    match attribute(attributes, "type").as_deref() {
+        None | Some("explicit") => Ok(()),
+        Some(other) => Err(MetError::Unsupported(format!("table type '{other}'"))),
+    }
+}
+
+fn rows(body: &str) -> Result<Vec<Vec<f32>>, MetError> {
+    let mut rows = Vec::new();
+    let mut rest = body;
+    while let Some((_, row, after)) = next_element(rest, "row") {
+        let mut values = Vec::new();
+        let mut cells = row;
+        while let Some((_, value, after)) = next_element(cells, "me") {
+            let value = value
+                .trim()
+                .parse::<f32>()
+                .map_err(|_| MetError::Invalid(format!("bad value '{}'", value.trim())))?;
+            values.push(value);
+            cells = after;
+        }
+        rows.push(values);
+        rest = after;
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MatchEquityTable, MetError, MAX_SCORE};
+    use crate::equity::Probabilities;
+
+    #[test]
+    fn builtin_table_is_consistent() {
+        let met = MatchEquityTable::builtin();
+        assert_eq!(met.length(), MAX_SCORE);
+        let mwc = |a, b, crawford_used| met.mwc(a, b, crawford_used).unwrap();
+        for a in 1..=MAX_SCORE as i32 {
+            for b in 1..=MAX_SCORE as i32 {
+                assert!((mwc(a, b, false) + mwc(b, a, false) - 1.0).abs() < 1e-5);
+                if b > a {
+                    assert!(mwc(a, b, false) > 0.5, "{a}-away vs {b}-away");
+                }
+                if a > 1 {
+                    assert!(mwc(a, b, false) < mwc(a - 1, b, false));
+                }
+            }
+        }
+        assert_eq!(mwc(5, 5, false), 0.5);
+        // The leader at 1-away is a big favorite in the Crawford game.
+        let crawford = mwc(1, 2, false);
+        assert!(crawford > 0.65 && crawford < 0.72);
+        // The free drop costs the trailer at 2-away about 1.2% after the Crawford game.
+        assert!((mwc(2, 1, true) - 0.488).abs() < 1e-3);
+        assert!(mwc(3, 1, true) < mwc(2, 1, true));
+        // Even scores are worth little more than one point less, like in published tables.
+        assert!(mwc(4, 1, true) < mwc(3, 1, true));
+        assert!(mwc(4, 1, true) > 0.9 * mwc(3, 1, true));
+        assert_eq!(met.mwc(26, 1, true), None);
+    }
+
+    #[test]
+    fn converts_probabilities_to_mwc() {
+        let met = MatchEquityTable::builtin();
+        let sure_win = Probabilities {
+            win: 1.0,
+            ..Probabilities::default()
+        };
+        assert_eq!(met.cubeless_mwc(&sure_win, 1, 5, 1, false), Some(1.0));
+        assert_eq!(
+            met.cubeless_mwc(&sure_win, 3, 3, 1, false),
+            met.mwc(2, 3, false)
+        );
+        assert_eq!(met.cubeless_mwc(&sure_win, 30, 3, 8, false), None);
+        let even = Probabilities {
+            win: 0.5,
+            ..Probabilities::default()
+        };
+        let mwc = met.cubeless_mwc(&even, 3, 3, 1, false).unwrap();
+        assert!((mwc - 0.5).abs() < 1e-6);
+
+        let equity = met.mwc_to_equity(0.6, 4, 3, 2, false).unwrap();
+        assert!((met.equity_to_mwc(equity, 4, 3, 2, false).unwrap() - 0.6).abs() < 1e-5);
+    }
+
+    #[test]
+    fn loads_gnubg_xml() {
+        let xml = r#"<?xml version="1.0" encoding="ISO-8859-1"?>
+<!-- a small table -->
+<met>
+  <info>
+    <name>Test</name>
+    <length>2</length>
+  </info>
+  <pre-crawford-table type="explicit">
+    <row> <me>0.5</me> <me>0.7</me> </row>
+    <row> <me>0.3</me> <me>0.5</me> </row>
+  </pre-crawford-table>
+  <post-crawford-table player="both" type="explicit">
+    <row> <me>0.5</me> <me>0.48</me> <me>0.32</me> </row>
+  </post-crawford-table>
+</met>"#;
+        let met = MatchEquityTable::from_gnubg_xml(xml).unwrap();
+        assert_eq!(met.name(), "Test");
+        assert_eq!(met.length(), 2);
+        assert_eq!(met.mwc(1, 2, false), Some(0.7));
+        assert_eq!(met.mwc(2, 1, true), Some(0.48));
+        assert!((met.mwc(1, 2, true).unwrap() - 0.52).abs() < 1e-6);
+        assert_eq!(met.mwc(1, 9, false), None);
+        assert_eq!(met.mwc(0, 9, false), Some(1.0));
+
+        let zadeh = xml.replace("type=\"explicit\"", "type=\"zadeh\"");
+        assert!(matches!(
+            MatchEquityTable::from_gnubg_xml(&zadeh),
+            Err(MetError::Unsupported(_))
+        ));
+    }
+}
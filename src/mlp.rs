@@ -0,0 +1,475 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::equity::Probabilities;
+use crate::evaluator::Evaluator;
+use crate::features::Encoding;
+use crate::variants::VariantPosition;
+
+const MAGIC: [u8; 4] = *b"BKNN";
+const VERSION: u32 = 1;
+
+/// Outputs of a network used as an [Evaluator], in the order of [Probabilities].
+pub const MLP_OUTPUTS: usize = 5;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MlpError {
+    #[error("network i/o failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid network header")]
+    InvalidHeader,
+    #[error("unsupported network file version {0}")]
+    UnsupportedVersion(u32),
+    #[error("layer {layer} expects {expected} inputs, got {found}")]
+    ShapeMismatch {
+        layer: usize,
+        expected: usize,
+        found: usize,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Activation {
+    Linear,
+    Sigmoid,
+    Relu,
+}
+
+impl Activation {
+    pub fn apply(self, x: f32) -> f32 {
+        match self {
+            Activation::Linear => x,
+            Activation::Sigmoid => 1.0 / (1.0 + (-x).exp()),
+            Activation::Relu => x.max(0.0),
+        }
+    }
+
+    /// Derivative at the input whose activation is `y`.
+    pub fn derivative(self, y: f32) -> f32 {
+        match self {
+            Activation::Linear => 1.0,
+            Activation::Sigmoid => y * (1.0 - y),
+            Activation::Relu => (y > 0.0) as u8 as f32,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Activation::Linear => 0,
+            Activation::Sigmoid => 1,
+            Activation::Relu => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, MlpError> {
+        match byte {
+            0 => Ok(Activation::Linear),
+            1 => Ok(Activation::Sigmoid),
+            2 => Ok(Activation::Relu),
+            _ => Err(MlpError::InvalidHeader),
+        }
+    }
+}
+
+fn encoding_to_byte(encoding: Encoding) -> u8 {
+    match encoding {
+        Encoding::TdGammon => 1,
+        Encoding::Gnubg => 2,
+        Encoding::GnubgContact => 3,
+        Encoding::GnubgRace => 4,
+        Encoding::Wildbg => 5,
+    }
+}
+
+fn encoding_from_byte(byte: u8) -> Result<Encoding, MlpError> {
+    match byte {
+        1 => Ok(Encoding::TdGammon),
+        2 => Ok(Encoding::Gnubg),
+        3 => Ok(Encoding::GnubgContact),
+        4 => Ok(Encoding::GnubgRace),
+        5 => Ok(Encoding::Wildbg),
+        _ => Err(MlpError::InvalidHeader),
+    }
+}
+
+/// A dense layer, `weights` holds one row of `inputs` weights per output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layer {
+    pub(crate) inputs: usize,
+    pub(crate) outputs: usize,
+    pub(crate) weights: Vec<f32>,
+    pub(crate) biases: Vec<f32>,
+    pub(crate) activation: Activation,
+}
+
+impl Layer {
+    /// Xavier uniform weights and zero biases.
+    pub fn random(
+        inputs: usize,
+        outputs: usize,
+        activation: Activation,
+        rng: &mut fastrand::Rng,
+    ) -> Self {
+        let limit = (6.0 / (inputs + outputs) as f32).sqrt();
+        Self {
+            inputs,
+            outputs,
+            weights: (0..inputs * outputs)
+                .map(|_| (2.0 * rng.f32() - 1.0) * limit)
+                .collect(),
+            biases: vec![0.0; outputs],
+            activation,
+        }
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.outputs
+    }
+
+    pub fn activation(&self) -> Activation {
+        self.activation
+    }
+
+    pub fn forward(&self, inputs: &[f32], outputs: &mut [f32]) {
+        for ((output, row), bias) in outputs
+            .iter_mut()
+            .zip(self.weights.chunks_exact(self.inputs))
+            .zip(&self.biases)
+        {
+            let sum: f32 = row.iter().zip(inputs).map(|(w, x)| w * x).sum();
+            *output = self.activation.apply(sum + bias);
+        }
+    }
+}
+
+/// A dense feed-forward network reading the inputs of an [Encoding].
+///
+/// Networks are stored as little endian binary files:
+///
+/// | bytes | content          |
+/// |-------|------------------|
+/// | 4     | magic `BKNN`     |
+/// | 4     | format version   |
+/// | 1     | encoding         |
+/// | 1     | number of layers |
+/// | 2     | reserved         |
+/// | 4     | number of inputs |
+///
+/// The encoding is 1 for td-gammon, 2 gnubg, 3 gnubg-contact, 4 gnubg-race and 5 wildbg. Each
+/// layer follows with 4 bytes outputs, 1 byte activation (0 = linear, 1 = sigmoid, 2 = relu),
+/// 3 reserved bytes, then the weights row by row and the biases as `f32`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mlp {
+    encoding: Encoding,
+    pub(crate) layers: Vec<Layer>,
+}
+
+impl Mlp {
+    pub fn new(encoding: Encoding, layers: Vec<Layer>) -> Result<Self, MlpError> {
+        let mut inputs = encoding.inputs();
+        for (i, layer) in layers.iter().enumerate() {
+            if layer.inputs != inputs
+                || layer.weights.len() != layer.inputs * layer.outputs
+                || layer.biases.len() != layer.outputs
+            {
+                return Err(MlpError::ShapeMismatch {
+                    layer: i,
+                    expected: inputs,
+                    found: layer.inputs,
+                });
+            }
+            inputs = layer.outputs;
+        }
+        // The file header stores the number of layers in one byte.
+        if layers.is_empty() || layers.len() > u8::MAX as usize {
+            return Err(MlpError::InvalidHeader);
+        }
+        Ok(Self { encoding, layers })
+    }
+
+    /// Randomly initialized network with `hidden` layers of `activation` and [MLP_OUTPUTS]
+    /// sigmoid outputs.
+    pub fn random(encoding: Encoding, hidden: &[usize], activation: Activation, seed: u64) -> Self {
+        let mut rng = fastrand::Rng::with_seed(seed);
+        let mut layers = Vec::with_capacity(hidden.len() + 1);
+        let mut inputs = encoding.inputs();
+        for &outputs in hidden {
+            layers.push(Layer::random(inputs, outputs, activation, &mut rng));
+            inputs = outputs;
+        }
+        layers.push(Layer::random(
+            inputs,
+            MLP_OUTPUTS,
+            Activation::Sigmoid,
+            &mut rng,
+        ));
+        Self { encoding, layers }
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.layers[0].inputs
+    }
+
+    pub fn outputs(&self) -> usize {
+        self.layers[self.layers.len() - 1].outputs
+    }
+
+    /// Outputs for one row of inputs.
+    pub fn forward(&self, inputs: &[f32], outputs: &mut [f32]) {
+        self.forward_batch(inputs, outputs);
+    }
+
+    /// Outputs for `inputs.len() / self.inputs()` rows of inputs, row by row.
+    pub fn forward_batch(&self, inputs: &[f32], outputs: &mut [f32]) {
+        assert_eq!(inputs.len() % self.inputs(), 0);
+        let rows = inputs.len() / self.inputs();
+        assert_eq!(outputs.len(), rows * self.outputs());
+        let widest = self.layers.iter().map(|l| l.outputs).max().unwrap_or(0);
+        let mut current = vec![0.0; widest];
+        let mut next = vec![0.0; widest];
+        for (row, out) in inputs
+            .chunks_exact(self.inputs())
+            .zip(outputs.chunks_exact_mut(self.outputs()))
+        {
+            let mut width = row.len();
+            for (i, layer) in self.layers.iter().enumerate() {
+                let input = if i == 0 { row } else { &current[..width] };
+                layer.forward(input, &mut next[..layer.outputs]);
+                width = layer.outputs;
+                std::mem::swap(&mut current, &mut next);
+            }
+            out.copy_from_slice(&current[..width]);
+        }
+    }
+
+    /// Evaluations of many positions, encoded together and run as one batch.
+    pub fn evaluate_batch(&self, positions: &[VariantPosition]) -> Vec<Probabilities> {
+        let mut inputs = vec![0.0; positions.len() * self.inputs()];
+        for (position, row) in positions.iter().zip(inputs.chunks_exact_mut(self.inputs())) {
+            self.encoding.encode_variant(*position, row);
+        }
+        let mut outputs = vec![0.0; positions.len() * self.outputs()];
+        self.forward_batch(&inputs, &mut outputs);
+        outputs
+            .chunks_exact(self.outputs())
+            .map(probabilities)
+            .collect()
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), MlpError> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[
+            encoding_to_byte(self.encoding),
+            self.layers.len() as u8,
+            0,
+            0,
+        ])?;
+        writer.write_all(&(self.inputs() as u32).to_le_bytes())?;
+        for layer in &self.layers {
+            writer.write_all(&(layer.outputs as u32).to_le_bytes())?;
+            writer.write_all(&[layer.activation.to_byte(), 0, 0, 0])?;
+            for value in layer.weights.iter().chain(&layer.biases) {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, MlpError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(MlpError::InvalidHeader);
+        }
+        let version = read_u32(reader)?;
+        if version != VERSION {
+            return Err(MlpError::UnsupportedVersion(version));
+        }
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes)?;
+        let encoding = encoding_from_byte(bytes[0])?;
+        let mut inputs = read_u32(reader)? as usize;
+        if inputs != encoding.inputs() {
+            return Err(MlpError::ShapeMismatch {
+                layer: 0,
+                expected: encoding.inputs(),
+                found: inputs,
+            });
+        }
+        let mut layers = Vec::with_capacity(bytes[1] as usize);
+        for _ in 0..bytes[1] {
+            let outputs = read_u32(reader)? as usize;
+            let mut flags = [0u8; 4];
+            reader.read_exact(&mut flags)?;
+            let activation = Activation::from_byte(flags[0])?;
+            let size = inputs
+                .checked_mul(outputs)
+                .and_then(|weights| weights.checked_add(outputs))
+                .and_then(|values| values.checked_mul(4))
+                .ok_or(MlpError::InvalidHeader)?;
+            // Read through `take`, a corrupt size ends the file instead of allocating all of it.
+            let mut values = Vec::new();
+            reader.by_ref().take(size as u64).read_to_end(&mut values)?;
+            if values.len() != size {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            let mut values = values
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+            layers.push(Layer {
+                inputs,
+                outputs,
+                weights: values.by_ref().take(inputs * outputs).collect(),
+                biases: values.collect(),
+                activation,
+            });
+            inputs = outputs;
+        }
+        Self::new(encoding, layers)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MlpError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MlpError> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+/// Reads the network outputs as [Probabilities], missing outputs are zero. Gammons are capped
+/// by the wins and losses and backgammons by the gammons.
+fn probabilities(outputs: &[f32]) -> Probabilities {
+    let output = |i: usize| outputs.get(i).copied().unwrap_or(0.0).clamp(0.0, 1.0);
+    let win = output(0);
+    let win_gammon = output(1).min(win);
+    let lose_gammon = output(3).min(1.0 - win);
+    Probabilities {
+        win,
+        win_gammon,
+        win_backgammon: output(2).min(win_gammon),
+        lose_gammon,
+        lose_backgammon: output(4).min(lose_gammon),
+    }
+}
+
+impl Evaluator for Mlp {
+    fn evaluate(&self, position: VariantPosition) -> Probabilities {
+        let mut inputs = vec![0.0; self.inputs()];
+        self.encoding.encode_variant(position, &mut inputs);
+        let mut outputs = vec![0.0; self.outputs()];
+        self.forward(&inputs, &mut outputs);
+        probabilities(&outputs)
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, MlpError> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Activation, Layer, Mlp, MlpError, MLP_OUTPUTS};
+    use crate::evaluator::Evaluator;
+    use crate::features::{Encoding, TD_GAMMON_INPUTS};
+    use crate::variants::Variant;
+
+    #[test]
+    fn runs_layers_and_batches() {
+        let hidden = Layer {
+            inputs: TD_GAMMON_INPUTS,
+            outputs: 2,
+            weights: [vec![1.0; TD_GAMMON_INPUTS], vec![-1.0; TD_GAMMON_INPUTS]].concat(),
+            biases: vec![0.0, 0.5],
+            activation: Activation::Relu,
+        };
+        let output = Layer {
+            inputs: 2,
+            outputs: 1,
+            weights: vec![1.0, 2.0],
+            biases: vec![-1.0],
+            activation: Activation::Linear,
+        };
+        let mlp = Mlp::new(Encoding::TdGammon, vec![hidden, output]).unwrap();
+        let mut inputs = vec![0.0; 2 * TD_GAMMON_INPUTS];
+        inputs[0] = 3.0;
+        inputs[TD_GAMMON_INPUTS] = -0.25;
+        let mut outputs = [0.0; 2];
+        mlp.forward_batch(&inputs, &mut outputs);
+        // relu(3) - 1 and 2 * relu(0.25 + 0.5) - 1.
+        assert_eq!(outputs, [2.0, 0.5]);
+
+        let err = Mlp::new(Encoding::Wildbg, mlp.layers().to_vec()).unwrap_err();
+        assert!(matches!(err, MlpError::ShapeMismatch { layer: 0, .. }));
+    }
+
+    #[test]
+    fn round_trips_weight_files() {
+        let mlp = Mlp::random(Encoding::Gnubg, &[16, 8], Activation::Sigmoid, 7);
+        assert_eq!(mlp.outputs(), MLP_OUTPUTS);
+        let mut bytes = Vec::new();
+        mlp.write_to(&mut bytes).unwrap();
+        assert_eq!(&bytes[..4], b"BKNN");
+        assert_eq!(Mlp::read_from(&mut bytes.as_slice()).unwrap(), mlp);
+
+        let start = Variant::Backgammon.start_position();
+        let probabilities = mlp.evaluate(start);
+        assert!(probabilities.win_gammon <= probabilities.win);
+        assert!(probabilities.lose_backgammon <= probabilities.lose_gammon);
+        assert_eq!(mlp.evaluate_batch(&[start, start]), vec![probabilities; 2]);
+
+        assert!(matches!(
+            Mlp::read_from(&mut &bytes[..20]),
+            Err(MlpError::Io(_))
+        ));
+        let mut huge = bytes.clone();
+        huge[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            Mlp::read_from(&mut huge.as_slice()),
+            Err(MlpError::Io(_))
+        ));
+        bytes[4] = 9;
+        assert!(matches!(
+            Mlp::read_from(&mut bytes.as_slice()),
+            Err(MlpError::UnsupportedVersion(9))
+        ));
+
+        let mut layers = vec![Layer {
+            inputs: Encoding::Gnubg.inputs(),
+            outputs: 1,
+            weights: vec![0.0; Encoding::Gnubg.inputs()],
+            biases: vec![0.0],
+            activation: Activation::Linear,
+        }];
+        layers.extend((0..255).map(|_| Layer {
+            inputs: 1,
+            outputs: 1,
+            weights: vec![1.0],
+            biases: vec![0.0],
+            activation: Activation::Linear,
+        }));
+        assert!(matches!(
+            Mlp::new(Encoding::Gnubg, layers),
+            Err(MlpError::InvalidHeader)
+        ));
+    }
+}
@@ -1,12 +1,14 @@
 mod conversion;
 mod double_moves;
 mod mixed_moves;
+mod perfect_hash;
+
+pub use perfect_hash::num_positions;
 
 use crate::dice::Dice;
 use crate::position::GameResult::*;
 use crate::position::GameState::*;
 use crate::position::OngoingPhase::{Contact, Race};
-use crate::utils::mcomb;
 use std::cmp::min;
 use std::fmt;
 use std::fmt::Formatter;
@@ -61,6 +63,19 @@ impl GameResult {
             LoseBackgammon => -3.0,
         }
     }
+
+    /// Inverse of [Self::value].
+    pub fn from_value(value: f32) -> Option<Self> {
+        Some(match value as i32 {
+            1 => WinNormal,
+            2 => WinGammon,
+            3 => WinBackgammon,
+            -1 => LoseNormal,
+            -2 => LoseGammon,
+            -3 => LoseBackgammon,
+            _ => return None,
+        })
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash)]
@@ -123,7 +138,8 @@ pub trait State: Sized + Sync + Clone + Copy + Hash + PartialEq + Eq + fmt::Debu
         }
     }
 
-    fn dbhash(&self) -> usize;
+    /// Perfect hash of the position, see [Position::rank].
+    fn dbhash(&self) -> u128;
 
     fn show(&self) {
         println!("┌13─14─15─16─17─18─┬───┬19─20─21─22─23─24─┬───┐");
@@ -312,35 +328,8 @@ impl<const N: u8> State for Position<N> {
         }
     }
 
-    fn dbhash(&self) -> usize {
-        let points = 26;
-        let mut x_remaining = (Self::NUM_CHECKERS - self.x_off()) as usize;
-        let mut o_remaining = (Self::NUM_CHECKERS - self.o_off()) as usize;
-        let mut x_index = if x_remaining > 0 {
-            mcomb(points, x_remaining - 1)
-        } else {
-            0
-        };
-        let mut o_index = if o_remaining > 0 {
-            mcomb(points, o_remaining - 1)
-        } else {
-            0
-        };
-        for i in 1..=24 {
-            let n = self.pip(i);
-            match n {
-                n if n < 0 => o_remaining -= n.unsigned_abs() as usize,
-                n if n > 0 => x_remaining -= n as usize,
-                _ => {}
-            }
-            if o_remaining > 0 {
-                o_index += mcomb(points - i, o_remaining - 1);
-            }
-            if x_remaining > 0 {
-                x_index += mcomb(points - i, x_remaining - 1);
-            }
-        }
-        x_index * mcomb(points, Self::NUM_CHECKERS as usize) + o_index
+    fn dbhash(&self) -> u128 {
+        self.rank()
     }
 }
 
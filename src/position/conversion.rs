@@ -40,6 +40,15 @@ impl<const N: u8> Position<N> {
         }
         Self::try_from(pips).expect("Need legal position")
     }
+
+    /// Like [Position::from_hash_maps], with `(pip, checkers)` pairs for each side.
+    #[cfg(test)]
+    pub(crate) fn from_pairs(x: &[(usize, u8)], o: &[(usize, u8)]) -> Self {
+        Self::from_hash_maps(
+            &HashMap::from_iter(x.iter().copied()),
+            &HashMap::from_iter(o.iter().copied()),
+        )
+    }
 }
 
 // #[cfg(test)]
@@ -0,0 +1,246 @@
+use crate::position::{Position, O_BAR, X_BAR};
+
+/// Biggest checker count which can be ranked. This includes all built-in variants.
+const MAX_CHECKERS: usize = 15;
+
+/// Checkers are placed in slots, in this order: `x` off, `o` off, `x` bar, `o` bar, pips 1 to 24.
+/// The first four slots belong to one player, the pips can be taken by either player.
+const SLOTS: usize = 28;
+const X_OFF_SLOT: usize = 0;
+const O_OFF_SLOT: usize = 1;
+const X_BAR_SLOT: usize = 2;
+const O_BAR_SLOT: usize = 3;
+const FIRST_PIP_SLOT: usize = 4;
+
+type Counts = [[[u128; MAX_CHECKERS + 1]; MAX_CHECKERS + 1]; SLOTS + 1];
+
+/// `COUNTS[slot][x][o]` is the number of ways to place `x` checkers of `x` and `o` checkers of `o`
+/// in the slots starting with `slot`.
+static COUNTS: Counts = counts();
+
+const fn counts() -> Counts {
+    let mut counts = [[[0u128; MAX_CHECKERS + 1]; MAX_CHECKERS + 1]; SLOTS + 1];
+    counts[SLOTS][0][0] = 1;
+    let mut slot = SLOTS;
+    while slot > 0 {
+        slot -= 1;
+        let mut x = 0;
+        while x <= MAX_CHECKERS {
+            let mut o = 0;
+            while o <= MAX_CHECKERS {
+                let next = &counts[slot + 1];
+                let mut sum = 0;
+                if slot == X_OFF_SLOT || slot == X_BAR_SLOT {
+                    let mut k = 0;
+                    while k <= x {
+                        sum += next[x - k][o];
+                        k += 1;
+                    }
+                } else if slot == O_OFF_SLOT || slot == O_BAR_SLOT {
+                    let mut k = 0;
+                    while k <= o {
+                        sum += next[x][o - k];
+                        k += 1;
+                    }
+                } else {
+                    sum += next[x][o];
+                    let mut k = 1;
+                    while k <= x {
+                        sum += next[x - k][o];
+                        k += 1;
+                    }
+                    let mut k = 1;
+                    while k <= o {
+                        sum += next[x][o - k];
+                        k += 1;
+                    }
+                }
+                counts[slot][x][o] = sum;
+                o += 1;
+            }
+            x += 1;
+        }
+    }
+    counts
+}
+
+/// Number of positions with `checkers` checkers per side.
+///
+/// Positions where both players have borne off all checkers are not counted.
+/// For 15 checkers this is 18528584051601162495.
+pub const fn num_positions(checkers: u8) -> u128 {
+    assert!(checkers as usize <= MAX_CHECKERS);
+    counts()[0][checkers as usize][checkers as usize] - 1
+}
+
+impl<const N: u8> Position<N> {
+    /// Number of different positions, see [num_positions].
+    pub const NUM_POSITIONS: u128 = num_positions(N);
+
+    /// Checkers of `x` (positive) or `o` (negative) in a slot.
+    fn slot(&self, slot: usize) -> i8 {
+        match slot {
+            X_OFF_SLOT => self.x_off as i8,
+            O_OFF_SLOT => -(self.o_off as i8),
+            X_BAR_SLOT => self.pips[X_BAR],
+            O_BAR_SLOT => self.pips[O_BAR],
+            _ => self.pips[slot - FIRST_PIP_SLOT + 1],
+        }
+    }
+
+    /// Perfect hash of the position: a unique number smaller than [Self::NUM_POSITIONS].
+    ///
+    /// Positions are ordered lexicographically by their slots. In each slot no checkers come first,
+    /// then an increasing number of checkers of `x`, then an increasing number of checkers of `o`.
+    /// `turn` is ignored.
+    pub fn rank(&self) -> u128 {
+        let mut x = N as usize;
+        let mut o = N as usize;
+        let mut index = 0u128;
+        for slot in 0..SLOTS {
+            let next = &COUNTS[slot + 1];
+            let checkers = self.slot(slot);
+            let shared = slot >= FIRST_PIP_SLOT;
+            if checkers > 0 {
+                let k = checkers as usize;
+                let first = if shared { 1 } else { 0 };
+                if shared {
+                    index += next[x][o];
+                }
+                for j in first..k {
+                    index += next[x - j][o];
+                }
+                x -= k;
+            } else if checkers < 0 {
+                let k = checkers.unsigned_abs() as usize;
+                let first = if shared { 1 } else { 0 };
+                if shared {
+                    index += next[x][o];
+                    for j in 1..=x {
+                        index += next[x - j][o];
+                    }
+                }
+                for j in first..k {
+                    index += next[x][o - j];
+                }
+                o -= k;
+            }
+        }
+        debug_assert!(x == 0 && o == 0);
+        index
+    }
+
+    /// Inverse of [Self::rank]. Returns `None` if `index` is not smaller than [Self::NUM_POSITIONS].
+    pub fn unrank(index: u128) -> Option<Self> {
+        if index >= Self::NUM_POSITIONS {
+            return None;
+        }
+        let mut x = N as usize;
+        let mut o = N as usize;
+        let mut rest = index;
+        let mut position = Position {
+            turn: true,
+            pips: [0; 26],
+            x_off: 0,
+            o_off: 0,
+        };
+        for slot in 0..SLOTS {
+            let next = &COUNTS[slot + 1];
+            let checkers: i8 = match slot {
+                X_OFF_SLOT | X_BAR_SLOT => {
+                    let k = take(&mut rest, (0..=x).map(|k| next[x - k][o]));
+                    x -= k;
+                    k as i8
+                }
+                O_OFF_SLOT | O_BAR_SLOT => {
+                    let k = take(&mut rest, (0..=o).map(|k| next[x][o - k]));
+                    o -= k;
+                    -(k as i8)
+                }
+                _ => {
+                    let options = std::iter::once(next[x][o])
+                        .chain((1..=x).map(|k| next[x - k][o]))
+                        .chain((1..=o).map(|k| next[x][o - k]));
+                    let choice = take(&mut rest, options);
+                    if choice == 0 {
+                        0
+                    } else if choice <= x {
+                        x -= choice;
+                        choice as i8
+                    } else {
+                        let k = choice - x;
+                        o -= k;
+                        -(k as i8)
+                    }
+                }
+            };
+            match slot {
+                X_OFF_SLOT => position.x_off = checkers as u8,
+                O_OFF_SLOT => position.o_off = checkers.unsigned_abs(),
+                X_BAR_SLOT => position.pips[X_BAR] = checkers,
+                O_BAR_SLOT => position.pips[O_BAR] = checkers,
+                _ => position.pips[slot - FIRST_PIP_SLOT + 1] = checkers,
+            }
+        }
+        debug_assert!(x == 0 && o == 0 && rest == 0);
+        Some(position)
+    }
+}
+
+/// Returns the option in which `rest` falls and subtracts the sizes of all options before it.
+fn take(rest: &mut u128, options: impl Iterator<Item = u128>) -> usize {
+    for (choice, size) in options.enumerate() {
+        if *rest < size {
+            return choice;
+        }
+        *rest -= size;
+    }
+    unreachable!("index is smaller than the number of positions")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::num_positions;
+    use crate::position::{Position, State};
+    use crate::variants::{BACKGAMMON, HYPERGAMMON, HYPERGAMMON2};
+
+    #[test]
+    fn number_of_positions() {
+        assert_eq!(Position::<2>::NUM_POSITIONS, 107_252);
+        assert_eq!(Position::<3>::NUM_POSITIONS, 7_959_903);
+        assert_eq!(num_positions(15), 18_528_584_051_601_162_495);
+    }
+
+    #[test]
+    fn bijective_for_hypergammon2() {
+        for index in 0..Position::<2>::NUM_POSITIONS {
+            let position = Position::<2>::unrank(index).unwrap();
+            assert_eq!(Position::<2>::try_from(position.pips), Ok(position));
+            assert_eq!(position.rank(), index);
+        }
+        assert_eq!(Position::<2>::unrank(Position::<2>::NUM_POSITIONS), None);
+    }
+
+    #[test]
+    fn bijective_for_hypergammon3_sample() {
+        for index in (0..Position::<3>::NUM_POSITIONS).step_by(7919) {
+            let position = Position::<3>::unrank(index).unwrap();
+            assert_eq!(Position::<3>::try_from(position.pips), Ok(position));
+            assert_eq!(position.rank(), index);
+        }
+        let last = Position::<3>::NUM_POSITIONS - 1;
+        assert_eq!(Position::<3>::unrank(last).unwrap().rank(), last);
+    }
+
+    #[test]
+    fn rank_of_starting_positions() {
+        for position in [HYPERGAMMON2.flip(), HYPERGAMMON2] {
+            assert_eq!(Position::unrank(position.rank()), Some(position));
+        }
+        assert_eq!(Position::unrank(HYPERGAMMON.rank()), Some(HYPERGAMMON));
+        let rank = BACKGAMMON.rank();
+        assert!(rank < Position::<15>::NUM_POSITIONS);
+        assert_eq!(Position::unrank(rank), Some(BACKGAMMON));
+        assert_eq!(BACKGAMMON.dbhash(), rank);
+    }
+}
@@ -0,0 +1,397 @@
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::dice::Dice;
+use crate::dice_gen::DiceGen;
+use crate::equity::Probabilities;
+use crate::evaluator::{evaluate_or_result, Evaluator, Policy};
+use crate::position::GameState;
+use crate::rules::{ClassicRules, VariantRules};
+use crate::search::{Search, SearchOptions};
+use crate::variants::VariantPosition;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RolloutOptions {
+    pub trials: usize,
+    /// Trial `i` only depends on the seed and `i`, not on the number of threads.
+    pub seed: u64,
+    pub threads: usize,
+    /// Settle with the evaluator after this many plies, `None` plays every game to the end.
+    pub truncation: Option<usize>,
+    /// Rotate the 36 first rolls over the trials and stratify later rolls.
+    pub quasi_random: bool,
+    /// Subtract the luck of each roll as judged by the evaluator.
+    pub variance_reduction: bool,
+    /// Games still running after this many plies are settled with the evaluator.
+    pub max_plies: usize,
+}
+
+impl Default for RolloutOptions {
+    fn default() -> Self {
+        Self {
+            trials: 1296,
+            seed: 0,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            truncation: None,
+            quasi_random: true,
+            variance_reduction: true,
+            max_plies: 10_000,
+        }
+    }
+}
+
+/// Mean probabilities of the rolled out position with their standard errors, seen from the
+/// player on roll.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RolloutResult {
+    pub trials: usize,
+    pub probabilities: Probabilities,
+    pub errors: Probabilities,
+    pub equity: f32,
+    pub equity_error: f32,
+}
+
+/// Monte Carlo rollouts of a position before rolling. Moves are played by a [Policy], the
+/// [Evaluator] settles truncated games and judges the luck for variance reduction.
+pub struct Rollout<'a, E: ?Sized, R = ClassicRules> {
+    evaluator: &'a E,
+    options: RolloutOptions,
+    rules: PhantomData<fn() -> R>,
+}
+
+impl<'a, E: Evaluator + Sync + ?Sized> Rollout<'a, E> {
+    pub fn new(evaluator: &'a E, options: RolloutOptions) -> Self {
+        Self::with_rules(evaluator, options)
+    }
+}
+
+impl<'a, E: Evaluator + Sync + ?Sized, R: VariantRules> Rollout<'a, E, R> {
+    pub fn with_rules(evaluator: &'a E, options: RolloutOptions) -> Self {
+        Self {
+            evaluator,
+            options,
+            rules: PhantomData,
+        }
+    }
+
+    pub fn options(&self) -> &RolloutOptions {
+        &self.options
+    }
+
+    /// Rolls out `position`, both players play the best move of the evaluator.
+    pub fn run(&self, position: VariantPosition) -> RolloutResult {
+        self.run_with(position, || {
+            Search::<E, R>::with_rules(self.evaluator, SearchOptions::default())
+        })
+    }
+
+    /// Rolls out `position` with moves chosen by a policy, each thread creates its own.
+    pub fn run_with<P, F>(&self, position: VariantPosition, make_policy: F) -> RolloutResult
+    where
+        P: Policy,
+        F: Fn() -> P + Sync,
+    {
+        let trials = self.options.trials;
+        let results = Mutex::new(vec![Probabilities::default(); trials]);
+        let next = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..self.options.threads.clamp(1, trials.max(1)) {
+                scope.spawn(|| {
+                    let mut policy = make_policy();
+                    loop {
+                        let trial = next.fetch_add(1, Ordering::Relaxed);
+                        if trial >= trials {
+                            break;
+                        }
+                        let value = self.trial(position, trial, &mut policy);
+                        results.lock().expect("results lock")[trial] = value;
+                    }
+                });
+            }
+        });
+        summarize(&results.into_inner().expect("results lock"))
+    }
+
+    /// Result of one game from the side of the player on roll in `position`.
+    fn trial<P: Policy>(
+        &self,
+        mut position: VariantPosition,
+        trial: usize,
+        policy: &mut P,
+    ) -> Probabilities {
+        let options = &self.options;
+        let search = Search::<E, R>::with_rules(self.evaluator, SearchOptions::default());
+        let mut dice_gen = RolloutDice::new(options, trial);
+        let mut luck = Probabilities::default();
+        let mut root_on_roll = true;
+        let from_root = |value: Probabilities, root_on_roll: bool| {
+            if root_on_roll {
+                value
+            } else {
+                value.flip()
+            }
+        };
+        let mut ply = 0;
+        let value = loop {
+            if let GameState::GameOver(result) = position.game_state() {
+                break Probabilities::from_result(&result);
+            }
+            if options.truncation == Some(ply) || ply >= options.max_plies {
+                break evaluate_or_result(self.evaluator, position);
+            }
+            let dice = dice_gen.roll();
+            if options.variance_reduction {
+                let expected = search.evaluate(position, 1);
+                let actual = match search.best_move(position, &dice) {
+                    Some(best) => best.probabilities,
+                    None => evaluate_or_result(self.evaluator, position.flip()).flip(),
+                };
+                let actual = from_root(actual, root_on_roll);
+                let expected = from_root(expected, root_on_roll);
+                luck = add(luck, sub(actual, expected));
+            }
+            let legal = R::legal_positions(position, &dice);
+            // Without a legal position the roll is passed, like in [Search::evaluate].
+            position = if legal.is_empty() {
+                position.flip()
+            } else {
+                legal[policy.choose(position, &dice, &legal).min(legal.len() - 1)]
+            };
+            root_on_roll = !root_on_roll;
+            ply += 1;
+        };
+        sub(from_root(value, root_on_roll), luck)
+    }
+}
+
+/// Dice of one trial. With quasi-random dice, roll `k` of trial `i` is the `k`th base-36 digit
+/// of `i`, rotated by an offset depending on the earlier digits. So every sequence of earlier
+/// rolls is followed by all 36 rolls equally often, as long as there are enough trials.
+struct RolloutDice {
+    trial: usize,
+    trials: usize,
+    seed: u64,
+    quasi_random: bool,
+    ply: u32,
+    rng: fastrand::Rng,
+}
+
+impl RolloutDice {
+    fn new(options: &RolloutOptions, trial: usize) -> Self {
+        Self {
+            trial,
+            trials: options.trials,
+            seed: options.seed,
+            quasi_random: options.quasi_random,
+            ply: 0,
+            rng: fastrand::Rng::with_seed(mix(options.seed, trial as u64)),
+        }
+    }
+
+    fn stratified(&self) -> Option<usize> {
+        if !self.quasi_random {
+            return None;
+        }
+        let stride = 36usize.checked_pow(self.ply)?;
+        if stride >= self.trials {
+            return None;
+        }
+        let digit = self.trial / stride % 36;
+        let prefix = self.trial % stride;
+        let offset = mix(mix(self.seed, self.ply as u64), prefix as u64) % 36;
+        Some((digit + offset as usize) % 36)
+    }
+}
+
+impl DiceGen for RolloutDice {
+    fn roll(&mut self) -> Dice {
+        let index = self.stratified().unwrap_or_else(|| self.rng.usize(0..36));
+        self.ply += 1;
+        Dice::new(index / 6 + 1, index % 6 + 1)
+    }
+
+    fn choose_index(&mut self, chances: &[f32]) -> usize {
+        let total: f32 = chances.iter().sum();
+        let mut threshold = self.rng.f32() * total;
+        for (index, &chance) in chances.iter().enumerate() {
+            if threshold < chance {
+                return index;
+            }
+            threshold -= chance;
+        }
+        chances.len() - 1
+    }
+}
+
+/// SplitMix64 finalizer of `a` and `b`.
+fn mix(a: u64, b: u64) -> u64 {
+    let mut z = a ^ b
+        .wrapping_add(0x9e37_79b9_7f4a_7c15)
+        .wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn add(a: Probabilities, b: Probabilities) -> Probabilities {
+    let (a, b) = (a.to_array(), b.to_array());
+    Probabilities::from_array(std::array::from_fn(|i| a[i] + b[i]))
+}
+
+fn sub(a: Probabilities, b: Probabilities) -> Probabilities {
+    let (a, b) = (a.to_array(), b.to_array());
+    Probabilities::from_array(std::array::from_fn(|i| a[i] - b[i]))
+}
+
+fn summarize(values: &[Probabilities]) -> RolloutResult {
+    let n = values.len();
+    if n == 0 {
+        return RolloutResult::default();
+    }
+    let mut sums = [0f64; 6];
+    let mut squares = [0f64; 6];
+    for value in values {
+        let c = value.to_array();
+        let x = [c[0], c[1], c[2], c[3], c[4], value.equity()];
+        for i in 0..6 {
+            sums[i] += x[i] as f64;
+            squares[i] += (x[i] as f64).powi(2);
+        }
+    }
+    let mean: [f64; 6] = std::array::from_fn(|i| sums[i] / n as f64);
+    let error: [f64; 6] = std::array::from_fn(|i| {
+        if n < 2 {
+            return 0.0;
+        }
+        let variance = (squares[i] - n as f64 * mean[i] * mean[i]) / (n - 1) as f64;
+        (variance.max(0.0) / n as f64).sqrt()
+    });
+    RolloutResult {
+        trials: n,
+        probabilities: Probabilities::from_array(std::array::from_fn(|i| mean[i] as f32)),
+        errors: Probabilities::from_array(std::array::from_fn(|i| error[i] as f32)),
+        equity: mean[5] as f32,
+        equity_error: error[5] as f32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rollout, RolloutDice, RolloutOptions};
+    use crate::dice_gen::DiceGen;
+    use crate::evaluator::{Evaluator, PipEvaluator};
+    use crate::position::Position;
+    use crate::rules::PassRules;
+    use crate::variants::VariantPosition;
+    use std::collections::HashSet;
+
+    fn options(trials: usize) -> RolloutOptions {
+        RolloutOptions {
+            trials,
+            seed: 7,
+            threads: 3,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn quasi_random_dice_cover_the_first_two_rolls() {
+        let options = options(1296);
+        let mut first = HashSet::new();
+        let mut sequences = HashSet::new();
+        for trial in 0..1296 {
+            let mut dice = RolloutDice::new(&options, trial);
+            let a = dice.stratified().unwrap();
+            dice.roll();
+            let b = dice.stratified().unwrap();
+            dice.roll();
+            assert_eq!(dice.stratified(), None);
+            if trial < 36 {
+                first.insert(a);
+            }
+            sequences.insert((a, b));
+        }
+        assert_eq!(first.len(), 36);
+        assert_eq!(sequences.len(), 1296);
+
+        let random = RolloutOptions {
+            quasi_random: false,
+            ..options
+        };
+        assert_eq!(RolloutDice::new(&random, 0).stratified(), None);
+    }
+
+    #[test]
+    fn finished_races_have_no_error() {
+        let mut pips = [0; 26];
+        pips[1] = 2;
+        pips[10] = -3;
+        let position = VariantPosition::Hypergammon(Position {
+            turn: true,
+            pips,
+            x_off: 1,
+            o_off: 0,
+        });
+        let result = Rollout::new(&PipEvaluator, options(100)).run(position);
+        assert_eq!(result.trials, 100);
+        assert!((result.probabilities.win - 1.0).abs() < 1e-6);
+        assert!((result.probabilities.win_gammon - 1.0).abs() < 1e-6);
+        assert_eq!(result.probabilities.win_backgammon, 0.0);
+        assert!(result.errors.win < 1e-6);
+    }
+
+    #[test]
+    fn results_are_reproducible_and_variance_is_reduced() {
+        // A race, where pip counts are a fair judge of luck.
+        let mut pips = [0; 26];
+        pips[6] = 1;
+        pips[8] = 1;
+        pips[11] = 1;
+        pips[15] = -1;
+        pips[18] = -1;
+        pips[20] = -1;
+        let start = VariantPosition::Hypergammon(Position {
+            turn: true,
+            pips,
+            x_off: 0,
+            o_off: 0,
+        });
+        let plain = RolloutOptions {
+            variance_reduction: false,
+            ..options(360)
+        };
+        let single_thread = RolloutOptions {
+            threads: 1,
+            ..plain
+        };
+        let single = Rollout::new(&PipEvaluator, single_thread).run(start);
+        let plain = Rollout::new(&PipEvaluator, plain).run(start);
+        assert_eq!(single, plain);
+
+        let reduced = Rollout::new(&PipEvaluator, options(360)).run(start);
+        assert!(
+            reduced.errors.win < plain.errors.win,
+            "{reduced:?} {plain:?}"
+        );
+        assert!((reduced.probabilities.win - plain.probabilities.win).abs() < 0.1);
+
+        let truncated = RolloutOptions {
+            truncation: Some(0),
+            ..options(10)
+        };
+        let truncated = Rollout::new(&PipEvaluator, truncated).run(start);
+        assert_eq!(truncated.probabilities, PipEvaluator.evaluate(start));
+        assert_eq!(truncated.equity_error, 0.0);
+
+        // Passed rolls hand the position to the opponent instead of ending the trial.
+        let passing = RolloutOptions {
+            truncation: Some(1),
+            ..options(10)
+        };
+        let passed = Rollout::<_, PassRules>::with_rules(&PipEvaluator, passing).run(start);
+        let expected = PipEvaluator.evaluate(start.flip()).flip();
+        assert!((passed.probabilities.win - expected.win).abs() < 1e-6);
+        assert_eq!(passed.equity_error, 0.0);
+    }
+}
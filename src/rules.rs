@@ -116,6 +116,17 @@ impl VariantRules for NoHitRules {
     }
 }
 
+/// Rules without legal positions, every roll is passed.
+#[cfg(test)]
+pub(crate) struct PassRules;
+
+#[cfg(test)]
+impl VariantRules for PassRules {
+    fn legal_positions(_position: VariantPosition, _dice: &Dice) -> Vec<VariantPosition> {
+        Vec::new()
+    }
+}
+
 fn map_variant_positions<const N: u8>(
     positions: Vec<Position<N>>,
     wrap: fn(Position<N>) -> VariantPosition,
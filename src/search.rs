@@ -0,0 +1,286 @@
+use std::marker::PhantomData;
+
+use crate::dice::{Dice, ALL_21};
+use crate::equity::Probabilities;
+use crate::evaluator::{evaluate_or_result, Evaluator, Policy};
+use crate::position::GameState;
+use crate::rules::{ClassicRules, VariantRules};
+use crate::variants::VariantPosition;
+
+/// gnubg-style move filter, applied before the candidates are searched one ply deeper.
+///
+/// The best `accept` moves are always kept, then up to `extra` more whose equity is within
+/// `threshold` of the best move. The best move itself is never dropped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveFilter {
+    pub accept: usize,
+    pub extra: usize,
+    pub threshold: f32,
+}
+
+impl MoveFilter {
+    /// gnubg's "normal" filter.
+    pub const NORMAL: Self = Self {
+        accept: 0,
+        extra: 8,
+        threshold: 0.16,
+    };
+
+    /// Keeps every move.
+    pub const ALL: Self = Self {
+        accept: usize::MAX,
+        extra: 0,
+        threshold: 0.0,
+    };
+
+    /// Number of moves to keep from candidates sorted by descending equity.
+    fn keep(&self, sorted: &[Candidate]) -> usize {
+        let Some(best) = sorted.first() else {
+            return 0;
+        };
+        let accepted = self.accept.max(1).min(sorted.len());
+        let extra = sorted[accepted..]
+            .iter()
+            .take(self.extra)
+            .take_while(|c| best.equity - c.equity <= self.threshold)
+            .count();
+        accepted + extra
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchOptions {
+    /// Lookahead in rolls, 0 is a static evaluation of the positions after each move.
+    pub plies: usize,
+    /// `filters[i]` selects the moves searched at ply `i + 1`, missing filters keep all moves.
+    pub filters: Vec<MoveFilter>,
+}
+
+impl SearchOptions {
+    pub fn new(plies: usize) -> Self {
+        Self {
+            plies,
+            filters: vec![MoveFilter::NORMAL; plies],
+        }
+    }
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// A legal move with its evaluation, seen from the player who moved.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candidate {
+    /// Position after the move, seen from the opponent.
+    pub position: VariantPosition,
+    pub probabilities: Probabilities,
+    /// Cubeless money equity of [Self::probabilities].
+    pub equity: f32,
+    /// Ply the move was evaluated at, lower than the search depth if it was filtered out.
+    pub ply: usize,
+}
+
+/// Expectiminimax search over [ALL_21] with an [Evaluator] at the leaves.
+pub struct Search<'a, E: ?Sized, R = ClassicRules> {
+    evaluator: &'a E,
+    options: SearchOptions,
+    rules: PhantomData<fn() -> R>,
+}
+
+impl<'a, E: Evaluator + ?Sized> Search<'a, E> {
+    pub fn new(evaluator: &'a E, options: SearchOptions) -> Self {
+        Self::with_rules(evaluator, options)
+    }
+}
+
+impl<'a, E: Evaluator + ?Sized, R: VariantRules> Search<'a, E, R> {
+    pub fn with_rules(evaluator: &'a E, options: SearchOptions) -> Self {
+        Self {
+            evaluator,
+            options,
+            rules: PhantomData,
+        }
+    }
+
+    pub fn options(&self) -> &SearchOptions {
+        &self.options
+    }
+
+    /// Legal moves for `dice` from best to worst. Moves searched deeper come first.
+    pub fn rank_moves(&self, position: VariantPosition, dice: &Dice) -> Vec<Candidate> {
+        let mut candidates: Vec<Candidate> = R::legal_positions(position, dice)
+            .into_iter()
+            .map(|child| self.candidate(child, 0))
+            .collect();
+        sort(&mut candidates);
+        let mut searched = candidates.len();
+        for ply in 1..=self.options.plies {
+            let filter = self
+                .options
+                .filters
+                .get(ply - 1)
+                .copied()
+                .unwrap_or(MoveFilter::ALL);
+            searched = filter.keep(&candidates[..searched]);
+            for candidate in &mut candidates[..searched] {
+                *candidate = self.candidate(candidate.position, ply);
+            }
+            sort(&mut candidates[..searched]);
+        }
+        candidates
+    }
+
+    /// The best move for `dice`, `None` if there is no legal position.
+    pub fn best_move(&self, position: VariantPosition, dice: &Dice) -> Option<Candidate> {
+        self.rank_moves(position, dice).into_iter().next()
+    }
+
+    /// Probabilities of `position` before rolling, searched `plies` rolls deep. Inner nodes play
+    /// the best move by static evaluation.
+    pub fn evaluate(&self, position: VariantPosition, plies: usize) -> Probabilities {
+        if plies == 0 || position.game_state() != GameState::Ongoing {
+            return evaluate_or_result(self.evaluator, position);
+        }
+        let mut sum = Probabilities::default();
+        for (dice, weight) in ALL_21 {
+            // Without a legal position the roll is passed.
+            let child = R::legal_positions(position, &dice)
+                .into_iter()
+                .map(|child| (child, evaluate_or_result(self.evaluator, child).flip()))
+                .max_by(|(_, a), (_, b)| a.equity().total_cmp(&b.equity()))
+                .map_or(position.flip(), |(child, _)| child);
+            let value = self.evaluate(child, plies - 1).flip();
+            let probability = weight as f32 / 36.0;
+            sum.win += probability * value.win;
+            sum.win_gammon += probability * value.win_gammon;
+            sum.win_backgammon += probability * value.win_backgammon;
+            sum.lose_gammon += probability * value.lose_gammon;
+            sum.lose_backgammon += probability * value.lose_backgammon;
+        }
+        sum
+    }
+
+    fn candidate(&self, position: VariantPosition, ply: usize) -> Candidate {
+        let probabilities = self.evaluate(position, ply).flip();
+        Candidate {
+            position,
+            probabilities,
+            equity: probabilities.equity(),
+            ply,
+        }
+    }
+}
+
+/// Plays the best move of the search.
+impl<E: Evaluator + ?Sized, R: VariantRules> Policy for Search<'_, E, R> {
+    fn choose(
+        &mut self,
+        position: VariantPosition,
+        dice: &Dice,
+        legal: &[VariantPosition],
+    ) -> usize {
+        let ranked = self.rank_moves(position, dice);
+        ranked
+            .first()
+            .and_then(|best| legal.iter().position(|p| *p == best.position))
+            .unwrap_or(0)
+    }
+}
+
+fn sort(candidates: &mut [Candidate]) {
+    candidates.sort_by(|a, b| b.equity.total_cmp(&a.equity));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MoveFilter, Search, SearchOptions};
+    use crate::dice::{Dice, ALL_21};
+    use crate::evaluator::{Evaluator, PipEvaluator};
+    use crate::position::Position;
+    use crate::rules::{ClassicRules, NoHitRules, PassRules, VariantRules};
+    use crate::variants::{Variant, VariantPosition};
+
+    fn hypergammon(pips: [i8; 26]) -> VariantPosition {
+        VariantPosition::Hypergammon(Position {
+            turn: true,
+            pips,
+            x_off: (3 - pips.iter().filter(|&&p| p > 0).sum::<i8>()) as u8,
+            o_off: (3 + pips.iter().filter(|&&p| p < 0).sum::<i8>()) as u8,
+        })
+    }
+
+    #[test]
+    fn static_ranking_prefers_the_shorter_race() {
+        let search = Search::new(&PipEvaluator, SearchOptions::default());
+        let start = Variant::Backgammon.start_position();
+        let ranked = search.rank_moves(start, &Dice::new(6, 5));
+        assert!(ranked.windows(2).all(|w| w[0].equity >= w[1].equity));
+        assert!(ranked.iter().all(|c| c.ply == 0));
+        // Every move of 6-5 shortens the race by 11 pips, so all are equal.
+        assert!((ranked[0].equity - ranked.last().unwrap().equity).abs() < 1e-6);
+    }
+
+    #[test]
+    fn one_ply_averages_the_opponent_replies() {
+        let search = Search::new(&PipEvaluator, SearchOptions::new(1));
+        // One checker each left near home, the player on roll bears off both with any roll.
+        let mut pips = [0; 26];
+        pips[1] = 1;
+        pips[2] = 1;
+        pips[20] = -2;
+        let position = hypergammon(pips);
+        assert!((search.evaluate(position, 1).win - 1.0).abs() < 1e-5);
+        let best = search.best_move(position, &Dice::new(2, 1)).unwrap();
+        assert!((best.probabilities.win - 1.0).abs() < 1e-5);
+        assert_eq!(best.ply, 1);
+
+        // The 1-ply value of a move is the average of the opponent's best replies.
+        let start = Variant::Hypergammon.start_position();
+        let dice = Dice::new(3, 1);
+        let child = search.rank_moves(start, &dice)[0].position;
+        let mut expected = 0.0;
+        for (roll, weight) in ALL_21 {
+            let reply = <ClassicRules as VariantRules>::legal_positions(child, &roll)
+                .into_iter()
+                .map(|next| PipEvaluator.evaluate(next).win)
+                .fold(f32::MAX, f32::min);
+            expected += weight as f32 / 36.0 * reply;
+        }
+        assert!((search.evaluate(child, 1).flip().win - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn passed_rolls_leave_the_position_to_the_opponent() {
+        let search = Search::<_, PassRules>::with_rules(&PipEvaluator, SearchOptions::new(1));
+        let start = Variant::Hypergammon.start_position();
+        let expected = PipEvaluator.evaluate(start.flip()).flip();
+        assert!((search.evaluate(start, 1).win - expected.win).abs() < 1e-5);
+    }
+
+    #[test]
+    fn filters_limit_the_deeper_search() {
+        let options = SearchOptions {
+            plies: 2,
+            filters: vec![
+                MoveFilter {
+                    accept: 3,
+                    extra: 0,
+                    threshold: 0.0,
+                },
+                MoveFilter {
+                    accept: 1,
+                    extra: 0,
+                    threshold: 0.0,
+                },
+            ],
+        };
+        let search = Search::<_, NoHitRules>::with_rules(&PipEvaluator, options);
+        let ranked = search.rank_moves(Variant::Hypergammon.start_position(), &Dice::new(4, 2));
+        let plies: Vec<usize> = ranked.iter().map(|c| c.ply).collect();
+        assert_eq!(&plies[..3], [2, 1, 1]);
+        assert!(plies[3..].iter().all(|&ply| ply == 0));
+    }
+}
@@ -0,0 +1,620 @@
+use crate::dice::ALL_21;
+use crate::equity::{CubeDecision, CubeEquities, CubeOwnership, Probabilities};
+use crate::position::{GameState, Position, State};
+use crate::rules::{ClassicRules, PositionRules};
+use crate::variants::VariantPosition;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: [u8; 4] = *b"BKHG";
+const VERSION: u32 = 1;
+const FLAG_CUBEFUL: u8 = 1;
+const FLAG_JACOBY: u8 = 2;
+/// Positions a thread solves before its new values are written back into the table.
+const BLOCK: usize = 1 << 16;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SolverError {
+    #[error("solver table i/o failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid solver table header")]
+    InvalidHeader,
+    #[error("unsupported solver table version {0}")]
+    UnsupportedVersion(u32),
+    #[error("{0} checkers have too many positions to solve in memory")]
+    TooManyPositions(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SolveOptions {
+    /// Stop when no probability changes more than this in one iteration.
+    pub tolerance: f32,
+    pub max_iterations: usize,
+    pub threads: usize,
+    /// Also solve cubeful money equities.
+    pub cubeful: bool,
+    /// Gammons only count after the cube has been turned. Only used with `cubeful`.
+    pub jacoby: bool,
+}
+
+impl Default for SolveOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-6,
+            max_iterations: 1000,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            cubeful: false,
+            jacoby: false,
+        }
+    }
+}
+
+/// Money equities for a cube value of 1 if the player on roll doesn't double, for each
+/// ownership of the cube.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CubefulValues {
+    pub centered: f32,
+    pub owned: f32,
+    pub unavailable: f32,
+}
+
+impl CubefulValues {
+    fn no_double(&self, ownership: CubeOwnership) -> f32 {
+        match ownership {
+            CubeOwnership::Centered => self.centered,
+            CubeOwnership::Owned => self.owned,
+            CubeOwnership::Unavailable => self.unavailable,
+        }
+    }
+
+    /// Equity after double and take: the opponent owns a cube of twice the value.
+    pub fn double_take(&self) -> f32 {
+        2.0 * self.unavailable
+    }
+
+    /// Equities of the cube decision of the player on roll.
+    pub fn cube_equities(&self, ownership: CubeOwnership) -> CubeEquities {
+        CubeEquities {
+            ownership,
+            no_double: self.no_double(ownership),
+            double_take: self.double_take(),
+            double_pass: 1.0,
+        }
+    }
+
+    pub fn decision(&self, ownership: CubeOwnership) -> CubeDecision {
+        self.cube_equities(ownership).decision()
+    }
+
+    /// Equity with optimal cube decisions of both players.
+    pub fn equity(&self, ownership: CubeOwnership) -> f32 {
+        self.cube_equities(ownership).equity()
+    }
+
+    fn max_difference(&self, other: &Self) -> f32 {
+        (self.centered - other.centered)
+            .abs()
+            .max((self.owned - other.owned).abs())
+            .max((self.unavailable - other.unavailable).abs())
+    }
+}
+
+/// Exact probabilities for every position of a variant, indexed by [Position::rank].
+///
+/// Optionally also cubeful money equities. Beavers are not considered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolvedTable {
+    checkers: u8,
+    values: Vec<Probabilities>,
+    cubeful: Option<Vec<CubefulValues>>,
+    jacoby: bool,
+}
+
+impl SolvedTable {
+    /// Solves all positions with `N` checkers per side by value iteration.
+    ///
+    /// Values are updated in place, so every position takes five `f32`, eight with cubeful
+    /// equities. That is about 0.16 GB for hypergammon with 3 checkers, 6.8 GB with 4 (10.9 GB
+    /// cubeful) and too much with 5.
+    pub fn solve<const N: u8>(options: &SolveOptions) -> Result<Self, SolverError> {
+        Self::solve_with::<ClassicRules, N, _>(options, |_, _| {})
+    }
+
+    /// Like [Self::solve], with other rules and a callback after each iteration.
+    ///
+    /// The callback gets the number of finished iterations and the biggest change in them.
+    pub fn solve_with<R, const N: u8, F>(
+        options: &SolveOptions,
+        mut progress: F,
+    ) -> Result<Self, SolverError>
+    where
+        R: PositionRules<N>,
+        F: FnMut(usize, f32),
+    {
+        let count = usize::try_from(Position::<N>::NUM_POSITIONS)
+            .map_err(|_| SolverError::TooManyPositions(N))?;
+        let mut values: Vec<Probabilities> = (0..count)
+            .map(|index| match unrank::<N>(index).game_state() {
+                GameState::GameOver(result) => Probabilities::from_result(&result),
+                GameState::Ongoing => Probabilities {
+                    win: 0.5,
+                    ..Probabilities::default()
+                },
+            })
+            .collect();
+        let jacoby = options.cubeful && options.jacoby;
+        let mut cubeful: Option<Vec<CubefulValues>> = options.cubeful.then(|| {
+            (0..count)
+                .map(|index| match unrank::<N>(index).game_state() {
+                    GameState::GameOver(result) => {
+                        let points = result.value();
+                        CubefulValues {
+                            centered: if jacoby { points.signum() } else { points },
+                            owned: points,
+                            unavailable: points,
+                        }
+                    }
+                    GameState::Ongoing => CubefulValues::default(),
+                })
+                .collect()
+        });
+        let threads = options.threads.max(1);
+        let mut buffers = vec![Vec::new(); threads];
+        let mut cubeful_buffers = vec![Vec::new(); threads];
+
+        for iteration in 1..=options.max_iterations {
+            let mut delta = 0.0f32;
+            for round in (0..count).step_by(threads * BLOCK) {
+                let blocks = (round..count.min(round + threads * BLOCK)).step_by(BLOCK);
+                let current = Current {
+                    values: &values,
+                    cubeful: cubeful.as_deref(),
+                };
+                let round_delta = std::thread::scope(|scope| {
+                    let handles: Vec<_> = blocks
+                        .clone()
+                        .zip(buffers.iter_mut().zip(cubeful_buffers.iter_mut()))
+                        .map(|(first, (out, cubeful_out))| {
+                            let len = BLOCK.min(count - first);
+                            out.clear();
+                            out.extend_from_slice(&current.values[first..first + len]);
+                            let cubeful_out = current.cubeful.map(|cubeful| {
+                                cubeful_out.clear();
+                                cubeful_out.extend_from_slice(&cubeful[first..first + len]);
+                                Vec::as_mut_slice(cubeful_out)
+                            });
+                            scope.spawn(move || {
+                                iterate_chunk::<R, N>(current, first, out, cubeful_out)
+                            })
+                        })
+                        .collect();
+                    handles
+                        .into_iter()
+                        .map(|h| h.join().expect("solver thread panicked"))
+                        .fold(0.0f32, f32::max)
+                });
+                delta = delta.max(round_delta);
+                // Later rounds already see these values.
+                for (first, (out, cubeful_out)) in blocks.zip(buffers.iter().zip(&cubeful_buffers))
+                {
+                    values[first..first + out.len()].copy_from_slice(out);
+                    if let Some(cubeful) = cubeful.as_mut() {
+                        cubeful[first..first + out.len()].copy_from_slice(cubeful_out);
+                    }
+                }
+            }
+            progress(iteration, delta);
+            if delta <= options.tolerance {
+                break;
+            }
+        }
+        Ok(Self {
+            checkers: N,
+            values,
+            cubeful,
+            jacoby,
+        })
+    }
+
+    pub fn checkers(&self) -> u8 {
+        self.checkers
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn is_cubeful(&self) -> bool {
+        self.cubeful.is_some()
+    }
+
+    pub fn is_jacoby(&self) -> bool {
+        self.jacoby
+    }
+
+    /// Probabilities for the player on roll. `None` if the table is for another checker count.
+    pub fn lookup<const N: u8>(&self, position: &Position<N>) -> Option<Probabilities> {
+        if N != self.checkers {
+            return None;
+        }
+        self.values.get(position.rank() as usize).copied()
+    }
+
+    pub fn lookup_variant(&self, position: VariantPosition) -> Option<Probabilities> {
+        match position {
+            VariantPosition::Backgammon(p)
+            | VariantPosition::Nackgammon(p)
+            | VariantPosition::Longgammon(p) => self.lookup(&p),
+            VariantPosition::Hypergammon(p) => self.lookup(&p),
+            VariantPosition::Hypergammon2(p) => self.lookup(&p),
+            VariantPosition::Hypergammon4(p) => self.lookup(&p),
+            VariantPosition::Hypergammon5(p) => self.lookup(&p),
+        }
+    }
+
+    /// Cubeful equities of the player on roll, if the table was solved with them.
+    pub fn lookup_cubeful<const N: u8>(&self, position: &Position<N>) -> Option<CubefulValues> {
+        if N != self.checkers {
+            return None;
+        }
+        self.cubeful
+            .as_ref()?
+            .get(position.rank() as usize)
+            .copied()
+    }
+
+    pub fn lookup_cubeful_variant(&self, position: VariantPosition) -> Option<CubefulValues> {
+        match position {
+            VariantPosition::Backgammon(p)
+            | VariantPosition::Nackgammon(p)
+            | VariantPosition::Longgammon(p) => self.lookup_cubeful(&p),
+            VariantPosition::Hypergammon(p) => self.lookup_cubeful(&p),
+            VariantPosition::Hypergammon2(p) => self.lookup_cubeful(&p),
+            VariantPosition::Hypergammon4(p) => self.lookup_cubeful(&p),
+            VariantPosition::Hypergammon5(p) => self.lookup_cubeful(&p),
+        }
+    }
+
+    pub fn cube_decision<const N: u8>(
+        &self,
+        position: &Position<N>,
+        ownership: CubeOwnership,
+    ) -> Option<CubeDecision> {
+        self.lookup_cubeful(position)
+            .map(|values| values.decision(ownership))
+    }
+
+    /// File layout, little endian: magic `BKHG`, version (u32), checkers (u8), flags (u8, bit 0
+    /// cubeful, bit 1 Jacoby), reserved (u16), number of entries (u64), then five `f32` per
+    /// position, followed by three more if cubeful.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), SolverError> {
+        let mut flags = 0;
+        if self.is_cubeful() {
+            flags |= FLAG_CUBEFUL;
+        }
+        if self.jacoby {
+            flags |= FLAG_JACOBY;
+        }
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[self.checkers, flags, 0, 0])?;
+        writer.write_all(&(self.values.len() as u64).to_le_bytes())?;
+        for (index, p) in self.values.iter().enumerate() {
+            for value in [
+                p.win,
+                p.win_gammon,
+                p.win_backgammon,
+                p.lose_gammon,
+                p.lose_backgammon,
+            ] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+            if let Some(cubeful) = &self.cubeful {
+                let c = cubeful[index];
+                for value in [c.centered, c.owned, c.unavailable] {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, SolverError> {
+        let mut header = [0u8; 20];
+        reader.read_exact(&mut header)?;
+        if header[0..4] != MAGIC {
+            return Err(SolverError::InvalidHeader);
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into().expect("4 bytes"));
+        if version != VERSION {
+            return Err(SolverError::UnsupportedVersion(version));
+        }
+        let checkers = header[8];
+        let flags = header[9];
+        let is_cubeful = flags & FLAG_CUBEFUL != 0;
+        let entries = u64::from_le_bytes(header[12..20].try_into().expect("8 bytes"));
+        if checkers > 15 || entries as u128 != crate::position::num_positions(checkers) {
+            return Err(SolverError::InvalidHeader);
+        }
+        let mut values = Vec::with_capacity(entries as usize);
+        let mut cubeful = Vec::new();
+        let mut bytes = [0u8; 32];
+        let size = if is_cubeful { 32 } else { 20 };
+        for _ in 0..entries {
+            reader.read_exact(&mut bytes[..size])?;
+            let f = |i: usize| f32::from_le_bytes(bytes[4 * i..4 * i + 4].try_into().unwrap());
+            values.push(Probabilities {
+                win: f(0),
+                win_gammon: f(1),
+                win_backgammon: f(2),
+                lose_gammon: f(3),
+                lose_backgammon: f(4),
+            });
+            if is_cubeful {
+                cubeful.push(CubefulValues {
+                    centered: f(5),
+                    owned: f(6),
+                    unavailable: f(7),
+                });
+            }
+        }
+        Ok(Self {
+            checkers,
+            values,
+            cubeful: is_cubeful.then_some(cubeful),
+            jacoby: flags & FLAG_JACOBY != 0,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SolverError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SolverError> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+fn unrank<const N: u8>(index: usize) -> Position<N> {
+    Position::unrank(index as u128).expect("index within number of positions")
+}
+
+/// Values of the table, shared by all threads of a round.
+#[derive(Clone, Copy)]
+struct Current<'a> {
+    values: &'a [Probabilities],
+    cubeful: Option<&'a [CubefulValues]>,
+}
+
+/// Computes the next values for the positions starting with `first`. Returns the biggest change.
+fn iterate_chunk<R: PositionRules<N>, const N: u8>(
+    current: Current,
+    first: usize,
+    out: &mut [Probabilities],
+    mut cubeful_out: Option<&mut [CubefulValues]>,
+) -> f32 {
+    let mut delta = 0.0f32;
+    for (offset, slot) in out.iter_mut().enumerate() {
+        let index = first + offset;
+        let position = unrank::<N>(index);
+        if position.game_state() != GameState::Ongoing {
+            continue;
+        }
+        let mut sum = Probabilities::default();
+        let mut cube_sum = CubefulValues::default();
+        for (dice, weight) in ALL_21 {
+            let mut best: Option<Probabilities> = None;
+            let mut cube_best = CubefulValues {
+                centered: f32::MIN,
+                owned: f32::MIN,
+                unavailable: f32::MIN,
+            };
+            for child in R::legal_positions(position, &dice) {
+                // Children are seen from the opponent, who is on roll next.
+                let rank = child.rank() as usize;
+                let value = current.values[rank].flip();
+                if best.is_none_or(|b| value.equity() > b.equity()) {
+                    best = Some(value);
+                }
+                if let Some(cubeful) = current.cubeful {
+                    // The opponent's cube decision is part of the child's value.
+                    let child = cubeful[rank];
+                    cube_best.centered = cube_best
+                        .centered
+                        .max(-child.equity(CubeOwnership::Centered));
+                    cube_best.owned = cube_best
+                        .owned
+                        .max(-child.equity(CubeOwnership::Unavailable));
+                    cube_best.unavailable = cube_best
+                        .unavailable
+                        .max(-child.equity(CubeOwnership::Owned));
+                }
+            }
+            let best = best.expect("there is always a legal position");
+            let probability = weight as f32 / 36.0;
+            sum.win += probability * best.win;
+            sum.win_gammon += probability * best.win_gammon;
+            sum.win_backgammon += probability * best.win_backgammon;
+            sum.lose_gammon += probability * best.lose_gammon;
+            sum.lose_backgammon += probability * best.lose_backgammon;
+            cube_sum.centered += probability * cube_best.centered;
+            cube_sum.owned += probability * cube_best.owned;
+            cube_sum.unavailable += probability * cube_best.unavailable;
+        }
+        let old = current.values[index];
+        delta = delta
+            .max((sum.win - old.win).abs())
+            .max((sum.win_gammon - old.win_gammon).abs())
+            .max((sum.win_backgammon - old.win_backgammon).abs())
+            .max((sum.lose_gammon - old.lose_gammon).abs())
+            .max((sum.lose_backgammon - old.lose_backgammon).abs());
+        *slot = sum;
+        if let (Some(cubeful), Some(cubeful_out)) = (current.cubeful, cubeful_out.as_mut()) {
+            delta = delta.max(cube_sum.max_difference(&cubeful[index]));
+            cubeful_out[offset] = cube_sum;
+        }
+    }
+    delta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SolveOptions, SolvedTable};
+    use crate::bearoff::TwoSidedBearoff;
+    use crate::equity::{CubeDecision, CubeOwnership};
+    use crate::position::{Position, State};
+    use std::sync::OnceLock;
+
+    // One checker per side keeps the test fast, the solver doesn't care about the number.
+    fn table() -> &'static SolvedTable {
+        static TABLE: OnceLock<SolvedTable> = OnceLock::new();
+        TABLE.get_or_init(|| {
+            let options = SolveOptions {
+                tolerance: 1e-6,
+                max_iterations: 1000,
+                threads: 2,
+                cubeful: true,
+                jacoby: false,
+            };
+            SolvedTable::solve::<1>(&options).unwrap()
+        })
+    }
+
+    fn jacoby_table() -> &'static SolvedTable {
+        static TABLE: OnceLock<SolvedTable> = OnceLock::new();
+        TABLE.get_or_init(|| {
+            let options = SolveOptions {
+                cubeful: true,
+                jacoby: true,
+                ..SolveOptions::default()
+            };
+            SolvedTable::solve::<1>(&options).unwrap()
+        })
+    }
+
+    #[test]
+    fn certain_gammon() {
+        let position = Position::<1>::from_pairs(&[(1, 1)], &[(20, 1)]);
+        let p = table().lookup(&position).unwrap();
+        assert!((p.win - 1.0).abs() < 1e-5);
+        assert!((p.win_gammon - 1.0).abs() < 1e-5);
+        assert!(p.win_backgammon.abs() < 1e-5);
+    }
+
+    #[test]
+    fn last_roll_position() {
+        // Only 2-1 misses, then the opponent bears off.
+        let position = Position::<1>::from_pairs(&[(4, 1)], &[(24, 1)]);
+        let p = table().lookup(&position).unwrap();
+        assert!((p.win - 34.0 / 36.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn symmetric_position_favors_player_on_roll() {
+        let position = Position::<1>::from_pairs(&[(24, 1)], &[(1, 1)]);
+        let p = table().lookup(&position).unwrap();
+        assert!(p.win > 0.5 && p.win < 1.0);
+        assert!(p.win >= p.win_gammon && p.win_gammon >= p.win_backgammon);
+        assert_eq!(table().lookup(&position.flip()), Some(p));
+    }
+
+    #[test]
+    fn too_good_to_double_on_last_roll() {
+        // With one checker every game ends in a gammon, so playing on wins more than cashing.
+        let position = Position::<1>::from_pairs(&[(4, 1)], &[(24, 1)]);
+        let values = table().lookup_cubeful(&position).unwrap();
+        assert!((values.unavailable - 64.0 / 36.0).abs() < 1e-5);
+        assert_eq!(
+            values.decision(CubeOwnership::Centered),
+            CubeDecision::TooGoodToDouble
+        );
+        assert!((values.equity(CubeOwnership::Centered) - 64.0 / 36.0).abs() < 1e-5);
+        assert_eq!(
+            values.decision(CubeOwnership::Unavailable),
+            CubeDecision::NoDouble
+        );
+    }
+
+    #[test]
+    fn jacoby_makes_gammonish_positions_cash() {
+        let position = Position::<1>::from_pairs(&[(4, 1)], &[(24, 1)]);
+        let values = jacoby_table().lookup_cubeful(&position).unwrap();
+        assert!((values.centered - 32.0 / 36.0).abs() < 1e-5);
+        assert_eq!(
+            values.decision(CubeOwnership::Centered),
+            CubeDecision::DoublePass
+        );
+        assert!((values.equity(CubeOwnership::Centered) - 1.0).abs() < 1e-5);
+        assert!((values.equity(CubeOwnership::Unavailable) - 64.0 / 36.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn cube_decisions_are_consistent() {
+        let mut takes = 0;
+        for index in 0..Position::<1>::NUM_POSITIONS {
+            let position = Position::<1>::unrank(index).unwrap();
+            let values = table().lookup_cubeful(&position).unwrap();
+            // Owning the cube is never worse than sharing it, which is never worse than not having it.
+            assert!(values.owned >= values.centered - 1e-4);
+            assert!(values.centered >= values.unavailable - 1e-4);
+            if values.decision(CubeOwnership::Centered) == CubeDecision::DoubleTake {
+                takes += 1;
+                assert!(values.double_take() <= 1.0);
+            }
+        }
+        assert!(takes > 0);
+    }
+
+    #[test]
+    fn hypergammon2_races_match_the_bearoff_database() {
+        // Value iteration is exact after as many iterations as the longest game lasts. With one
+        // checker left per side in the home board, a player bears off in at most two rolls.
+        let options = SolveOptions {
+            max_iterations: 3,
+            ..SolveOptions::default()
+        };
+        let table = SolvedTable::solve::<2>(&options).unwrap();
+        let bearoff = TwoSidedBearoff::generate(2, false).unwrap();
+        let mut races = 0;
+        for x in 1..=6 {
+            for o in 19..=24 {
+                // The other checker of each side is already off.
+                let position = Position::<2>::from_pairs(&[(x, 1)], &[(o, 1)]);
+                let p = table.lookup(&position).unwrap();
+                let win = bearoff.win_probability(&position).unwrap();
+                assert!((p.win - win).abs() < 1e-5, "{x} {o}: {} {win}", p.win);
+                assert_eq!(p.win_gammon, 0.0);
+                races += 1;
+            }
+        }
+        assert_eq!(races, 36);
+
+        // Bearing off from the 6 point needs at least 6 pips, only 9 rolls fall short.
+        let position = Position::<2>::from_pairs(&[(6, 1)], &[(24, 1)]);
+        assert!((table.lookup(&position).unwrap().win - 27.0 / 36.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn lookup_needs_matching_checkers() {
+        let position = Position::<2>::unrank(0).unwrap();
+        assert_eq!(table().lookup(&position), None);
+    }
+
+    #[test]
+    fn file_roundtrip() {
+        for table in [table(), jacoby_table()] {
+            let mut bytes = Vec::new();
+            table.write_to(&mut bytes).unwrap();
+            let read = SolvedTable::read_from(&mut bytes.as_slice()).unwrap();
+            assert_eq!(&read, table);
+        }
+    }
+}
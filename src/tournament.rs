@@ -0,0 +1,599 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::duel::{duel, DuelError, DuelOptions, DuelPlayer, DuelReport, GameRecord, MatchRecord};
+use crate::engine_spec::{format_engine_spec, EngineSpec};
+use crate::position::GameResult;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TournamentError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("{engine}: {source}")]
+    Duel {
+        engine: String,
+        #[source]
+        source: DuelError,
+    },
+    #[error("engine '{0}' appears twice")]
+    DuplicateEngine(String),
+    #[error("a tournament needs at least two engines")]
+    TooFewEngines,
+    #[error("invalid results file: {0}")]
+    InvalidResults(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TournamentFormat {
+    /// Every engine plays every other engine.
+    RoundRobin,
+    /// The first engine plays all others.
+    Gauntlet,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TournamentOptions {
+    pub format: TournamentFormat,
+    /// Options of each pairing, [DuelOptions::seeds] is the number of seeds per pairing.
+    pub duel: DuelOptions,
+    pub threads: usize,
+    /// Finished seeds are appended to this file and skipped when the tournament is run again.
+    pub results: Option<PathBuf>,
+}
+
+impl Default for TournamentOptions {
+    fn default() -> Self {
+        Self {
+            format: TournamentFormat::RoundRobin,
+            duel: DuelOptions::default(),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            results: None,
+        }
+    }
+}
+
+/// Pairs of engine indices, the first engine of a pair is player `a` of the duel.
+pub fn pairings(engines: usize, format: TournamentFormat) -> Vec<(usize, usize)> {
+    match format {
+        TournamentFormat::RoundRobin => (0..engines)
+            .flat_map(|a| (a + 1..engines).map(move |b| (a, b)))
+            .collect(),
+        TournamentFormat::Gauntlet => (1..engines).map(|b| (0, b)).collect(),
+    }
+}
+
+/// Both games, or matches, of one seed of a pairing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedResult {
+    pub a: usize,
+    pub b: usize,
+    pub seed: u64,
+    pub report: DuelReport,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TournamentResults {
+    /// Engine labels from [format_engine_spec].
+    pub labels: Vec<String>,
+    pub results: Vec<SeedResult>,
+    pub options: TournamentOptions,
+}
+
+/// Plays a tournament between `specs`, each worker thread starts its own players with
+/// `make_player`.
+pub fn run_tournament<F>(
+    specs: &[EngineSpec],
+    options: &TournamentOptions,
+    make_player: F,
+) -> Result<TournamentResults, TournamentError>
+where
+    F: Fn(&EngineSpec) -> Result<Box<dyn DuelPlayer>, DuelError> + Sync,
+{
+    if specs.len() < 2 {
+        return Err(TournamentError::TooFewEngines);
+    }
+    let labels: Vec<String> = specs.iter().map(format_engine_spec).collect();
+    let mut seen = HashSet::new();
+    if let Some(label) = labels.iter().find(|label| !seen.insert(label.as_str())) {
+        return Err(TournamentError::DuplicateEngine(label.clone()));
+    }
+
+    let header = results_header(&options.duel);
+    let (mut results, complete) = match &options.results {
+        Some(path) if path.exists() => read_results(path, &header, &labels)?,
+        _ => (Vec::new(), 0),
+    };
+    let done: HashSet<(usize, usize, u64)> = results.iter().map(|r| (r.a, r.b, r.seed)).collect();
+    let seeds = (0..options.duel.seeds as u64).map(|i| options.duel.first_seed.wrapping_add(i));
+    let units: Vec<(usize, usize, u64)> = seeds
+        .flat_map(|seed| {
+            pairings(specs.len(), options.format)
+                .into_iter()
+                .map(move |(a, b)| (a, b, seed))
+        })
+        .filter(|unit| !done.contains(unit))
+        .collect();
+
+    let writer = match &options.results {
+        Some(path) => {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            // Drops the lines of a seed which was cut off, it is played again.
+            file.set_len(complete)?;
+            if complete == 0 {
+                writeln!(file, "{header}")?;
+            }
+            Some(file)
+        }
+        None => None,
+    };
+    let shared = Mutex::new((writer, Vec::new()));
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let first_error = Mutex::new(None);
+
+    std::thread::scope(|scope| {
+        for _ in 0..options.threads.max(1).min(units.len().max(1)) {
+            scope.spawn(|| {
+                let mut players: HashMap<usize, Box<dyn DuelPlayer>> = HashMap::new();
+                while !failed.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(&(a, b, seed)) = units.get(index) else {
+                        break;
+                    };
+                    let outcome = play_seed(
+                        specs,
+                        &labels,
+                        &mut players,
+                        &make_player,
+                        options,
+                        a,
+                        b,
+                        seed,
+                    )
+                    .and_then(|result| {
+                        let mut shared = shared.lock().expect("results lock");
+                        if let Some(file) = &mut shared.0 {
+                            write_seed(file, &labels, &result)?;
+                        }
+                        shared.1.push(result);
+                        Ok(())
+                    });
+                    if let Err(err) = outcome {
+                        failed.store(true, Ordering::Relaxed);
+                        first_error.lock().expect("error lock").get_or_insert(err);
+                    }
+                }
+            });
+        }
+    });
+
+    if let Some(err) = first_error.into_inner().expect("error lock") {
+        return Err(err);
+    }
+    results.extend(shared.into_inner().expect("results lock").1);
+    results.sort_by_key(|r| (r.seed, r.a, r.b));
+    Ok(TournamentResults {
+        labels,
+        results,
+        options: options.clone(),
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn play_seed<F>(
+    specs: &[EngineSpec],
+    labels: &[String],
+    players: &mut HashMap<usize, Box<dyn DuelPlayer>>,
+    make_player: &F,
+    options: &TournamentOptions,
+    a: usize,
+    b: usize,
+    seed: u64,
+) -> Result<SeedResult, TournamentError>
+where
+    F: Fn(&EngineSpec) -> Result<Box<dyn DuelPlayer>, DuelError>,
+{
+    for index in [a, b] {
+        if let Entry::Vacant(entry) = players.entry(index) {
+            let player = make_player(&specs[index]).map_err(|source| TournamentError::Duel {
+                engine: labels[index].clone(),
+                source,
+            })?;
+            entry.insert(player);
+        }
+    }
+    let mut player_a = players.remove(&a).expect("player a was started");
+    let mut player_b = players.remove(&b).expect("player b was started");
+    let duel_options = DuelOptions {
+        seeds: 1,
+        first_seed: seed,
+        ..options.duel.clone()
+    };
+    let report = duel(player_a.as_mut(), player_b.as_mut(), &duel_options);
+    players.insert(a, player_a);
+    players.insert(b, player_b);
+    let report = report.map_err(|source| TournamentError::Duel {
+        engine: format!("{} vs {}", labels[a], labels[b]),
+        source,
+    })?;
+    Ok(SeedResult { a, b, seed, report })
+}
+
+fn results_header(options: &DuelOptions) -> String {
+    format!(
+        "# bkgm tournament variant={} match_length={} cubeful={} max_plies={}",
+        options.variant,
+        options.match_length.unwrap_or(0),
+        options.cubeful,
+        options.max_plies
+    )
+}
+
+/// Writes the games and matches of a seed followed by a `done` line, which marks it as
+/// complete.
+fn write_seed(file: &mut File, labels: &[String], result: &SeedResult) -> io::Result<()> {
+    let (a, b) = (&labels[result.a], &labels[result.b]);
+    let mut text = String::new();
+    for game in &result.report.games {
+        text.push_str(&format!(
+            "game\t{a}\t{b}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            game.seed,
+            game.a_first as u8,
+            game.match_index.map_or(-1, |i| i as i64),
+            game.points,
+            game.result.value(),
+            game.plies
+        ));
+    }
+    for m in &result.report.matches {
+        text.push_str(&format!(
+            "match\t{a}\t{b}\t{}\t{}\t{}\n",
+            m.seed, m.a_first as u8, m.a_won as u8
+        ));
+    }
+    text.push_str(&format!("done\t{a}\t{b}\t{}\n", result.seed));
+    file.write_all(text.as_bytes())?;
+    file.flush()
+}
+
+/// Reads the complete seeds of a results file and the length of the file up to its last
+/// complete seed. Seeds of engines which are not in `labels` are skipped.
+fn read_results(
+    path: &Path,
+    header: &str,
+    labels: &[String],
+) -> Result<(Vec<SeedResult>, u64), TournamentError> {
+    let index: HashMap<&str, usize> = labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| (label.as_str(), i))
+        .collect();
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    match line.strip_suffix('\n') {
+        Some(first) if first == header => {}
+        // Empty, or cut off while writing the header.
+        None => return Ok((Vec::new(), 0)),
+        Some(first) => {
+            return Err(TournamentError::InvalidResults(format!(
+                "written with other options: '{first}'"
+            )))
+        }
+    }
+    let mut read = line.len() as u64;
+    let mut complete = read;
+    // Seeds are written whole, so lines of another seed mean that the open one was cut off.
+    let mut open: Option<((usize, usize, u64), DuelReport)> = None;
+    let mut results = Vec::new();
+    loop {
+        line.clear();
+        let length = reader.read_line(&mut line)?;
+        // A last line without newline was cut off while writing.
+        let Some(text) = line.strip_suffix('\n') else {
+            break;
+        };
+        read += length as u64;
+        if text.is_empty() {
+            continue;
+        }
+        let invalid = || TournamentError::InvalidResults(text.to_string());
+        let fields: Vec<&str> = text.split('\t').collect();
+        if fields.len() < 4 {
+            return Err(invalid());
+        }
+        let seed: u64 = fields[3].parse().map_err(|_| invalid())?;
+        if fields[0] == "done" {
+            complete = read;
+        }
+        let (Some(&a), Some(&b)) = (index.get(fields[1]), index.get(fields[2])) else {
+            continue;
+        };
+        let key = (a, b, seed);
+        if open.as_ref().is_some_and(|(open_key, _)| *open_key != key) {
+            open = None;
+        }
+        let report = &mut open.get_or_insert_with(|| (key, DuelReport::default())).1;
+        match (fields[0], fields.len()) {
+            ("game", 9) => {
+                let match_index: i64 = fields[5].parse().map_err(|_| invalid())?;
+                let value: f32 = fields[7].parse().map_err(|_| invalid())?;
+                report.games.push(GameRecord {
+                    seed,
+                    a_first: fields[4] == "1",
+                    match_index: usize::try_from(match_index).ok(),
+                    points: fields[6].parse().map_err(|_| invalid())?,
+                    result: GameResult::from_value(value).ok_or_else(invalid)?,
+                    plies: fields[8].parse().map_err(|_| invalid())?,
+                });
+            }
+            ("match", 6) => report.matches.push(MatchRecord {
+                seed,
+                a_first: fields[4] == "1",
+                a_won: fields[5] == "1",
+            }),
+            ("done", 4) => {
+                let (_, report) = open.take().expect("the seed is open");
+                results.push(SeedResult { a, b, seed, report });
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok((results, complete))
+}
+
+/// Score of a pairing: games or matches won by each side.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Score {
+    wins: f64,
+    losses: f64,
+}
+
+impl TournamentResults {
+    /// Duel report of engine `a` against engine `b`, in either seat order.
+    pub fn report(&self, a: usize, b: usize) -> DuelReport {
+        let mut report = DuelReport::default();
+        for result in &self.results {
+            let flip = if (result.a, result.b) == (a, b) {
+                false
+            } else if (result.a, result.b) == (b, a) {
+                true
+            } else {
+                continue;
+            };
+            let offset = report.matches.len();
+            report
+                .games
+                .extend(result.report.games.iter().map(|game| GameRecord {
+                    a_first: game.a_first != flip,
+                    match_index: game.match_index.map(|i| i + offset),
+                    points: if flip { -game.points } else { game.points },
+                    result: if flip {
+                        game.result.reverse()
+                    } else {
+                        game.result.clone()
+                    },
+                    ..game.clone()
+                }));
+            report
+                .matches
+                .extend(result.report.matches.iter().map(|m| MatchRecord {
+                    a_first: m.a_first != flip,
+                    a_won: m.a_won != flip,
+                    ..*m
+                }));
+        }
+        report
+    }
+
+    fn scores(&self) -> HashMap<(usize, usize), Score> {
+        let mut scores: HashMap<(usize, usize), Score> = HashMap::new();
+        for result in &self.results {
+            let score = scores.entry((result.a, result.b)).or_default();
+            if result.report.matches.is_empty() {
+                for game in &result.report.games {
+                    if game.result.is_win() {
+                        score.wins += 1.0;
+                    } else {
+                        score.losses += 1.0;
+                    }
+                }
+            } else {
+                for m in &result.report.matches {
+                    if m.a_won {
+                        score.wins += 1.0;
+                    } else {
+                        score.losses += 1.0;
+                    }
+                }
+            }
+        }
+        scores
+    }
+
+    /// Elo ratings fitted to the games (or matches) won, averaging 1500.
+    pub fn elo(&self) -> Vec<f64> {
+        fit_ratings(self.labels.len(), &self.scores(), 400.0)
+    }
+
+    /// FIBS-style ratings, where the winning chance of a match of length `n` is
+    /// `1 / (1 + 10^(-D * sqrt(n) / 2000))` with the match length of the tournament. Money
+    /// games count as 1-point matches.
+    pub fn fibs(&self) -> Vec<f64> {
+        let n = self.options.duel.match_length.unwrap_or(1).max(1) as f64;
+        fit_ratings(self.labels.len(), &self.scores(), 2000.0 / n.sqrt())
+    }
+
+    /// Points per game (money) or match win rate of each row engine against each column.
+    pub fn cross_table(&self) -> String {
+        let width = self
+            .labels
+            .iter()
+            .map(String::len)
+            .max()
+            .unwrap_or(0)
+            .max(6);
+        let mut out = format!("{:width$}", "");
+        for column in 0..self.labels.len() {
+            out.push_str(&format!(" {:>8}", column + 1));
+        }
+        out.push('\n');
+        for (row, label) in self.labels.iter().enumerate() {
+            out.push_str(&format!("{label:width$}"));
+            for column in 0..self.labels.len() {
+                let report = self.report(row, column);
+                let stats = report.stats();
+                let cell = if row == column || stats.games == 0 {
+                    "-".to_string()
+                } else if stats.matches > 0 {
+                    format!("{:.1}%", 100.0 * stats.match_win_rate())
+                } else {
+                    format!("{:+.3}", stats.points_per_game())
+                };
+                out.push_str(&format!(" {cell:>8}"));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Bradley-Terry fit with ratings on a logarithmic `scale`, like 400 for Elo.
+///
+/// Each pairing also gets half a win and half a loss, so unbeaten engines get finite ratings.
+fn fit_ratings(engines: usize, scores: &HashMap<(usize, usize), Score>, scale: f64) -> Vec<f64> {
+    let mut wins = vec![0.0; engines];
+    let mut games: HashMap<(usize, usize), f64> = HashMap::new();
+    for (&(a, b), score) in scores {
+        wins[a] += score.wins + 0.5;
+        wins[b] += score.losses + 0.5;
+        let (low, high) = (a.min(b), a.max(b));
+        *games.entry((low, high)).or_default() += score.wins + score.losses + 1.0;
+    }
+    let mut strength = vec![1.0f64; engines];
+    for _ in 0..10_000 {
+        let mut denominators = vec![0.0; engines];
+        for (&(a, b), &n) in &games {
+            let d = n / (strength[a] + strength[b]);
+            denominators[a] += d;
+            denominators[b] += d;
+        }
+        let mut change: f64 = 0.0;
+        for i in 0..engines {
+            if denominators[i] > 0.0 {
+                let next = wins[i] / denominators[i];
+                change = change.max((next / strength[i]).ln().abs());
+                strength[i] = next;
+            }
+        }
+        if change < 1e-10 {
+            break;
+        }
+    }
+    let ratings: Vec<f64> = strength.iter().map(|s| scale * s.log10()).collect();
+    let mean = ratings.iter().sum::<f64>() / engines.max(1) as f64;
+    ratings.iter().map(|r| r - mean + 1500.0).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use super::{
+        pairings, read_results, results_header, run_tournament, TournamentFormat, TournamentOptions,
+    };
+    use crate::cube::CubeAction;
+    use crate::duel::{DuelOptions, DuelPlayer};
+    use crate::ubgi::FirstMoveEngine;
+    use crate::{parse_engine_spec, Variant};
+
+    #[test]
+    fn generates_pairings() {
+        assert_eq!(
+            pairings(3, TournamentFormat::RoundRobin),
+            vec![(0, 1), (0, 2), (1, 2)]
+        );
+        assert_eq!(
+            pairings(3, TournamentFormat::Gauntlet),
+            vec![(0, 1), (0, 2)]
+        );
+    }
+
+    #[test]
+    fn resumes_from_results_file() {
+        let specs: Vec<_> = ["doubler", "taker", "passer"]
+            .iter()
+            .map(|s| parse_engine_spec(s).unwrap())
+            .collect();
+        let make_player = |spec: &crate::EngineSpec| -> Result<Box<dyn DuelPlayer>, _> {
+            let response = if spec.alias == "passer" {
+                CubeAction::Pass
+            } else {
+                CubeAction::Take
+            };
+            Ok(Box::new(FirstMoveEngine::new(
+                spec.alias == "doubler",
+                response,
+            )))
+        };
+        let path = std::env::temp_dir().join(format!("bkgm-tournament-{}.tsv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut options = TournamentOptions {
+            duel: DuelOptions {
+                variant: Variant::Hypergammon,
+                seeds: 2,
+                ..Default::default()
+            },
+            threads: 2,
+            results: Some(path.clone()),
+            ..Default::default()
+        };
+        let first = run_tournament(&specs, &options, make_player).unwrap();
+        assert_eq!(first.results.len(), 6);
+
+        // A seed cut off after its first game, played again behind it by an older version,
+        // and a seed cut off in the middle of a line.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        let game = "game\tdoubler\ttaker\t2\t1\t-1\t2\t1\t9\n";
+        write!(file, "{game}{game}{game}done\tdoubler\ttaker\t2\n{game}gam").unwrap();
+        drop(file);
+        let (read, complete) =
+            read_results(&path, &results_header(&options.duel), &first.labels).unwrap();
+        assert_eq!(read.len(), 7);
+        assert_eq!(read[6].report.games.len(), 3);
+        options.duel.seeds = 2;
+        run_tournament(&specs, &options, make_player).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), complete);
+
+        options.duel.seeds = 3;
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let resumed = run_tournament(&specs, &options, |spec| {
+            calls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            make_player(spec)
+        })
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resumed.results.len(), 9);
+        assert_eq!(resumed.results[..6], first.results[..]);
+
+        // The doubler cashes every game against the passer.
+        let report = resumed.report(2, 0);
+        assert_eq!(report.stats().points_per_game(), -1.0);
+        let elo = resumed.elo();
+        assert!(elo[0] > elo[2]);
+        assert!((elo.iter().sum::<f64>() / 3.0 - 1500.0).abs() < 1e-6);
+        // Money games are 1-point matches, on a scale of 2000 instead of 400.
+        for (fibs, elo) in resumed.fibs().iter().zip(&elo) {
+            assert!((fibs - 1500.0 - 5.0 * (elo - 1500.0)).abs() < 1e-6);
+        }
+        let table = resumed.cross_table();
+        assert!(table.lines().nth(3).unwrap().starts_with("passer"));
+        assert!(table.contains("-1.000"));
+    }
+}
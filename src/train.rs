@@ -0,0 +1,408 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::cube::CubeState;
+use crate::dice_gen::FastrandDice;
+use crate::duel::{duel, DuelError, DuelOptions, DuelPlayer, DuelStats};
+use crate::equity::Probabilities;
+use crate::evaluator::{evaluate_or_result, Evaluator, EvaluatorEngine};
+use crate::game::{Game, GameError};
+use crate::mlp::{Mlp, MlpError, MLP_OUTPUTS};
+use crate::position::GameState;
+use crate::solver::SolvedTable;
+use crate::variants::{Variant, VariantPosition};
+
+const MAGIC: [u8; 4] = *b"BKTD";
+const VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum TrainError {
+    #[error("checkpoint i/o failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid checkpoint header")]
+    InvalidHeader,
+    #[error("unsupported checkpoint version {0}")]
+    UnsupportedVersion(u32),
+    #[error(transparent)]
+    Mlp(#[from] MlpError),
+    #[error(transparent)]
+    Game(#[from] GameError),
+    #[error(transparent)]
+    Duel(#[from] DuelError),
+    #[error("network has {0} outputs, training needs {MLP_OUTPUTS}")]
+    Outputs(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrainOptions {
+    pub variant: Variant,
+    /// Learning rate.
+    pub alpha: f32,
+    /// Decay of the eligibility traces.
+    pub lambda: f32,
+    /// Dice seed of the first game, game `i` uses `seed + i`.
+    pub seed: u64,
+    pub max_plies: usize,
+}
+
+impl Default for TrainOptions {
+    fn default() -> Self {
+        Self {
+            variant: Variant::Hypergammon,
+            alpha: 0.1,
+            lambda: 0.7,
+            seed: 0,
+            max_plies: 10_000,
+        }
+    }
+}
+
+/// TD(λ) self-play training of an [Mlp] with [MLP_OUTPUTS] outputs.
+///
+/// Both sides play the move with the best 0-ply equity. The values are learnt from the view of
+/// the player with turn `true`, so the outputs of positions with the other player on roll are
+/// flipped, and the traces are kept per output.
+pub struct TdTrainer {
+    mlp: Mlp,
+    options: TrainOptions,
+    games: u64,
+    /// Per output, the eligibility trace of each parameter, layer by layer weights then biases.
+    traces: Vec<Vec<f32>>,
+}
+
+/// Index of the flipped output and whether it is `1 - output`, in the order of [Probabilities].
+const FLIPPED: [(usize, bool); MLP_OUTPUTS] =
+    [(0, true), (3, false), (4, false), (1, false), (2, false)];
+
+impl TdTrainer {
+    pub fn new(mlp: Mlp, options: TrainOptions) -> Result<Self, TrainError> {
+        if mlp.outputs() != MLP_OUTPUTS {
+            return Err(TrainError::Outputs(mlp.outputs()));
+        }
+        let parameters = parameters(&mlp);
+        Ok(Self {
+            mlp,
+            options,
+            games: 0,
+            traces: vec![vec![0.0; parameters]; MLP_OUTPUTS],
+        })
+    }
+
+    pub fn mlp(&self) -> &Mlp {
+        &self.mlp
+    }
+
+    pub fn options(&self) -> &TrainOptions {
+        &self.options
+    }
+
+    /// Games trained so far, including those before the checkpoint.
+    pub fn games(&self) -> u64 {
+        self.games
+    }
+
+    /// Plays and learns from one game, returning its number of plies.
+    pub fn train_game(&mut self) -> Result<usize, TrainError> {
+        let mut dice = FastrandDice::with_seed(self.options.seed.wrapping_add(self.games));
+        let mut game = Game::with_cube(self.options.variant, CubeState::unavailable());
+        for trace in &mut self.traces {
+            trace.fill(0.0);
+        }
+        let mut gradients = vec![vec![0.0; self.traces[0].len()]; MLP_OUTPUTS];
+        let mut previous: Option<[f32; MLP_OUTPUTS]> = None;
+        let plies =
+            game.play_episode(&mut dice, self.options.max_plies, |position, _, legal| {
+                let values = self.fixed_values(position, &mut gradients);
+                if let Some(previous) = previous {
+                    self.update(&previous, &values);
+                }
+                for (trace, gradient) in self.traces.iter_mut().zip(&gradients) {
+                    for (e, g) in trace.iter_mut().zip(gradient) {
+                        *e = self.options.lambda * *e + g;
+                    }
+                }
+                previous = Some(values);
+                best_move(&self.mlp, legal)
+            })?;
+        if let (GameState::GameOver(result), Some(previous)) = (game.game_state(), previous) {
+            let mut outcome = Probabilities::from_result(&result);
+            if !game.position().turn() {
+                outcome = outcome.flip();
+            }
+            self.update(&previous, &outcome.to_array());
+        }
+        self.games += 1;
+        Ok(plies)
+    }
+
+    /// Outputs for `position` from the view of the player with turn `true`, and their gradients.
+    fn fixed_values(
+        &self,
+        position: VariantPosition,
+        gradients: &mut [Vec<f32>],
+    ) -> [f32; MLP_OUTPUTS] {
+        let mut inputs = vec![0.0; self.mlp.inputs()];
+        self.mlp.encoding().encode_variant(position, &mut inputs);
+        let activations = forward(&self.mlp, &inputs);
+        let outputs = &activations[activations.len() - 1];
+        let mut values = [0.0; MLP_OUTPUTS];
+        for (k, gradient) in gradients.iter_mut().enumerate() {
+            let (j, negate) = if position.turn() {
+                (k, false)
+            } else {
+                FLIPPED[k]
+            };
+            backward(&self.mlp, &activations, j, gradient);
+            values[k] = outputs[j];
+            if negate {
+                values[k] = 1.0 - values[k];
+                gradient.iter_mut().for_each(|g| *g = -*g);
+            }
+        }
+        values
+    }
+
+    /// Moves the parameters by `alpha` times the TD errors along the traces.
+    fn update(&mut self, previous: &[f32; MLP_OUTPUTS], target: &[f32; MLP_OUTPUTS]) {
+        let alpha = self.options.alpha;
+        let errors: Vec<f32> = target.iter().zip(previous).map(|(t, p)| t - p).collect();
+        let mut offset = 0;
+        for layer in &mut self.mlp.layers {
+            for parameter in layer.weights.iter_mut().chain(layer.biases.iter_mut()) {
+                let step: f32 = errors
+                    .iter()
+                    .zip(&self.traces)
+                    .map(|(error, trace)| error * trace[offset])
+                    .sum();
+                *parameter += alpha * step;
+                offset += 1;
+            }
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), TrainError> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.games.to_le_bytes())?;
+        self.mlp.write_to(writer)?;
+        Ok(())
+    }
+
+    /// Resumes training from a checkpoint written by [Self::write_to].
+    pub fn read_from<R: Read>(reader: &mut R, options: TrainOptions) -> Result<Self, TrainError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(TrainError::InvalidHeader);
+        }
+        let mut version = [0u8; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(TrainError::UnsupportedVersion(version));
+        }
+        let mut games = [0u8; 8];
+        reader.read_exact(&mut games)?;
+        let mut trainer = Self::new(Mlp::read_from(reader)?, options)?;
+        trainer.games = u64::from_le_bytes(games);
+        Ok(trainer)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), TrainError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>, options: TrainOptions) -> Result<Self, TrainError> {
+        Self::read_from(&mut BufReader::new(File::open(path)?), options)
+    }
+
+    /// Cubeless duel of the network at 0-ply against `baseline`, seeds from `first_seed`.
+    pub fn benchmark(
+        &self,
+        baseline: &mut dyn DuelPlayer,
+        seeds: usize,
+        first_seed: u64,
+    ) -> Result<DuelStats, TrainError> {
+        let mut player = EvaluatorEngine::new("bkgm-mlp", self.mlp.clone());
+        let options = DuelOptions {
+            variant: self.options.variant,
+            seeds,
+            first_seed,
+            cubeful: false,
+            max_plies: self.options.max_plies,
+            ..Default::default()
+        };
+        Ok(duel(&mut player, baseline, &options)?.stats())
+    }
+}
+
+/// Mean absolute difference of the cubeless equities of `evaluator` and the exact `table`, over
+/// the positions of `games` games played by `evaluator` with dice seeds from `seed`.
+pub fn equity_error<E: Evaluator>(
+    evaluator: &E,
+    table: &SolvedTable,
+    variant: Variant,
+    games: usize,
+    seed: u64,
+) -> Result<f32, TrainError> {
+    let mut total = 0.0;
+    let mut count = 0;
+    for i in 0..games as u64 {
+        let mut dice = FastrandDice::with_seed(seed.wrapping_add(i));
+        let mut game = Game::with_cube(variant, CubeState::unavailable());
+        game.play_episode(&mut dice, 10_000, |position, _, legal| {
+            if let Some(exact) = table.lookup_variant(position) {
+                total += (evaluator.evaluate(position).equity() - exact.equity()).abs();
+                count += 1;
+            }
+            best_move(evaluator, legal)
+        })?;
+    }
+    Ok(if count == 0 {
+        0.0
+    } else {
+        total / count as f32
+    })
+}
+
+/// Index of the legal position with the best 0-ply equity for the player who moved.
+fn best_move<E: Evaluator + ?Sized>(evaluator: &E, legal: &[VariantPosition]) -> usize {
+    legal
+        .iter()
+        .map(|&child| evaluate_or_result(evaluator, child).flip().equity())
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(0, |(i, _)| i)
+}
+
+fn parameters(mlp: &Mlp) -> usize {
+    mlp.layers
+        .iter()
+        .map(|layer| layer.weights.len() + layer.biases.len())
+        .sum()
+}
+
+/// Activations of every layer, starting with the inputs.
+fn forward(mlp: &Mlp, inputs: &[f32]) -> Vec<Vec<f32>> {
+    let mut activations = Vec::with_capacity(mlp.layers.len() + 1);
+    activations.push(inputs.to_vec());
+    for layer in &mlp.layers {
+        let mut outputs = vec![0.0; layer.outputs];
+        layer.forward(&activations[activations.len() - 1], &mut outputs);
+        activations.push(outputs);
+    }
+    activations
+}
+
+/// Gradient of output `k` by all parameters, in the order of the traces.
+fn backward(mlp: &Mlp, activations: &[Vec<f32>], k: usize, gradient: &mut [f32]) {
+    let last = mlp.layers.len() - 1;
+    let mut deltas = vec![0.0; mlp.layers[last].outputs];
+    deltas[k] = mlp.layers[last]
+        .activation
+        .derivative(activations[last + 1][k]);
+    let mut end = gradient.len();
+    for (l, layer) in mlp.layers.iter().enumerate().rev() {
+        let start = end - layer.weights.len() - layer.biases.len();
+        let (weights, biases) = gradient[start..end].split_at_mut(layer.weights.len());
+        let inputs = &activations[l];
+        for ((row, delta), bias) in weights
+            .chunks_exact_mut(layer.inputs)
+            .zip(&deltas)
+            .zip(biases.iter_mut())
+        {
+            for (g, x) in row.iter_mut().zip(inputs) {
+                *g = delta * x;
+            }
+            *bias = *delta;
+        }
+        if l > 0 {
+            let below = mlp.layers[l - 1].activation;
+            let mut next = vec![0.0; layer.inputs];
+            for (row, delta) in layer.weights.chunks_exact(layer.inputs).zip(&deltas) {
+                for (n, w) in next.iter_mut().zip(row) {
+                    *n += delta * w;
+                }
+            }
+            for (n, y) in next.iter_mut().zip(inputs) {
+                *n *= below.derivative(*y);
+            }
+            deltas = next;
+        }
+        end = start;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{backward, forward, parameters, TdTrainer, TrainOptions};
+    use crate::evaluator::RandomPlayer;
+    use crate::features::Encoding;
+    use crate::mlp::{Activation, Mlp};
+
+    #[test]
+    fn gradients_match_finite_differences() {
+        let mlp = Mlp::random(Encoding::TdGammon, &[6], Activation::Sigmoid, 3);
+        let mut inputs = vec![0.0; mlp.inputs()];
+        for (i, x) in inputs.iter_mut().enumerate() {
+            *x = (i % 5) as f32 / 4.0;
+        }
+        let mut gradient = vec![0.0; parameters(&mlp)];
+        backward(&mlp, &forward(&mlp, &inputs), 1, &mut gradient);
+        // The first weight, a weight and a bias of the output layer.
+        let output_layer = parameters(&mlp) - 6 * 5 - 5;
+        for index in [1, output_layer + 7, parameters(&mlp) - 4] {
+            let mut shifted = mlp.clone();
+            let eps = 1e-2;
+            let mut offset = index;
+            for layer in &mut shifted.layers {
+                let len = layer.weights.len() + layer.biases.len();
+                if offset < len {
+                    if offset < layer.weights.len() {
+                        layer.weights[offset] += eps;
+                    } else {
+                        layer.biases[offset - layer.weights.len()] += eps;
+                    }
+                    break;
+                }
+                offset -= len;
+            }
+            let before = forward(&mlp, &inputs)[2][1];
+            let after = forward(&shifted, &inputs)[2][1];
+            let numeric = (after - before) / eps;
+            assert!(
+                (numeric - gradient[index]).abs() < 1e-3,
+                "{index}: {numeric} {}",
+                gradient[index]
+            );
+        }
+    }
+
+    #[test]
+    fn training_is_reproducible_and_resumable() {
+        let options = TrainOptions::default();
+        let mlp = Mlp::random(Encoding::TdGammon, &[8], Activation::Sigmoid, 1);
+        let mut a = TdTrainer::new(mlp.clone(), options.clone()).unwrap();
+        for _ in 0..3 {
+            a.train_game().unwrap();
+        }
+        assert_ne!(a.mlp(), &mlp);
+
+        let mut bytes = Vec::new();
+        let mut b = TdTrainer::new(mlp, options.clone()).unwrap();
+        b.train_game().unwrap();
+        b.write_to(&mut bytes).unwrap();
+        let mut b = TdTrainer::read_from(&mut bytes.as_slice(), options).unwrap();
+        assert_eq!(b.games(), 1);
+        b.train_game().unwrap();
+        b.train_game().unwrap();
+        assert_eq!(a.mlp(), b.mlp());
+
+        let stats = a.benchmark(&mut RandomPlayer::with_seed(0), 2, 0).unwrap();
+        assert_eq!(stats.games, 4);
+    }
+}
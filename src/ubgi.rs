@@ -1,9 +1,12 @@
 use crate::codecs::move_text::{self, MoveStep};
+use crate::cube::CubeAction;
 use crate::dice::Dice;
 use crate::Game;
 
+mod client;
 mod runtime;
-pub use runtime::run_ubgi_stdio;
+pub use client::{UbgiClient, UbgiClientError, UbgiClientResult, DEFAULT_TIMEOUT};
+pub use runtime::{run_ubgi, run_ubgi_stdio, run_ubgi_tcp, serve_ubgi_tcp};
 
 pub enum OptionSpec {
     Integer {
@@ -46,6 +49,16 @@ pub enum UbgiError {
     BadState(String),
 }
 
+/// Match score of a position, from the player on roll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MatchScore {
+    pub length: u32,
+    pub score: u32,
+    pub opponent_score: u32,
+    /// This is the Crawford game.
+    pub crawford: bool,
+}
+
 pub type UbgiResult<T> = Result<T, UbgiError>;
 pub type UbgiMove = Vec<MoveStep>;
 
@@ -55,6 +68,30 @@ pub fn parse_ubgi_move(text: &str) -> UbgiResult<UbgiMove> {
     move_text::parse_move_steps(&normalized).ok_or_else(|| UbgiError::bad_value("move.invalid"))
 }
 
+/// Wire name of a cube decision as sent in `bestcube`, `None` is no double.
+pub fn format_cube_decision(action: Option<CubeAction>) -> &'static str {
+    match action {
+        None => "nodouble",
+        Some(CubeAction::Double) => "double",
+        Some(CubeAction::Take) => "take",
+        Some(CubeAction::Pass) => "pass",
+        Some(CubeAction::Beaver) => "beaver",
+        Some(CubeAction::Raccoon) => "raccoon",
+    }
+}
+
+pub fn parse_cube_decision(text: &str) -> UbgiResult<Option<CubeAction>> {
+    match text.trim() {
+        "nodouble" => Ok(None),
+        "double" => Ok(Some(CubeAction::Double)),
+        "take" => Ok(Some(CubeAction::Take)),
+        "pass" => Ok(Some(CubeAction::Pass)),
+        "beaver" => Ok(Some(CubeAction::Beaver)),
+        "raccoon" => Ok(Some(CubeAction::Raccoon)),
+        _ => Err(UbgiError::bad_value("cube.invalid")),
+    }
+}
+
 impl UbgiError {
     pub fn unsupported(msg: impl Into<String>) -> Self {
         Self::Unsupported(msg.into())
@@ -309,6 +346,39 @@ pub fn parse_key_line(line: &str) -> Option<KeyLineSpec> {
     })
 }
 
+impl KeyLineSpec {
+    /// Checks a value against the advertised type and domain. Unknown types accept anything.
+    pub fn check_value(&self, raw: &str) -> Result<(), String> {
+        match self.key_type.as_str() {
+            "int" => {
+                let v: i64 = raw
+                    .parse()
+                    .map_err(|_| format!("expected int, got '{raw}'"))?;
+                let range = self.domain.as_deref().and_then(|d| d.split_once(".."));
+                if let Some((min, max)) = range {
+                    let in_range = min.parse::<i64>().map_or(true, |min| v >= min)
+                        && max.parse::<i64>().map_or(true, |max| v <= max);
+                    if !in_range {
+                        return Err(format!("must be in range {min}..{max}"));
+                    }
+                }
+                Ok(())
+            }
+            "bool" => match raw {
+                "true" | "false" => Ok(()),
+                _ => Err(format!("expected bool true|false, got '{raw}'")),
+            },
+            "enum" => match &self.domain {
+                Some(choices) if !choices.split('|').any(|c| c == raw) => {
+                    Err(format!("expected one of [{choices}]"))
+                }
+                _ => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
 fn looks_like_int_domain(token: &str) -> bool {
     token.contains("..")
 }
@@ -328,12 +398,85 @@ pub trait UbgiEngine {
     fn set(&mut self, _key: &str, _value: &OptionValue) -> UbgiResult<()> {
         Err(UbgiError::unsupported("key"))
     }
+    /// Called when a position sets the match score, `None` is money play.
+    fn set_match_score(&mut self, _score: Option<MatchScore>) -> UbgiResult<()> {
+        Ok(())
+    }
     fn choose_move(&mut self, game: &Game, dice: Dice) -> UbgiResult<UbgiMove>;
+    /// Whether the player on roll doubles before rolling. The default never doubles.
+    fn offer_double(&mut self, _game: &Game) -> UbgiResult<bool> {
+        Ok(false)
+    }
+    /// Answer to a double offered by the player on roll: [CubeAction::Take],
+    /// [CubeAction::Beaver] or [CubeAction::Pass]. The default always takes.
+    fn respond_to_double(&mut self, _game: &Game) -> UbgiResult<CubeAction> {
+        Ok(CubeAction::Take)
+    }
+}
+
+/// Test engine which plays the first legal move and gives fixed cube answers.
+#[cfg(test)]
+pub(crate) struct FirstMoveEngine {
+    pub double: bool,
+    pub response: CubeAction,
+    /// The last score set by the session.
+    pub score: Option<MatchScore>,
+    /// Dice of every move asked for, in order.
+    pub rolls: Vec<Dice>,
+}
+
+#[cfg(test)]
+impl FirstMoveEngine {
+    pub fn new(double: bool, response: CubeAction) -> Self {
+        Self {
+            double,
+            response,
+            score: None,
+            rolls: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl UbgiEngine for FirstMoveEngine {
+    fn id_name(&self) -> &'static str {
+        "first-move"
+    }
+
+    fn id_version(&self) -> &'static str {
+        "0"
+    }
+
+    fn set_match_score(&mut self, score: Option<MatchScore>) -> UbgiResult<()> {
+        self.score = score;
+        Ok(())
+    }
+
+    fn choose_move(&mut self, game: &Game, dice: Dice) -> UbgiResult<UbgiMove> {
+        self.rolls.push(dice);
+        let legal = move_text::legal_steps(game.position(), dice).map_err(|err| err.to_string())?;
+        Ok(legal
+            .into_iter()
+            .next()
+            .map(|(steps, _)| steps)
+            .unwrap_or_default())
+    }
+
+    fn offer_double(&mut self, _game: &Game) -> UbgiResult<bool> {
+        Ok(self.double)
+    }
+
+    fn respond_to_double(&mut self, _game: &Game) -> UbgiResult<CubeAction> {
+        Ok(self.response)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{parse_key_line, OptionSpec, OptionValue};
+    use super::{
+        format_cube_decision, parse_cube_decision, parse_key_line, OptionSpec, OptionValue,
+    };
+    use crate::cube::CubeAction;
 
     #[test]
     fn emits_minor_marker() {
@@ -393,4 +536,25 @@ mod tests {
         let value = spec.parse_value("true").expect("expected bool true");
         assert_eq!(value, OptionValue::Bool(true));
     }
+
+    #[test]
+    fn checks_values_against_key_lines() {
+        let ply = parse_key_line("key engine.ply int 1..4 2").unwrap();
+        assert!(ply.check_value("3").is_ok());
+        assert!(ply.check_value("7").unwrap_err().contains("1..4"));
+        let mode = parse_key_line("key engine.mode enum fast|slow fast").unwrap();
+        assert!(mode.check_value("slow").is_ok());
+        assert!(mode.check_value("medium").is_err());
+        let debug = parse_key_line("key engine.debug bool false").unwrap();
+        assert!(debug.check_value("yes").is_err());
+    }
+
+    #[test]
+    fn cube_decisions_roundtrip() {
+        for action in [None, Some(CubeAction::Double), Some(CubeAction::Beaver)] {
+            let text = format_cube_decision(action);
+            assert_eq!(parse_cube_decision(text).unwrap(), action);
+        }
+        assert!(parse_cube_decision("redouble").is_err());
+    }
 }
@@ -0,0 +1,344 @@
+use std::ffi::OsStr;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::codecs::gnuid;
+use crate::cube::CubeAction;
+use crate::dice::Dice;
+use crate::engine_spec::EngineSpec;
+use crate::variants::{Variant, VariantPosition};
+
+use super::{parse_cube_decision, parse_key_line, parse_ubgi_move, KeyLineSpec, UbgiMove};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum UbgiClientError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// The engine may still answer later, so the client is best dropped.
+    #[error("engine did not answer '{0}' in time")]
+    Timeout(String),
+    /// The engine closed its output, `None` if it is still running.
+    #[error("engine exited while waiting for '{command}' ({})", describe_exit(.status))]
+    Exited {
+        command: String,
+        status: Option<ExitStatus>,
+    },
+    /// An `error ...` line sent by the engine.
+    #[error("engine replied '{0}'")]
+    Engine(String),
+    #[error("unexpected reply '{0}'")]
+    Protocol(String),
+    #[error("engine has no key '{0}'")]
+    UnknownKey(String),
+    #[error("invalid value for key '{key}': {reason}")]
+    InvalidValue { key: String, reason: String },
+}
+
+fn describe_exit(status: &Option<ExitStatus>) -> String {
+    match status {
+        Some(status) => status.to_string(),
+        None => "end of output".to_string(),
+    }
+}
+
+pub type UbgiClientResult<T> = Result<T, UbgiClientError>;
+
+/// Client side of UBGI: runs an engine process and talks to it over stdin and stdout.
+pub struct UbgiClient {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    timeout: Duration,
+    name: String,
+    author: String,
+    version: String,
+    keys: Vec<KeyLineSpec>,
+}
+
+impl UbgiClient {
+    /// Starts `program` with `args` and performs the `ubgi` handshake.
+    pub fn spawn<S: AsRef<OsStr>>(
+        program: impl AsRef<OsStr>,
+        args: &[S],
+    ) -> UbgiClientResult<Self> {
+        let mut command = Command::new(program);
+        command.args(args);
+        Self::spawn_command(command, DEFAULT_TIMEOUT)
+    }
+
+    /// Like [Self::spawn] with a working directory.
+    pub fn spawn_in<S: AsRef<OsStr>>(
+        program: impl AsRef<OsStr>,
+        args: &[S],
+        cwd: impl AsRef<Path>,
+    ) -> UbgiClientResult<Self> {
+        let mut command = Command::new(program);
+        command.args(args).current_dir(cwd);
+        Self::spawn_command(command, DEFAULT_TIMEOUT)
+    }
+
+    /// Starts an engine from a prepared command. `timeout` applies to every reply, including the
+    /// handshake.
+    pub fn spawn_command(mut command: Command, timeout: Duration) -> UbgiClientResult<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut client = Self {
+            child,
+            stdin,
+            lines,
+            timeout,
+            name: String::new(),
+            author: String::new(),
+            version: String::new(),
+            keys: Vec::new(),
+        };
+        client.handshake()?;
+        Ok(client)
+    }
+
+    fn handshake(&mut self) -> UbgiClientResult<()> {
+        self.send("ubgi")?;
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let line = self.next_line("ubgi", deadline)?;
+            let line = line.trim();
+            if line == "ubgiok" {
+                return Ok(());
+            }
+            if let Some(name) = line.strip_prefix("id name ") {
+                self.name = name.to_string();
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                self.author = author.to_string();
+            } else if let Some(version) = line.strip_prefix("id version ") {
+                self.version = version.to_string();
+            } else if line.starts_with("key ") {
+                let key = parse_key_line(line)
+                    .ok_or_else(|| UbgiClientError::Protocol(line.to_string()))?;
+                self.keys.push(key);
+            } else if line.starts_with("error ") {
+                return Err(UbgiClientError::Engine(line.to_string()));
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Keys announced in the handshake.
+    pub fn keys(&self) -> &[KeyLineSpec] {
+        &self.keys
+    }
+
+    pub fn key(&self, name: &str) -> Option<&KeyLineSpec> {
+        self.keys.iter().find(|key| key.name == name)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Sends a raw command line.
+    pub fn send(&mut self, command: &str) -> UbgiClientResult<()> {
+        let written = writeln!(self.stdin, "{command}").and_then(|_| self.stdin.flush());
+        match written {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Err(self.exited(command)?),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn exited(&mut self, command: &str) -> UbgiClientResult<UbgiClientError> {
+        // Give a crashing engine a moment to be reaped.
+        let mut status = self.child.try_wait()?;
+        for _ in 0..50 {
+            if status.is_some() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+            status = self.child.try_wait()?;
+        }
+        Ok(UbgiClientError::Exited {
+            command: command.to_string(),
+            status,
+        })
+    }
+
+    fn next_line(&mut self, command: &str, deadline: Instant) -> UbgiClientResult<String> {
+        let wait = deadline.saturating_duration_since(Instant::now());
+        match self.lines.recv_timeout(wait) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => Err(UbgiClientError::Timeout(command.to_string())),
+            Err(RecvTimeoutError::Disconnected) => Err(self.exited(command)?),
+        }
+    }
+
+    /// Sends `command` and returns the first reply starting with `prefix`, without it.
+    fn request(&mut self, command: &str, prefix: &str) -> UbgiClientResult<String> {
+        self.send(command)?;
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let line = self.next_line(command, deadline)?;
+            let line = line.trim();
+            if let Some(rest) = line.strip_prefix(prefix) {
+                return Ok(rest.trim().to_string());
+            }
+            if line.starts_with("error ") {
+                return Err(UbgiClientError::Engine(line.to_string()));
+            }
+        }
+    }
+
+    /// Waits until the engine has processed all commands, failing on any error reported
+    /// before `readyok`.
+    pub fn sync(&mut self) -> UbgiClientResult<()> {
+        self.request("isready", "readyok").map(|_| ())
+    }
+
+    /// Checks `value` against the key line announced by the engine.
+    pub fn check_option(&self, key: &str, value: &str) -> UbgiClientResult<()> {
+        let spec = self
+            .key(key)
+            .ok_or_else(|| UbgiClientError::UnknownKey(key.to_string()))?;
+        spec.check_value(value)
+            .map_err(|reason| UbgiClientError::InvalidValue {
+                key: key.to_string(),
+                reason,
+            })
+    }
+
+    /// Sets a key announced by the engine and checks that it was accepted.
+    pub fn set_option(&mut self, key: &str, value: &str) -> UbgiClientResult<()> {
+        self.check_option(key, value)?;
+        self.send(&format!("set {key} {value}"))?;
+        self.sync()
+    }
+
+    /// Sets all options of an engine spec, checking them all before sending any.
+    pub fn apply_spec(&mut self, spec: &EngineSpec) -> UbgiClientResult<()> {
+        for (key, value) in &spec.options {
+            self.check_option(key, value)?;
+        }
+        for (key, value) in &spec.options {
+            self.send(&format!("set {key} {value}"))?;
+        }
+        self.sync()
+    }
+
+    pub fn get_option(&mut self, key: &str) -> UbgiClientResult<String> {
+        let value = self.request(&format!("get {key}"), "value ")?;
+        match value.split_once(' ') {
+            Some((name, value)) if name == key => Ok(value.to_string()),
+            _ => Err(UbgiClientError::Protocol(format!("value {value}"))),
+        }
+    }
+
+    pub fn set_variant(&mut self, variant: Variant) -> UbgiClientResult<()> {
+        self.send(&format!("set game.variant {variant}"))
+    }
+
+    pub fn new_game(&mut self) -> UbgiClientResult<()> {
+        self.send("newgame")
+    }
+
+    pub fn set_position(&mut self, position: VariantPosition) -> UbgiClientResult<()> {
+        self.send(&format!("position gnubgid {}", gnuid::encode(position)))
+    }
+
+    pub fn set_xgid(&mut self, xgid: &str) -> UbgiClientResult<()> {
+        self.send(&format!("position xgid {xgid}"))
+    }
+
+    pub fn set_dice(&mut self, dice: Dice) -> UbgiClientResult<()> {
+        let (d1, d2) = match dice {
+            Dice::Double(die) => (die, die),
+            Dice::Mixed(mixed) => (mixed.big(), mixed.small()),
+        };
+        self.send(&format!("dice {d1} {d2}"))
+    }
+
+    /// Sets the cube, `owner` is `None` for a centered cube and `Some(true)` for the player on
+    /// roll.
+    pub fn set_cube(
+        &mut self,
+        value: u32,
+        owner: Option<bool>,
+        offered: bool,
+    ) -> UbgiClientResult<()> {
+        let owner = match owner {
+            None => "center",
+            Some(true) => "x",
+            Some(false) => "o",
+        };
+        let offered = if offered { " offered" } else { "" };
+        self.send(&format!("cube {value} {owner}{offered}"))
+    }
+
+    /// Asks for the move with the current position and dice.
+    pub fn best_move(&mut self) -> UbgiClientResult<UbgiMove> {
+        let text = self.request("go", "bestmove ")?;
+        parse_ubgi_move(&text).map_err(|_| UbgiClientError::Protocol(format!("bestmove {text}")))
+    }
+
+    /// Asks for the cube decision, `None` is no double.
+    pub fn cube_decision(&mut self) -> UbgiClientResult<Option<CubeAction>> {
+        let text = self.request("go cube", "bestcube ")?;
+        parse_cube_decision(&text)
+            .map_err(|_| UbgiClientError::Protocol(format!("bestcube {text}")))
+    }
+
+    /// Sends `quit` and waits for the engine to exit, killing it after the timeout.
+    pub fn quit(mut self) -> UbgiClientResult<Option<ExitStatus>> {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + self.timeout;
+        while Instant::now() < deadline {
+            if let Some(status) = self.child.try_wait()? {
+                return Ok(Some(status));
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        self.child.kill()?;
+        Ok(None)
+    }
+}
+
+impl Drop for UbgiClient {
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -1,11 +1,18 @@
 use std::collections::HashMap;
-use std::io::{self, BufRead, Write};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+use std::sync::Arc;
+use std::thread;
 
 use crate::codecs::gnuid;
 use crate::codecs::move_text;
+use crate::codecs::xgid::{Xgid, XgidDice};
+use crate::cube::{CubeAction, CubeState};
+use crate::match_play::{Match, XGID_BEAVER, XGID_JACOBY};
+use crate::position::GameState;
 use crate::{Game, Variant, VARIANTS};
 
-use super::{OptionSpec, OptionValue, UbgiEngine, UbgiError};
+use super::{format_cube_decision, MatchScore, OptionSpec, OptionValue, UbgiEngine, UbgiError};
 
 enum ProtocolError {
     BadCommand(String),
@@ -31,11 +38,17 @@ enum Command {
     NewGame,
     Keys,
     Get(String),
-    Set { key: String, value: String },
-    PositionGnubgid(String),
-    PositionXgid,
+    Set {
+        key: String,
+        value: String,
+    },
+    Position {
+        setup: PositionSetup,
+        moves: Vec<String>,
+    },
     PositionUnknown,
     Dice(String),
+    Cube(String),
     GoChequer,
     GoCube,
     GoTurn,
@@ -43,10 +56,18 @@ enum Command {
     Unknown,
 }
 
+enum PositionSetup {
+    Startpos,
+    Gnubgid(String),
+    Xgid(String),
+}
+
 struct SessionState {
     variant: Variant,
     game: Game,
     dice: Option<crate::Dice>,
+    beavers: bool,
+    score: Option<MatchScore>,
     option_specs: Vec<OptionSpec>,
     option_values: HashMap<String, OptionValue>,
     variant_key: String,
@@ -70,20 +91,40 @@ impl SessionState {
             variant,
             game: Game::new(variant),
             dice: None,
+            beavers: false,
+            score: None,
             option_specs,
             option_values,
             variant_key,
         }
     }
+
+    /// A new game at the current score, without the cube in the Crawford game.
+    fn new_game(&self) -> Game {
+        let cube = if self.score.is_some_and(|score| score.crawford) {
+            CubeState::unavailable()
+        } else {
+            CubeState::new().with_beavers(self.beavers, false)
+        };
+        Game::with_cube(self.variant, cube)
+    }
 }
 
 pub fn run_ubgi_stdio(engine: &mut impl UbgiEngine) {
     let stdin = io::stdin();
-    let mut stdout = io::stdout();
+    let _ = run_ubgi(engine, stdin.lock(), io::stdout());
+}
+
+/// Runs one UBGI session, reading commands from `reader` until `quit` or end of input.
+pub fn run_ubgi<R: BufRead, W: Write>(
+    engine: &mut impl UbgiEngine,
+    reader: R,
+    mut writer: W,
+) -> io::Result<()> {
     let mut state = SessionState::new(engine);
 
-    for line in stdin.lock().lines() {
-        let Ok(line) = line else { break };
+    for line in reader.lines() {
+        let line = line?;
         let cmd = line.trim();
         if cmd.is_empty() {
             continue;
@@ -91,12 +132,53 @@ pub fn run_ubgi_stdio(engine: &mut impl UbgiEngine) {
         let command = parse_command(cmd);
         let should_quit = matches!(command, Command::Quit);
         for line in handle_command(engine, &mut state, command) {
-            reply(&mut stdout, &line);
+            reply(&mut writer, &line)?;
         }
         if should_quit {
             break;
         }
     }
+    Ok(())
+}
+
+/// Listens on `addr` and serves one session per connection, each with a new engine from
+/// `make_engine` on its own thread.
+pub fn run_ubgi_tcp<E, F>(addr: impl ToSocketAddrs, make_engine: F) -> io::Result<()>
+where
+    E: UbgiEngine,
+    F: Fn() -> E + Send + Sync + 'static,
+{
+    serve_ubgi_tcp(TcpListener::bind(addr)?, make_engine)
+}
+
+/// Like [run_ubgi_tcp] with a bound listener, for example on port 0.
+///
+/// A failed accept, for example when out of file descriptors, is logged and the server keeps
+/// listening.
+pub fn serve_ubgi_tcp<E, F>(listener: TcpListener, make_engine: F) -> io::Result<()>
+where
+    E: UbgiEngine,
+    F: Fn() -> E + Send + Sync + 'static,
+{
+    let make_engine = Arc::new(make_engine);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("ubgi: accept failed: {err}");
+                continue;
+            }
+        };
+        let make_engine = Arc::clone(&make_engine);
+        thread::spawn(move || {
+            let Ok(reader) = stream.try_clone() else {
+                return;
+            };
+            let mut engine = make_engine();
+            let _ = run_ubgi(&mut engine, BufReader::new(reader), stream);
+        });
+    }
+    Ok(())
 }
 
 fn parse_command(line: &str) -> Command {
@@ -121,18 +203,15 @@ fn parse_command(line: &str) -> Command {
         let value = it.next().unwrap_or("").trim().to_string();
         return Command::Set { key, value };
     }
-    if let Some(id) = line.strip_prefix("position gnubgid ") {
-        return Command::PositionGnubgid(id.trim().to_string());
-    }
-    if line == "position xgid" || line.starts_with("position xgid ") {
-        return Command::PositionXgid;
-    }
-    if line.starts_with("position ") {
-        return Command::PositionUnknown;
+    if let Some(rest) = line.strip_prefix("position ") {
+        return parse_position(rest);
     }
     if let Some(rest) = line.strip_prefix("dice ") {
         return Command::Dice(rest.to_string());
     }
+    if let Some(rest) = line.strip_prefix("cube ") {
+        return Command::Cube(rest.to_string());
+    }
     if line == "go" || line == "go chequer" {
         return Command::GoChequer;
     }
@@ -148,6 +227,29 @@ fn parse_command(line: &str) -> Command {
     Command::Unknown
 }
 
+/// `startpos`, `gnubgid <ID>` or `xgid <XGID>`, optionally followed by `moves <dice>:<move> ...`.
+fn parse_position(rest: &str) -> Command {
+    let mut tokens = rest.split_whitespace();
+    let setup = match tokens.next() {
+        Some("startpos") => PositionSetup::Startpos,
+        Some("gnubgid") => match tokens.next() {
+            Some(id) => PositionSetup::Gnubgid(id.to_string()),
+            None => return Command::PositionUnknown,
+        },
+        Some("xgid") => PositionSetup::Xgid(tokens.next().unwrap_or_default().to_string()),
+        _ => return Command::PositionUnknown,
+    };
+    let moves: Vec<String> = match tokens.next() {
+        None => Vec::new(),
+        Some("moves") => tokens.map(str::to_string).collect(),
+        Some(_) => return Command::PositionUnknown,
+    };
+    if moves.is_empty() && rest.split_whitespace().any(|token| token == "moves") {
+        return Command::PositionUnknown;
+    }
+    Command::Position { setup, moves }
+}
+
 fn handle_command(
     engine: &mut impl UbgiEngine,
     state: &mut SessionState,
@@ -170,39 +272,35 @@ fn handle_command(
             Err(err) => vec![ProtocolError::BadState(format!("isready {err}")).to_line()],
         },
         Command::NewGame => {
-            state.game = Game::new(state.variant);
+            let out = set_score(engine, state, None);
+            state.game = state.new_game();
             state.dice = None;
-            Vec::new()
+            out
         }
         Command::Keys => render_key_lines(state),
         Command::Get(key) => handle_get(engine, state, &key),
         Command::Set { key, value } => handle_set(engine, state, &key, &value),
-        Command::PositionGnubgid(id) => {
-            if let Ok(pos) = gnuid::decode(state.variant, id.trim()) {
-                let _ = state.game.set_position(pos);
-                Vec::new()
-            } else {
-                vec![ProtocolError::BadValue("position".to_string()).to_line()]
-            }
-        }
-        Command::PositionXgid => {
-            vec![ProtocolError::Unsupported("position.xgid".to_string()).to_line()]
-        }
+        Command::Position { setup, moves } => handle_position(engine, state, setup, &moves),
         Command::PositionUnknown => vec![ProtocolError::BadCommand(
-            "expected: position gnubgid <GNU_POSITION_ID>".to_string(),
+            "expected: position startpos|gnubgid <GNU_POSITION_ID>|xgid <XGID> [moves <dice>:<move> ...]"
+                .to_string(),
         )
         .to_line()],
         Command::Dice(rest) => handle_dice(state, &rest),
+        Command::Cube(rest) => handle_cube(state, &rest),
         Command::GoChequer => handle_go(engine, state),
-        Command::GoCube => vec![ProtocolError::Unsupported("go cube".to_string()).to_line()],
-        Command::GoTurn => vec![ProtocolError::Unsupported("go turn".to_string()).to_line()],
+        Command::GoCube => handle_go_cube(engine, state),
+        Command::GoTurn => handle_go_turn(engine, state),
         Command::Quit => Vec::new(),
         Command::Unknown => vec![ProtocolError::BadCommand("unknown".to_string()).to_line()],
     }
 }
 
 fn render_key_lines(state: &SessionState) -> Vec<String> {
-    let mut out = vec![state.variant_key.clone()];
+    let mut out = vec![
+        state.variant_key.clone(),
+        format!("key game.beavers bool {}", state.beavers),
+    ];
     for spec in &state.option_specs {
         out.push(spec.to_key_line());
     }
@@ -213,6 +311,9 @@ fn handle_get(engine: &impl UbgiEngine, state: &SessionState, key: &str) -> Vec<
     if key == "game.variant" {
         return vec![format!("value game.variant {}", state.variant)];
     }
+    if key == "game.beavers" {
+        return vec![format!("value game.beavers {}", state.beavers)];
+    }
     if let Some(value) = state.option_values.get(key) {
         return vec![format!("value {key} {}", value.to_wire())];
     }
@@ -237,12 +338,23 @@ fn handle_set(
         return match value.parse::<Variant>() {
             Ok(v) => {
                 state.variant = v;
-                state.game = Game::new(v);
+                state.game = state.new_game();
                 Vec::new()
             }
             Err(_) => vec![ProtocolError::BadValue("game.variant".to_string()).to_line()],
         };
     }
+    if key == "game.beavers" {
+        return match value {
+            "true" | "false" => {
+                state.beavers = value == "true";
+                let cube = state.game.cube().with_beavers(state.beavers, false);
+                state.game.set_cube(cube);
+                Vec::new()
+            }
+            _ => vec![ProtocolError::BadValue("game.beavers".to_string()).to_line()],
+        };
+    }
     let Some(spec) = state.option_specs.iter().find(|spec| spec.key() == key) else {
         return vec![ProtocolError::Unsupported("key".to_string()).to_line()];
     };
@@ -289,7 +401,178 @@ fn handle_dice(state: &mut SessionState, rest: &str) -> Vec<String> {
     }
 }
 
+fn handle_position(
+    engine: &mut impl UbgiEngine,
+    state: &mut SessionState,
+    setup: PositionSetup,
+    moves: &[String],
+) -> Vec<String> {
+    let setup = match setup {
+        PositionSetup::Startpos => Ok((state.new_game(), None, state.score)),
+        PositionSetup::Gnubgid(id) => match gnuid::decode(state.variant, &id) {
+            Ok(position) => {
+                let mut game = state.game;
+                let _ = game.set_position(position);
+                Ok((game, state.dice, state.score))
+            }
+            Err(_) => Err(ProtocolError::BadValue("position".to_string())),
+        },
+        PositionSetup::Xgid(id) => position_from_xgid(state, &id),
+    };
+    let (mut game, mut dice, score) = match setup {
+        Ok(setup) => setup,
+        Err(err) => return vec![err.to_line()],
+    };
+    for (index, token) in moves.iter().enumerate() {
+        if let Err(err) = apply_history_move(&mut game, index + 1, token) {
+            return vec![err.to_line()];
+        }
+        dice = None;
+    }
+    state.game = game;
+    state.dice = dice;
+    set_score(engine, state, score)
+}
+
+/// Applies one `<dice>:<move>` token of a game history, `index` counts from 1.
+fn apply_history_move(game: &mut Game, index: usize, token: &str) -> Result<(), ProtocolError> {
+    let error = |what: &str| ProtocolError::BadValue(format!("moves.{index}.{what} {token}"));
+    let (dice, text) = token.split_once(':').ok_or_else(|| error("syntax"))?;
+    let dice = match dice.as_bytes() {
+        [d1 @ b'1'..=b'6', d2 @ b'1'..=b'6'] => {
+            crate::Dice::new((d1 - b'0') as usize, (d2 - b'0') as usize)
+        }
+        _ => return Err(error("dice")),
+    };
+    if game.game_state() != GameState::Ongoing {
+        return Err(error("game_over"));
+    }
+    if !game.cube().is_settled() {
+        return Err(error("cube_offered"));
+    }
+    let text = text.replace(',', " ");
+    if move_text::normalize(&text).is_none() {
+        return Err(error("syntax"));
+    }
+    let next = move_text::apply(game.position(), dice, &text).ok_or_else(|| error("illegal"))?;
+    let index = game
+        .legal_positions(&dice)
+        .iter()
+        .position(|position| *position == next)
+        .ok_or_else(|| error("illegal"))?;
+    game.apply_nth_legal_position(&dice, index)
+        .map_err(|_| error("illegal"))
+}
+
+/// Game, dice and match score of an XGID, with the side to move of its move flag on roll. Dice
+/// `00` leave the dice unset.
+fn position_from_xgid(
+    state: &SessionState,
+    id: &str,
+) -> Result<(Game, Option<crate::Dice>, Option<MatchScore>), ProtocolError> {
+    let bad_value = || ProtocolError::BadValue("position".to_string());
+    let parsed = Xgid::parse(id).map_err(|_| bad_value())?;
+    let (mut game, score) = if parsed.match_length == 0 {
+        let position = parsed.position(state.variant).map_err(|_| bad_value())?;
+        if parsed.cube_power > 30 || parsed.max_cube > 30 {
+            return Err(bad_value());
+        }
+        // `x` of the XGID has turn `true`.
+        let owner = match parsed.cube_owner {
+            1 => Some(true),
+            -1 => Some(false),
+            _ => None,
+        };
+        let cube = CubeState::new()
+            .with_value(1 << parsed.cube_power, owner)
+            .with_max_value((parsed.max_cube > 0).then(|| 1u32 << parsed.max_cube))
+            .with_beavers(state.beavers || parsed.rules & XGID_BEAVER != 0, false)
+            .with_jacoby(parsed.rules & XGID_JACOBY != 0);
+        let mut game = Game::with_cube(state.variant, cube);
+        game.set_position(position).map_err(|_| bad_value())?;
+        (game, None)
+    } else {
+        let m = Match::from_xgid(state.variant, &parsed).map_err(|_| bad_value())?;
+        let on_roll = m.game().position().turn();
+        let score = MatchScore {
+            length: m.length(),
+            score: m.score(on_roll),
+            opponent_score: m.score(!on_roll),
+            crawford: m.is_crawford(),
+        };
+        (*m.game(), Some(score))
+    };
+    let dice = match parsed.dice {
+        XgidDice::Rolled(0, 0) => None,
+        XgidDice::Rolled(d1, d2) if d1 > 0 && d2 > 0 => {
+            Some(crate::Dice::new(d1 as usize, d2 as usize))
+        }
+        XgidDice::Rolled(..) => return Err(bad_value()),
+        XgidDice::DoubleOffered => {
+            game.apply_cube_action(CubeAction::Double)
+                .map_err(|_| bad_value())?;
+            None
+        }
+    };
+    Ok((game, dice, score))
+}
+
+fn set_score(
+    engine: &mut impl UbgiEngine,
+    state: &mut SessionState,
+    score: Option<MatchScore>,
+) -> Vec<String> {
+    if state.score == score {
+        return Vec::new();
+    }
+    state.score = score;
+    match engine.set_match_score(score) {
+        Ok(()) => Vec::new(),
+        Err(err) => vec![ProtocolError::BadState(format!("score {err}")).to_line()],
+    }
+}
+
+/// `cube off`, or `cube <value> <center|x|o> [offered]` where `x` is the player on roll and
+/// `offered` means that `x` has doubled.
+fn handle_cube(state: &mut SessionState, rest: &str) -> Vec<String> {
+    let bad_value = || vec![ProtocolError::BadValue("cube".to_string()).to_line()];
+    let parts: Vec<&str> = rest.split_whitespace().collect();
+    if parts == ["off"] {
+        state.game.set_cube(CubeState::unavailable());
+        return Vec::new();
+    }
+    let (value, owner, offered) = match parts.as_slice() {
+        [value, owner] => (value, owner, false),
+        [value, owner, "offered"] => (value, owner, true),
+        _ => return bad_value(),
+    };
+    let Ok(value) = value.parse::<u32>() else {
+        return bad_value();
+    };
+    if !value.is_power_of_two() || value > 1 << 30 {
+        return bad_value();
+    }
+    let on_roll = state.game.position().turn();
+    let owner = match *owner {
+        "center" => None,
+        "x" => Some(on_roll),
+        "o" => Some(!on_roll),
+        _ => return bad_value(),
+    };
+    let mut cube = CubeState::new()
+        .with_value(value, owner)
+        .with_beavers(state.beavers, false);
+    if offered && cube.apply(CubeAction::Double, on_roll).is_err() {
+        return bad_value();
+    }
+    state.game.set_cube(cube);
+    Vec::new()
+}
+
 fn handle_go(engine: &mut impl UbgiEngine, state: &SessionState) -> Vec<String> {
+    if state.game.cube().is_double_offered() {
+        return vec![ProtocolError::BadState("cube.offered".to_string()).to_line()];
+    }
     let Some(current_dice) = state.dice else {
         return vec![ProtocolError::BadState("missing.dice".to_string()).to_line()];
     };
@@ -299,7 +582,335 @@ fn handle_go(engine: &mut impl UbgiEngine, state: &SessionState) -> Vec<String>
     }
 }
 
-fn reply(out: &mut impl Write, line: &str) {
-    let _ = writeln!(out, "{line}");
-    let _ = out.flush();
+/// Asks the player on roll whether to double, or the opponent how to answer an offered double.
+fn handle_go_cube(engine: &mut impl UbgiEngine, state: &SessionState) -> Vec<String> {
+    let decision = if state.game.cube().is_double_offered() {
+        respond_to_double(engine, &state.game).map(Some)
+    } else {
+        decide_double(engine, &state.game)
+    };
+    match decision {
+        Ok(action) => vec![format!("bestcube {}", format_cube_decision(action))],
+        Err(err) => vec![err.to_line()],
+    }
+}
+
+/// Cube decision of the player on roll and, without a double, the move for the dice.
+fn handle_go_turn(engine: &mut impl UbgiEngine, state: &SessionState) -> Vec<String> {
+    if state.game.cube().is_double_offered() {
+        return vec![ProtocolError::BadState("cube.offered".to_string()).to_line()];
+    }
+    if state.dice.is_none() {
+        return vec![ProtocolError::BadState("missing.dice".to_string()).to_line()];
+    }
+    match decide_double(engine, &state.game) {
+        Ok(Some(action)) => vec![format!("bestcube {}", format_cube_decision(Some(action)))],
+        Ok(None) => {
+            let mut out = vec![format!("bestcube {}", format_cube_decision(None))];
+            out.extend(handle_go(engine, state));
+            out
+        }
+        Err(err) => vec![err.to_line()],
+    }
+}
+
+fn decide_double(
+    engine: &mut impl UbgiEngine,
+    game: &Game,
+) -> Result<Option<CubeAction>, ProtocolError> {
+    if !game.legal_cube_actions().contains(&CubeAction::Double) {
+        return Ok(None);
+    }
+    match engine.offer_double(game) {
+        Ok(double) => Ok(double.then_some(CubeAction::Double)),
+        Err(err) => Err(ProtocolError::BadState(format!("cube.select {err}"))),
+    }
+}
+
+fn respond_to_double(
+    engine: &mut impl UbgiEngine,
+    game: &Game,
+) -> Result<CubeAction, ProtocolError> {
+    match engine.respond_to_double(game) {
+        Ok(action) if game.legal_cube_actions().contains(&action) => Ok(action),
+        Ok(action) => Err(ProtocolError::BadState(format!(
+            "cube.select illegal {}",
+            format_cube_decision(Some(action))
+        ))),
+        Err(err) => Err(ProtocolError::BadState(format!("cube.select {err}"))),
+    }
+}
+
+fn reply(out: &mut impl Write, line: &str) -> io::Result<()> {
+    writeln!(out, "{line}")?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Cursor, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    use super::{handle_command, parse_command, run_ubgi, serve_ubgi_tcp, SessionState};
+    use crate::codecs::move_text;
+    use crate::cube::CubeAction;
+    use crate::ubgi::{FirstMoveEngine, MatchScore};
+    use crate::{Dice, Game};
+
+    fn run(engine: &mut FirstMoveEngine, state: &mut SessionState, lines: &[&str]) -> Vec<String> {
+        lines
+            .iter()
+            .flat_map(|line| handle_command(engine, state, parse_command(line)))
+            .collect()
+    }
+
+    #[test]
+    fn go_cube_doubles_and_answers() {
+        let mut engine = FirstMoveEngine::new(true, CubeAction::Pass);
+        let mut state = SessionState::new(&engine);
+        assert_eq!(
+            run(&mut engine, &mut state, &["go cube"]),
+            ["bestcube double"]
+        );
+        assert_eq!(
+            run(&mut engine, &mut state, &["cube 2 o", "go cube"]),
+            ["bestcube nodouble"]
+        );
+        assert_eq!(
+            run(&mut engine, &mut state, &["cube 2 x offered", "go cube"]),
+            ["bestcube pass"]
+        );
+        assert_eq!(
+            run(&mut engine, &mut state, &["go"]),
+            ["error bad_state cube.offered"]
+        );
+
+        engine.response = CubeAction::Beaver;
+        assert_eq!(
+            run(&mut engine, &mut state, &["go cube"]),
+            ["error bad_state cube.select illegal beaver"]
+        );
+        assert_eq!(
+            run(
+                &mut engine,
+                &mut state,
+                &["set game.beavers true", "cube 1 center offered", "go cube"]
+            ),
+            ["bestcube beaver"]
+        );
+        assert_eq!(
+            run(
+                &mut engine,
+                &mut state,
+                &["cube 3 center", "cube 2 o offered"]
+            ),
+            ["error bad_value cube", "error bad_value cube"]
+        );
+    }
+
+    #[test]
+    fn go_turn_moves_without_double() {
+        let mut engine = FirstMoveEngine::new(false, CubeAction::Take);
+        let mut state = SessionState::new(&engine);
+        assert_eq!(
+            run(&mut engine, &mut state, &["go turn"]),
+            ["error bad_state missing.dice"]
+        );
+        let out = run(&mut engine, &mut state, &["dice 3 1", "go turn"]);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[0], "bestcube nodouble");
+        assert!(out[1].starts_with("bestmove "));
+
+        engine.double = true;
+        assert_eq!(
+            run(&mut engine, &mut state, &["go turn"]),
+            ["bestcube double"]
+        );
+        assert_eq!(
+            run(&mut engine, &mut state, &["cube off", "go turn"])[0],
+            "bestcube nodouble"
+        );
+    }
+
+    #[test]
+    fn position_xgid_sets_dice_cube_and_score() {
+        let mut engine = FirstMoveEngine::new(true, CubeAction::Take);
+        let mut state = SessionState::new(&engine);
+        let out = run(
+            &mut engine,
+            &mut state,
+            &[
+                "position xgid XGID=-b----E-C---eE---c-e----B-:3:7:0:4:2:52:1:-1:1",
+                "go turn",
+            ],
+        );
+        assert_eq!(out, ["bestcube nodouble", "bestmove 13/8 24/22"]);
+        assert_eq!(state.game.cube().value(), 2);
+        assert_eq!(
+            engine.score,
+            Some(MatchScore {
+                length: 7,
+                score: 4,
+                opponent_score: 2,
+                crawford: false
+            })
+        );
+
+        let out = run(
+            &mut engine,
+            &mut state,
+            &[
+                "position xgid XGID=-b----E-C---eE---c-e----B-:0:7:1:6:2:00:1:0:0",
+                "go cube",
+                "go",
+            ],
+        );
+        assert_eq!(out, ["bestcube nodouble", "error bad_state missing.dice"]);
+        assert!(engine.score.unwrap().crawford);
+
+        let out = run(
+            &mut engine,
+            &mut state,
+            &[
+                "position xgid XGID=-b----E-C---eE---c-e----B-:0:0:0:0:0:D:1:0:0",
+                "go cube",
+                "position xgid XGID=-b----E-C---eE---c-e----B-:0:0:0:0:0:77:1:0:0",
+                "position xgid",
+            ],
+        );
+        assert_eq!(
+            out,
+            [
+                "bestcube take",
+                "error bad_value position",
+                "error bad_value position"
+            ]
+        );
+        assert_eq!(engine.score, None);
+    }
+
+    #[test]
+    fn position_xgid_puts_the_side_to_move_on_roll() {
+        let mut engine = FirstMoveEngine::new(true, CubeAction::Take);
+        let mut state = SessionState::new(&engine);
+        let out = run(
+            &mut engine,
+            &mut state,
+            &[
+                "position xgid XGID=-b----E-C---eE---c-e----B-:3:7:0:4:2:00:0:-1:1",
+                "go cube",
+            ],
+        );
+        assert_eq!(out, ["bestcube double"]);
+        assert!(!state.game.position().turn());
+        assert_eq!(state.game.cube().owner(), Some(false));
+        assert_eq!(
+            engine.score,
+            Some(MatchScore {
+                length: 7,
+                score: 2,
+                opponent_score: 4,
+                crawford: false
+            })
+        );
+
+        let out = run(
+            &mut engine,
+            &mut state,
+            &["position xgid XGID=-b----E-C---eE---c-e----B-:0:0:3:0:0:00:0:0:0"],
+        );
+        assert!(out.is_empty(), "{out:?}");
+        assert!(!state.game.position().turn());
+        assert!(state.game.cube().is_jacoby());
+        assert!(state
+            .game
+            .legal_cube_actions()
+            .contains(&CubeAction::Double));
+        state.game.apply_cube_action(CubeAction::Double).unwrap();
+        assert!(state
+            .game
+            .legal_cube_actions()
+            .contains(&CubeAction::Beaver));
+        assert_eq!(engine.score, None);
+    }
+
+    #[test]
+    fn position_with_moves() {
+        let mut engine = FirstMoveEngine::new(false, CubeAction::Take);
+        let mut state = SessionState::new(&engine);
+        let out = run(
+            &mut engine,
+            &mut state,
+            &["position startpos moves 31:8/5,6/5 64:24/18,13/9"],
+        );
+        assert!(out.is_empty(), "{out:?}");
+        assert!(state.dice.is_none());
+        let mut expected = Game::new(state.variant);
+        let first = move_text::apply(expected.position(), Dice::new(3, 1), "8/5 6/5").unwrap();
+        let second = move_text::apply(first, Dice::new(6, 4), "24/18 13/9").unwrap();
+        expected.set_position(second).unwrap();
+        assert_eq!(state.game.position(), expected.position());
+
+        let out = run(
+            &mut engine,
+            &mut state,
+            &[
+                "position startpos moves 31:8/5,6/5 64:24/18,13/8",
+                "position startpos moves 31:8/5,6/5 74:24/18",
+                "position startpos moves 31",
+                "position startpos moves",
+                "position xgid XGID=-b----E-C---eE---c-e----B-:0:0:0:0:0:D:1:0:0 moves 52:13/8,13/11",
+            ],
+        );
+        assert_eq!(
+            out,
+            [
+                "error bad_value moves.2.illegal 64:24/18,13/8",
+                "error bad_value moves.2.dice 74:24/18",
+                "error bad_value moves.1.syntax 31",
+                "error bad_command expected: position startpos|gnubgid <GNU_POSITION_ID>|xgid <XGID> [moves <dice>:<move> ...]",
+                "error bad_value moves.1.cube_offered 52:13/8,13/11",
+            ]
+        );
+        // Failed commands leave the position unchanged.
+        assert_eq!(state.game.position(), expected.position());
+    }
+
+    #[test]
+    fn runs_session_on_reader_and_writer() {
+        let input = "ubgi\nisready\n\ngo cube\nquit\ngo cube\n";
+        let mut output = Vec::new();
+        run_ubgi(
+            &mut FirstMoveEngine::new(true, CubeAction::Take),
+            Cursor::new(input),
+            &mut output,
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "id name first-move");
+        assert!(lines.contains(&"ubgiok"));
+        assert_eq!(lines[lines.len() - 2..], ["readyok", "bestcube double"]);
+    }
+
+    #[test]
+    fn serves_sessions_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            serve_ubgi_tcp(listener, || FirstMoveEngine::new(true, CubeAction::Take))
+        });
+
+        let mut sessions: Vec<_> = (0..2)
+            .map(|_| {
+                let stream = TcpStream::connect(addr).unwrap();
+                (BufReader::new(stream.try_clone().unwrap()), stream)
+            })
+            .collect();
+        for (reader, writer) in &mut sessions {
+            writer.write_all(b"isready\ngo cube\nquit\n").unwrap();
+            let lines: Vec<String> = reader.lines().map(Result::unwrap).collect();
+            assert_eq!(lines, ["readyok", "bestcube double"]);
+        }
+    }
 }
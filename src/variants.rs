@@ -69,6 +69,22 @@ pub enum VariantPosition {
     Hypergammon5(Position<5>),
 }
 
+/// Evaluates `$body` with `$p` bound to the `Position<N>` inside a [VariantPosition].
+macro_rules! with_position {
+    ($position:expr, $p:ident => $body:expr) => {
+        match $position {
+            $crate::variants::VariantPosition::Backgammon($p) => $body,
+            $crate::variants::VariantPosition::Nackgammon($p) => $body,
+            $crate::variants::VariantPosition::Longgammon($p) => $body,
+            $crate::variants::VariantPosition::Hypergammon($p) => $body,
+            $crate::variants::VariantPosition::Hypergammon2($p) => $body,
+            $crate::variants::VariantPosition::Hypergammon4($p) => $body,
+            $crate::variants::VariantPosition::Hypergammon5($p) => $body,
+        }
+    };
+}
+pub(crate) use with_position;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct VariantSpec {
     pub name: &'static str,
@@ -159,6 +175,19 @@ impl VariantPosition {
         }
     }
 
+    /// The same position seen from the other player, who is then on roll.
+    pub fn flip(self) -> VariantPosition {
+        match self {
+            VariantPosition::Backgammon(p) => VariantPosition::Backgammon(p.flip()),
+            VariantPosition::Nackgammon(p) => VariantPosition::Nackgammon(p.flip()),
+            VariantPosition::Longgammon(p) => VariantPosition::Longgammon(p.flip()),
+            VariantPosition::Hypergammon(p) => VariantPosition::Hypergammon(p.flip()),
+            VariantPosition::Hypergammon2(p) => VariantPosition::Hypergammon2(p.flip()),
+            VariantPosition::Hypergammon4(p) => VariantPosition::Hypergammon4(p.flip()),
+            VariantPosition::Hypergammon5(p) => VariantPosition::Hypergammon5(p.flip()),
+        }
+    }
+
     pub fn phase(self) -> GamePhase {
         match self {
             VariantPosition::Backgammon(p) => p.phase(),
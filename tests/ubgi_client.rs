@@ -0,0 +1,132 @@
+use std::process::Command;
+use std::time::Duration;
+
+use bkgm::codecs::move_text;
+use bkgm::ubgi::{UbgiClient, UbgiClientError};
+use bkgm::{parse_engine_spec, Dice, EngineRegistry, Game, RegistryError, Variant};
+
+fn stub() -> UbgiClient {
+    UbgiClient::spawn::<&str>(env!("CARGO_BIN_EXE_bkgm-ubgi-stub"), &[]).expect("spawn stub")
+}
+
+#[test]
+fn handshake_and_options() {
+    let mut client = stub();
+    assert_eq!(client.name(), "bkgm-ubgi-stub");
+    assert_eq!(client.author(), "bkgm");
+    assert_eq!(client.version(), env!("CARGO_PKG_VERSION"));
+    let seed = client.key("engine.seed").expect("seed key");
+    assert_eq!(seed.key_type, "int");
+    assert!(client.key("game.variant").is_some());
+
+    client
+        .apply_spec(&parse_engine_spec("stub:seed=7").unwrap())
+        .unwrap();
+    assert_eq!(client.get_option("engine.seed").unwrap(), "7");
+
+    let err = client
+        .apply_spec(&parse_engine_spec("stub:ply=2").unwrap())
+        .unwrap_err();
+    assert!(matches!(err, UbgiClientError::UnknownKey(key) if key == "engine.ply"));
+    let err = client.set_option("engine.seed", "abc").unwrap_err();
+    assert!(
+        matches!(&err, UbgiClientError::InvalidValue { key, .. } if key == "engine.seed"),
+        "{err}"
+    );
+    client.send("set engine.seed abc").unwrap();
+    let err = client.sync().unwrap_err();
+    assert!(
+        matches!(&err, UbgiClientError::Engine(line) if line == "error bad_value engine.seed"),
+        "{err}"
+    );
+    assert!(client
+        .quit()
+        .unwrap()
+        .is_some_and(|status| status.success()));
+}
+
+#[test]
+fn plays_moves_and_cube() {
+    let mut client = stub();
+    let game = Game::new(Variant::Backgammon);
+    client.new_game().unwrap();
+    client.set_position(game.position()).unwrap();
+    let err = client.best_move().unwrap_err();
+    assert!(matches!(&err, UbgiClientError::Engine(line) if line.contains("missing.dice")));
+
+    let dice = Dice::new(3, 1);
+    client.set_dice(dice).unwrap();
+    let steps = client.best_move().unwrap();
+    let legal = move_text::legal_steps(game.position(), dice).unwrap();
+    assert!(legal.iter().any(|(legal, _)| *legal == steps));
+    assert_eq!(client.cube_decision().unwrap(), None);
+
+    client.set_cube(2, Some(false), false).unwrap();
+    client.set_cube(1, None, true).unwrap();
+    assert_eq!(
+        client.cube_decision().unwrap(),
+        Some(bkgm::CubeAction::Take)
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn reports_timeouts_and_crashes() {
+    let mut silent = Command::new("sh");
+    silent.args(["-c", "read line; sleep 5"]);
+    let err = UbgiClient::spawn_command(silent, Duration::from_millis(200))
+        .err()
+        .expect("timeout");
+    assert!(matches!(&err, UbgiClientError::Timeout(command) if command == "ubgi"));
+
+    let mut crashing = Command::new("sh");
+    crashing.args(["-c", "read line; echo 'id name crash'; exit 3"]);
+    let err = UbgiClient::spawn_command(crashing, Duration::from_secs(5))
+        .err()
+        .expect("crash");
+    match err {
+        UbgiClientError::Exited { command, status } => {
+            assert_eq!(command, "ubgi");
+            assert_eq!(status.and_then(|status| status.code()), Some(3));
+        }
+        err => panic!("unexpected error {err}"),
+    }
+}
+
+#[test]
+fn registry_launches_and_validates_specs() {
+    let config = format!(
+        "[stub@v1]\npath = \"{}\"\noptions.seed = 5\n",
+        env!("CARGO_BIN_EXE_bkgm-ubgi-stub").replace('\\', "\\\\")
+    );
+    let registry = EngineRegistry::parse(&config).unwrap();
+    let timeout = Duration::from_secs(10);
+
+    let mut client = registry
+        .launch(&parse_engine_spec("stub").unwrap(), timeout)
+        .unwrap();
+    assert_eq!(client.get_option("engine.seed").unwrap(), "5");
+    let mut client_2 = registry
+        .launch(&parse_engine_spec("stub@v1:seed=9").unwrap(), timeout)
+        .unwrap();
+    assert_eq!(client_2.get_option("engine.seed").unwrap(), "9");
+    client.quit().unwrap();
+    client_2.quit().unwrap();
+
+    let err = registry
+        .launch(&parse_engine_spec("stub@v1:seed=x").unwrap(), timeout)
+        .err()
+        .expect("invalid seed");
+    assert!(matches!(
+        err,
+        RegistryError::Client {
+            source: UbgiClientError::InvalidValue { .. },
+            ..
+        }
+    ));
+    let err = registry
+        .launch(&parse_engine_spec("stub@v2").unwrap(), timeout)
+        .err()
+        .expect("unknown version");
+    assert!(matches!(err, RegistryError::UnknownEngine(_)));
+}